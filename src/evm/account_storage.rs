@@ -106,6 +106,25 @@ impl AccountStorage {
         }
     }
 
+    /// Removes an account and all its storage.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address of the account to remove.
+    ///
+    /// # Returns
+    ///
+    /// Returns the removed `Account`, or `None` if the account was not present.
+    pub fn remove_account(&mut self, address: &Address) -> Option<Account> {
+        let account = self.accounts.remove(address);
+        if account.is_some() {
+            debug!("Removed account {:x?}", address);
+        } else {
+            warn!(?address, "Tried to remove account {:x?} that was not initialized", address);
+        }
+        account
+    }
+
     /// Retrieves the account information for a given address.
     ///
     /// This function retrieves the account information associated with the specified address from
@@ -547,4 +566,23 @@ mod tests {
             "Expected None for existing account without permanent storage"
         );
     }
+
+    #[test]
+    fn test_remove_account() {
+        let mut account_storage = AccountStorage::default();
+        let address = Address::from_str("0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc").unwrap();
+        let storage = HashMap::from([(U256::from(1), U256::from(5))]);
+        account_storage.init_account(address, AccountInfo::default(), Some(storage), false);
+        account_storage.set_temp_storage(address, U256::from(2), U256::from(6));
+
+        let removed = account_storage.remove_account(&address);
+
+        assert!(removed.is_some());
+        assert!(!account_storage.account_present(&address));
+        assert_eq!(account_storage.get_storage(&address, &U256::from(1)), None);
+        assert_eq!(account_storage.get_storage(&address, &U256::from(2)), None);
+        assert!(account_storage
+            .remove_account(&address)
+            .is_none());
+    }
 }
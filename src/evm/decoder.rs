@@ -1,5 +1,5 @@
 use std::{
-    collections::{hash_map::Entry, HashMap, HashSet},
+    collections::{HashMap, HashSet},
     future::Future,
     pin::Pin,
     str::FromStr,
@@ -7,15 +7,20 @@ use std::{
 };
 
 use alloy_primitives::Address;
+use futures::{stream, StreamExt};
 use thiserror::Error;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 use tycho_client::feed::{synchronizer::ComponentWithState, FeedMessage, Header};
-use tycho_core::{dto::ProtocolStateDelta, Bytes};
+use tycho_core::{
+    dto::{ChangeType, ProtocolStateDelta},
+    Bytes,
+};
 
 use crate::{
     evm::{
-        engine_db::{update_engine, SHARED_TYCHO_DB},
+        engine_db::{tycho_db::PreCachedDB, update_engine, SHARED_TYCHO_DB},
+        protocol::vm::state::EVMPoolState,
         tycho_models::{AccountUpdate, ResponseAccount},
     },
     models::Token,
@@ -36,6 +41,8 @@ pub enum StreamDecodeError {
 struct DecoderState {
     tokens: HashMap<Bytes, Token>,
     states: HashMap<String, Box<dyn ProtocolSim>>,
+    // components of the decoded states, by id
+    components: HashMap<String, ProtocolComponent>,
     // maps contract address to the pools they affect
     contracts_map: HashMap<Bytes, HashSet<String>>,
 }
@@ -62,6 +69,7 @@ pub(super) struct TychoStreamDecoder {
     state: Arc<RwLock<DecoderState>>,
     skip_state_decode_failures: bool,
     min_token_quality: u32,
+    parallelism: usize,
     registry: HashMap<String, Box<RegistryFn>>,
     inclusion_filters: HashMap<String, FilterFn>,
 }
@@ -72,6 +80,9 @@ impl TychoStreamDecoder {
             state: Arc::new(RwLock::new(DecoderState::default())),
             skip_state_decode_failures: false,
             min_token_quality: 51,
+            parallelism: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
             registry: HashMap::new(),
             inclusion_filters: HashMap::new(),
         }
@@ -90,6 +101,12 @@ impl TychoStreamDecoder {
         self.skip_state_decode_failures = skip;
     }
 
+    /// Sets the maximum number of snapshots decoded, or states transitioned, concurrently within a
+    /// block. Defaults to the number of available CPUs.
+    pub fn parallelism(&mut self, parallelism: usize) {
+        self.parallelism = parallelism.max(1);
+    }
+
     /// Registers a decoder for a given exchange.
     ///
     /// This method maps an exchange identifier to a specific protocol simulation type.
@@ -152,6 +169,8 @@ impl TychoStreamDecoder {
         let mut new_pairs = HashMap::new();
         let mut removed_pairs = HashMap::new();
         let mut contracts_map = HashMap::new();
+        let mut deleted_accounts = HashSet::new();
+        let mut invalidated_pools = HashSet::new();
 
         let block = msg
             .state_msgs
@@ -251,6 +270,7 @@ impl TychoStreamDecoder {
             info!("Engine updated");
 
             let mut new_components = HashMap::new();
+            let mut decode_futs = Vec::new();
 
             // PROCESS SNAPSHOTS
             'outer: for (id, snapshot) in protocol_msg
@@ -302,20 +322,10 @@ impl TychoStreamDecoder {
 
                 // Construct state from snapshot
                 if let Some(state_decode_f) = self.registry.get(protocol.as_str()) {
-                    match state_decode_f(snapshot, block.clone(), self.state.clone()).await {
-                        Ok(state) => {
-                            new_components.insert(id.clone(), state);
-                        }
-                        Err(e) => {
-                            if self.skip_state_decode_failures {
-                                warn!(pool = id, error = %e, "StateDecodingFailure");
-                                continue 'outer;
-                            } else {
-                                error!(pool = id, error = %e, "StateDecodingFailure");
-                                return Err(StreamDecodeError::Fatal(format!("{e}")));
-                            }
-                        }
-                    }
+                    decode_futs.push((
+                        id.clone(),
+                        state_decode_f(snapshot, block.clone(), self.state.clone()),
+                    ));
                 } else if self.skip_state_decode_failures {
                     warn!(pool = id, "MissingDecoderRegistration");
                     continue 'outer;
@@ -327,6 +337,44 @@ impl TychoStreamDecoder {
                 }
             }
 
+            // Decode the collected snapshots concurrently. VM pools run EVM simulations while
+            // decoding, so they are decoded on the blocking thread pool.
+            let is_vm_protocol = protocol.starts_with("vm:");
+            let decoded_states = stream::iter(decode_futs)
+                .map(|(id, decode_fut)| async move {
+                    let res = if is_vm_protocol {
+                        let handle = tokio::runtime::Handle::current();
+                        match tokio::task::spawn_blocking(move || handle.block_on(decode_fut)).await
+                        {
+                            Ok(res) => res.map_err(|e| e.to_string()),
+                            Err(e) => Err(e.to_string()),
+                        }
+                    } else {
+                        decode_fut
+                            .await
+                            .map_err(|e| e.to_string())
+                    };
+                    (id, res)
+                })
+                .buffer_unordered(self.parallelism)
+                .collect::<Vec<_>>()
+                .await;
+            for (id, res) in decoded_states {
+                match res {
+                    Ok(state) => {
+                        new_components.insert(id, state);
+                    }
+                    Err(e) => {
+                        if self.skip_state_decode_failures {
+                            warn!(pool = id, error = %e, "StateDecodingFailure");
+                        } else {
+                            error!(pool = id, error = %e, "StateDecodingFailure");
+                            return Err(StreamDecodeError::Fatal(e));
+                        }
+                    }
+                }
+            }
+
             if !new_components.is_empty() {
                 info!("Decoded {} snapshots for protocol {}", new_components.len(), protocol);
             }
@@ -350,6 +398,23 @@ impl TychoStreamDecoder {
                 .await;
                 info!("Engine updated");
 
+                deleted_accounts.extend(
+                    deltas
+                        .account_updates
+                        .iter()
+                        .filter(|(_, update)| matches!(update.change, ChangeType::Deletion))
+                        .map(|(key, _)| Address::from_slice(&key[..20])),
+                );
+                // VM pools that depend on a deleted contract can't be simulated anymore, so they
+                // are invalidated instead of transitioned
+                invalidated_pools.extend(find_invalidated_pools(
+                    state_guard
+                        .states
+                        .iter()
+                        .chain(updated_states.iter()),
+                    &deleted_accounts,
+                ));
+
                 // update states related to contracts with account deltas
                 let mut pools_to_update = HashSet::new();
                 // get pools related to the updated accounts
@@ -370,93 +435,119 @@ impl TychoStreamDecoder {
                             .unwrap_or_default(),
                     );
                 }
-                // update the pools
-                for pool in pools_to_update {
-                    match updated_states.entry(pool.clone()) {
-                        Entry::Occupied(mut entry) => {
-                            // if state exists in updated_states, update it
-                            let state: &mut Box<dyn ProtocolSim> = entry.get_mut();
-                            state
-                                .delta_transition(
-                                    ProtocolStateDelta::default(),
-                                    &state_guard.tokens,
-                                )
-                                .map_err(|e| {
-                                    error!(pool = pool, error = ?e, "DeltaTransitionError");
-                                    StreamDecodeError::Fatal(format!("TransitionFailure: {e:?}"))
-                                })?;
-                        }
-                        Entry::Vacant(_) => {
-                            match state_guard.states.get(&pool) {
-                                // if state does not exist in updated_states, update the stored
-                                // state
-                                Some(stored_state) => {
-                                    let mut state = stored_state.clone();
-                                    state
-                                        .delta_transition(
-                                            ProtocolStateDelta::default(),
-                                            &state_guard.tokens,
-                                        )
-                                        .map_err(|e| {
-                                            error!(pool = pool, error = ?e, "DeltaTransitionError");
-                                            StreamDecodeError::Fatal(format!(
-                                                "TransitionFailure: {e:?}"
-                                            ))
-                                        })?;
-                                    updated_states.insert(pool.clone(), state);
-                                }
-                                None => debug!(
-                                    pool = pool,
-                                    reason = "MissingState",
-                                    "DeltaTransitionError"
-                                ),
-                            }
-                        }
+                // collect the deltas to apply per pool, keeping their order
+                let mut pending_transitions: HashMap<
+                    String,
+                    (Box<dyn ProtocolSim>, Vec<ProtocolStateDelta>),
+                > = HashMap::new();
+                let deltas_by_pool = pools_to_update
+                    .into_iter()
+                    .map(|pool| (pool, ProtocolStateDelta::default()))
+                    // update states with protocol state deltas (attribute changes etc.)
+                    .chain(deltas.state_updates);
+                for (id, delta) in deltas_by_pool {
+                    if invalidated_pools.contains(&id) {
+                        debug!(pool = id, reason = "DeletedContract", "SkippingDeltaTransition");
+                    } else if let Some((_, pool_deltas)) = pending_transitions.get_mut(&id) {
+                        pool_deltas.push(delta);
+                    } else if let Some(state) = updated_states.remove(&id) {
+                        // if state exists in updated_states, apply the delta to it
+                        pending_transitions.insert(id, (state, vec![delta]));
+                    } else if let Some(stored_state) = state_guard.states.get(&id) {
+                        // if state does not exist in updated_states, apply the delta to the
+                        // stored state
+                        pending_transitions.insert(id, (stored_state.clone(), vec![delta]));
+                    } else {
+                        debug!(pool = id, reason = "MissingState", "DeltaTransitionError");
                     }
                 }
 
-                // update states with protocol state deltas (attribute changes etc.)
-                for (id, update) in deltas.state_updates {
-                    match updated_states.entry(id.clone()) {
-                        Entry::Occupied(mut entry) => {
-                            // if state exists in updated_states, apply the delta to it
-                            let state: &mut Box<dyn ProtocolSim> = entry.get_mut();
-                            state
-                                .delta_transition(update, &state_guard.tokens)
-                                .map_err(|e| {
-                                    error!(pool = id, error = ?e, "DeltaTransitionError");
-                                    StreamDecodeError::Fatal(format!("TransitionFailure: {e:?}"))
-                                })?;
-                        }
-                        Entry::Vacant(_) => {
-                            match state_guard.states.get(&id) {
-                                // if state does not exist in updated_states, apply the delta to the
-                                // stored state
-                                Some(stored_state) => {
-                                    let mut state = stored_state.clone();
+                // apply the transitions concurrently. Transitions of VM pools run simulations
+                // (e.g. to refresh spot prices), so they run on the blocking thread pool, while
+                // the others are cheap enough to run inline.
+                let tokens = Arc::new(state_guard.tokens.clone());
+                let transitioned_states = stream::iter(pending_transitions)
+                    .map(|(id, (mut state, pool_deltas))| {
+                        let tokens = tokens.clone();
+                        async move {
+                            let is_vm_state = state
+                                .as_any()
+                                .is::<EVMPoolState<PreCachedDB>>();
+                            let transition = move || {
+                                for delta in pool_deltas {
                                     state
-                                        .delta_transition(update, &state_guard.tokens)
-                                        .map_err(|e| {
-                                            error!(pool = id, error = ?e, "DeltaTransitionError");
-                                            StreamDecodeError::Fatal(format!(
-                                                "TransitionFailure: {e:?}"
-                                            ))
-                                        })?;
-                                    updated_states.insert(id, state);
+                                        .delta_transition(delta, &tokens)
+                                        .map_err(|e| format!("{e:?}"))?;
                                 }
-                                None => debug!(
-                                    pool = id,
-                                    reason = "MissingState",
-                                    "DeltaTransitionError"
-                                ),
-                            }
+                                Ok::<_, String>(state)
+                            };
+                            let res = if is_vm_state {
+                                tokio::task::spawn_blocking(transition)
+                                    .await
+                                    .unwrap_or_else(|e| Err(e.to_string()))
+                            } else {
+                                transition()
+                            };
+                            (id, res)
+                        }
+                    })
+                    .buffer_unordered(self.parallelism)
+                    .collect::<Vec<_>>()
+                    .await;
+                for (id, res) in transitioned_states {
+                    match res {
+                        Ok(state) => {
+                            updated_states.insert(id, state);
+                        }
+                        Err(e) => {
+                            error!(pool = id, error = %e, "DeltaTransitionError");
+                            return Err(StreamDecodeError::Fatal(format!("TransitionFailure: {e}")));
                         }
                     }
                 }
             };
         }
-        // Persist the newly added/updated states
         let mut state_guard = self.state.write().await;
+
+        // Invalidate VM pools that depend on deleted contracts, including pools decoded after the
+        // deletion was seen
+        if !deleted_accounts.is_empty() {
+            invalidated_pools.extend(find_invalidated_pools(
+                state_guard
+                    .states
+                    .iter()
+                    .chain(updated_states.iter()),
+                &deleted_accounts,
+            ));
+            for id in invalidated_pools {
+                warn!(pool = id, "Invalidating pool depending on a deleted contract");
+                state_guard.states.remove(&id);
+                updated_states.remove(&id);
+                // pools added in this block were never announced, so they are just dropped
+                if new_pairs.remove(&id).is_none() {
+                    if let Some(component) = state_guard.components.remove(&id) {
+                        removed_pairs.insert(id, component);
+                    }
+                }
+            }
+            // deleted contracts won't be updated anymore
+            let is_deleted = |contract: &Bytes| {
+                contract.len() >= 20 &&
+                    deleted_accounts.contains(&Address::from_slice(&contract[..20]))
+            };
+            contracts_map.retain(|contract, _| !is_deleted(contract));
+            state_guard
+                .contracts_map
+                .retain(|contract, _| !is_deleted(contract));
+        }
+
+        // Persist the newly added/updated states
+        for id in removed_pairs.keys() {
+            state_guard.components.remove(id);
+        }
+        state_guard
+            .components
+            .extend(new_pairs.clone());
         state_guard
             .states
             .extend(updated_states.clone().into_iter());
@@ -474,6 +565,25 @@ impl TychoStreamDecoder {
     }
 }
 
+/// Returns the ids of the VM pools among `states` that depend on any of the deleted accounts.
+fn find_invalidated_pools<'a>(
+    states: impl Iterator<Item = (&'a String, &'a Box<dyn ProtocolSim>)>,
+    deleted_accounts: &HashSet<Address>,
+) -> Vec<String> {
+    if deleted_accounts.is_empty() {
+        return Vec::new();
+    }
+    states
+        .filter(|(_, state)| {
+            state
+                .as_any()
+                .downcast_ref::<EVMPoolState<PreCachedDB>>()
+                .is_some_and(|state| state.involves_any_contract(deleted_accounts))
+        })
+        .map(|(id, _)| id.clone())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use std::{fs, path::Path};
@@ -481,10 +591,18 @@ mod tests {
     use mockall::predicate::*;
     use num_bigint::ToBigUint;
     use rstest::*;
+    use tycho_core::models::Chain;
 
     use super::*;
     use crate::{
-        evm::protocol::uniswap_v2::state::UniswapV2State, models::Token,
+        evm::{
+            engine_db::{create_engine, simulation_db::BlockHeader},
+            protocol::{
+                uniswap_v2::state::UniswapV2State,
+                vm::tycho_simulation_contract::TychoSimulationContract,
+            },
+        },
+        models::Token,
         protocol::state::MockProtocolSim,
     };
 
@@ -515,16 +633,34 @@ mod tests {
         serde_json::from_str(&json_data).expect("Failed to deserialize FeedMsg json!")
     }
 
-    #[tokio::test]
-    async fn test_decode() {
-        let decoder = setup_decoder(true).await;
+    /// Loads a test message, moving the messages of uniswap_v2 to `protocol`.
+    fn load_test_msg_as(name: &str, protocol: &str) -> FeedMessage {
+        let mut msg = load_test_msg(name);
+        if let Some(state_msg) = msg.state_msgs.remove("uniswap_v2") {
+            msg.state_msgs
+                .insert(protocol.to_string(), state_msg);
+        }
+        msg
+    }
 
-        let msg = load_test_msg("uniswap_v2_snapshot");
+    #[rstest]
+    #[case::native(1, "uniswap_v2")]
+    #[case::native_parallel(4, "uniswap_v2")]
+    // Snapshots of protocols prefixed with `vm:` are decoded on the blocking thread pool
+    #[case::vm(1, "vm:uniswap_v2")]
+    #[case::vm_parallel(4, "vm:uniswap_v2")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_decode(#[case] parallelism: usize, #[case] protocol: &str) {
+        let mut decoder = setup_decoder(true).await;
+        decoder.register_decoder::<UniswapV2State>("vm:uniswap_v2");
+        decoder.parallelism(parallelism);
+
+        let msg = load_test_msg_as("uniswap_v2_snapshot", protocol);
         let res1 = decoder
             .decode(msg)
             .await
             .expect("decode failure");
-        let msg = load_test_msg("uniswap_v2_delta");
+        let msg = load_test_msg_as("uniswap_v2_delta", protocol);
         let res2 = decoder
             .decode(msg)
             .await
@@ -666,4 +802,93 @@ mod tests {
 
         // The mock framework will assert that `delta_transition` was called exactly once
     }
+
+    #[tokio::test]
+    async fn test_decode_invalidates_pool_on_contract_deletion() {
+        let decoder = setup_decoder(true).await;
+        let pool_id =
+            "0x93d199263632a4ef4bb438f1feb99e57b4b5f0bd0000000000000000000005c2".to_string();
+        // A made-up contract, so that deleting it doesn't affect other tests using the shared DB
+        let contract = Address::from_str("0x000000000000000000000000000000000000dEaD").unwrap();
+        let contract_id = Bytes::from(contract.to_vec());
+
+        // The pool can't run its transition once the contract is gone, as the adapter call
+        // would fail with a missing account
+        let state = EVMPoolState::new(
+            pool_id.clone(),
+            Vec::new(),
+            BlockHeader::default(),
+            HashMap::new(),
+            None,
+            HashMap::new(),
+            HashSet::new(),
+            HashMap::new(),
+            HashSet::from([contract]),
+            HashMap::new(),
+            true,
+            TychoSimulationContract::new(
+                Address::ZERO,
+                create_engine(SHARED_TYCHO_DB.clone(), false).unwrap(),
+            )
+            .unwrap(),
+        );
+        let component = ProtocolComponent::new(
+            Bytes::from(pool_id.as_str()),
+            "vm:balancer_v2".to_string(),
+            "balancer_v2_pool".to_string(),
+            Chain::Ethereum,
+            Vec::new(),
+            vec![contract_id.clone()],
+            HashMap::new(),
+            Bytes::default(),
+            Default::default(),
+        );
+        {
+            let mut state_guard = decoder.state.write().await;
+            state_guard
+                .states
+                .insert(pool_id.clone(), Box::new(state));
+            state_guard
+                .components
+                .insert(pool_id.clone(), component);
+            state_guard
+                .contracts_map
+                .insert(contract_id.clone(), HashSet::from([pool_id.clone()]));
+        }
+
+        // Turn the vault update of the test message into a deletion of the contract
+        let mut msg = load_test_msg("balancer_v2_delta");
+        let account_updates = &mut msg
+            .state_msgs
+            .get_mut("vm:balancer_v2")
+            .unwrap()
+            .deltas
+            .as_mut()
+            .unwrap()
+            .account_updates;
+        let mut deletion = account_updates
+            .drain()
+            .next()
+            .unwrap()
+            .1;
+        deletion.address = contract_id.clone();
+        deletion.slots.clear();
+        deletion.change = ChangeType::Deletion;
+        account_updates.insert(contract_id.clone(), deletion);
+
+        let res = decoder
+            .decode(msg)
+            .await
+            .expect("decode failure");
+
+        assert!(res.removed_pairs.contains_key(&pool_id));
+        assert!(!res.states.contains_key(&pool_id));
+        let state_guard = decoder.state.read().await;
+        assert!(!state_guard
+            .states
+            .contains_key(&pool_id));
+        assert!(!state_guard
+            .contracts_map
+            .contains_key(&contract_id));
+    }
 }
@@ -2,10 +2,8 @@ use std::{collections::HashMap, fmt::Debug};
 
 use alloy_primitives::Address;
 use lazy_static::lazy_static;
-use revm::{
-    primitives::{AccountInfo, KECCAK_EMPTY},
-    DatabaseRef,
-};
+use revm::DatabaseRef;
+use tycho_core::models::Chain;
 
 use crate::{
     evm::{
@@ -13,6 +11,7 @@ use crate::{
             engine_db_interface::EngineDatabaseInterface, simulation_db::BlockHeader,
             tycho_db::PreCachedDB,
         },
+        presets::{EnginePreset, SystemAccount},
         simulation::SimulationEngine,
         tycho_models::{AccountUpdate, ChangeType, ResponseAccount},
     },
@@ -20,6 +19,7 @@ use crate::{
 };
 
 pub mod engine_db_interface;
+pub mod rpc_cache;
 pub mod simulation_db;
 pub mod tycho_db;
 
@@ -28,12 +28,12 @@ lazy_static! {
         PreCachedDB::new().expect("Failed to create PreCachedDB");
 }
 
-/// Creates a simulation engine.
+/// Creates a simulation engine with the preset of Ethereum.
 ///
 /// # Parameters
 ///
-/// - `trace`: Whether to trace calls. Only meant for debugging purposes, might print a lot of data
-///   to stdout.
+/// - `trace`: Whether to record call traces. Traces are attached to the simulation results. Only
+///   meant for debugging purposes, as tracing slows down simulations.
 pub fn create_engine<D: EngineDatabaseInterface + Clone + Debug>(
     db: D,
     trace: bool,
@@ -42,31 +42,48 @@ where
     <D as EngineDatabaseInterface>::Error: Debug,
     <D as DatabaseRef>::Error: Debug,
 {
-    let engine = SimulationEngine::new(db.clone(), trace);
+    create_engine_with_preset(db, trace, &EnginePreset::for_chain(Chain::Ethereum)?)
+}
 
-    let zero_account_info =
-        AccountInfo { balance: Default::default(), nonce: 0, code_hash: KECCAK_EMPTY, code: None };
+/// Creates a simulation engine, initializing the system accounts and registering the precompiles
+/// of the given preset. The engine runs in the environment of the preset.
+///
+/// # Parameters
+///
+/// - `trace`: Whether to record call traces. Traces are attached to the simulation results. Only
+///   meant for debugging purposes, as tracing slows down simulations.
+/// - `preset`: The chain specific setup of the engine, see `EnginePreset::for_chain`.
+pub fn create_engine_with_preset<D: EngineDatabaseInterface + Clone + Debug>(
+    db: D,
+    trace: bool,
+    preset: &EnginePreset,
+) -> Result<SimulationEngine<D>, SimulationError>
+where
+    <D as EngineDatabaseInterface>::Error: Debug,
+    <D as DatabaseRef>::Error: Debug,
+{
+    let engine = SimulationEngine::new(db.clone(), trace)
+        .with_precompiles(preset.precompiles.clone())
+        .with_environment(preset.env.clone());
 
-    // Accounts necessary for enabling pre-compilation are initialized by default.
-    engine.state.init_account(
-        Address::from_slice(
-            &hex::decode("0000000000000000000000000000000000000000")
-                .expect("Invalid string for precompile-enabling address"),
-        ),
-        zero_account_info.clone(),
-        None,
-        false,
-    );
+    for account in &preset.system_accounts {
+        engine.state.init_account(
+            account.address,
+            account.info.clone(),
+            account.storage.clone(),
+            false,
+        );
+    }
 
-    engine.state.init_account(
-        Address::from_slice(
-            &hex::decode("0000000000000000000000000000000000000004")
-                .expect("Invalid string for precompile-enabling address"),
-        ),
-        zero_account_info.clone(),
-        None,
-        false,
-    );
+    // Calling a precompile loads its account, so it has to exist in the database.
+    for precompile in &preset.precompiles {
+        engine.state.init_account(
+            precompile.0,
+            SystemAccount::empty(precompile.0).info,
+            None,
+            false,
+        );
+    }
 
     Ok(engine)
 }
@@ -97,9 +114,8 @@ pub async fn update_engine(
         }
     }
 
-    if !vm_updates.is_empty() {
-        db.update(vm_updates.clone(), Some(block));
-    }
+    // The block is set even if there are no updates, so that its hash is tracked.
+    db.update(vm_updates.clone(), Some(block));
 
     vm_updates
 }
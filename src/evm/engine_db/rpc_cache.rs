@@ -0,0 +1,199 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use alloy_primitives::Bytes;
+use revm::{
+    interpreter::analysis::to_analysed,
+    primitives::{AccountInfo, Address, Bytecode, U256},
+};
+use serde::{Deserialize, Serialize};
+
+/// Counter used to give temporary files unique names, so concurrent writers don't interfere.
+static TMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Account info as stored on disk
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedAccount {
+    balance: U256,
+    nonce: u64,
+    code: Bytes,
+}
+
+/// On-disk cache of state fetched from an RPC node.
+///
+/// Entries are keyed by chain, block number and address (and slot for storage), so they never
+/// become stale. The layout is `<root>/<chain_id>/<block>/<address>/account.json` for account
+/// infos and `<root>/<chain_id>/<block>/<address>/<slot>` for storage slots.
+///
+/// Writes go through a temporary file and a rename, so the cache can be shared by several
+/// processes.
+#[derive(Debug, Clone)]
+pub struct RpcCache {
+    root: PathBuf,
+    chain_id: u64,
+}
+
+impl RpcCache {
+    /// Creates a cache storing its entries under `root` for the chain with id `chain_id`.
+    ///
+    /// The directory is created lazily on the first write.
+    pub fn new(root: impl Into<PathBuf>, chain_id: u64) -> Self {
+        Self { root: root.into(), chain_id }
+    }
+
+    fn account_dir(&self, block: u64, address: &Address) -> PathBuf {
+        self.root
+            .join(self.chain_id.to_string())
+            .join(block.to_string())
+            .join(format!("{:x}", address))
+    }
+
+    /// Returns the cached account info of `address` at `block`, if any.
+    pub fn get_account(&self, block: u64, address: &Address) -> io::Result<Option<AccountInfo>> {
+        let path = self
+            .account_dir(block, address)
+            .join("account.json");
+        let Some(content) = read_if_exists(&path)? else {
+            return Ok(None);
+        };
+        let account: CachedAccount = serde_json::from_slice(&content)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+        let code = to_analysed(Bytecode::new_raw(account.code));
+        Ok(Some(AccountInfo::new(account.balance, account.nonce, code.hash_slow(), code)))
+    }
+
+    /// Stores the account info of `address` at `block`.
+    pub fn put_account(&self, block: u64, address: &Address, info: &AccountInfo) -> io::Result<()> {
+        let account = CachedAccount {
+            balance: info.balance,
+            nonce: info.nonce,
+            code: info
+                .code
+                .as_ref()
+                .map(|code| code.original_bytes())
+                .unwrap_or_default(),
+        };
+        let content = serde_json::to_vec(&account)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+        write_atomic(
+            &self
+                .account_dir(block, address)
+                .join("account.json"),
+            &content,
+        )
+    }
+
+    /// Returns the cached value of storage slot `index` of `address` at `block`, if any.
+    pub fn get_storage(
+        &self,
+        block: u64,
+        address: &Address,
+        index: U256,
+    ) -> io::Result<Option<U256>> {
+        let path = self
+            .account_dir(block, address)
+            .join(format!("{:x}", index));
+        let Some(content) = read_if_exists(&path)? else {
+            return Ok(None);
+        };
+        let value = String::from_utf8(content)
+            .ok()
+            .and_then(|value| U256::from_str_radix(value.trim(), 16).ok())
+            .ok_or_else(|| {
+                io::Error::new(ErrorKind::InvalidData, format!("Invalid slot value in {:?}", path))
+            })?;
+        Ok(Some(value))
+    }
+
+    /// Stores the value of storage slot `index` of `address` at `block`.
+    pub fn put_storage(
+        &self,
+        block: u64,
+        address: &Address,
+        index: U256,
+        value: U256,
+    ) -> io::Result<()> {
+        write_atomic(
+            &self
+                .account_dir(block, address)
+                .join(format!("{:x}", index)),
+            format!("{:x}", value).as_bytes(),
+        )
+    }
+}
+
+fn read_if_exists(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    let dir = path
+        .parent()
+        .expect("Cache entries always have a parent directory");
+    fs::create_dir_all(dir)?;
+    let tmp_path = dir.join(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        TMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_rpc_cache_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = RpcCache::new(dir.path(), 1);
+        let address = Address::from_str("0x6b175474e89094c44da98b954eedeac495271d0f").unwrap();
+        let code = Bytecode::new_raw(Bytes::from_static(&[0x60, 0x00, 0x60, 0x00, 0xf3]));
+        let info = AccountInfo::new(U256::from(100), 3, code.hash_slow(), code);
+
+        assert!(cache
+            .get_account(1, &address)
+            .unwrap()
+            .is_none());
+        cache
+            .put_account(1, &address, &info)
+            .unwrap();
+        cache
+            .put_storage(1, &address, U256::from(7), U256::from(42))
+            .unwrap();
+
+        let cached = cache
+            .get_account(1, &address)
+            .unwrap()
+            .unwrap();
+        assert_eq!(cached.balance, info.balance);
+        assert_eq!(cached.nonce, info.nonce);
+        assert_eq!(cached.code_hash, info.code_hash);
+        assert_eq!(
+            cache
+                .get_storage(1, &address, U256::from(7))
+                .unwrap(),
+            Some(U256::from(42))
+        );
+        // Entries are specific to their block and chain
+        assert!(cache
+            .get_account(2, &address)
+            .unwrap()
+            .is_none());
+        assert!(RpcCache::new(dir.path(), 8453)
+            .get_storage(1, &address, U256::from(7))
+            .unwrap()
+            .is_none());
+    }
+}
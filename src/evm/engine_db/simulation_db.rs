@@ -1,30 +1,103 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::Debug,
     sync::{Arc, RwLock},
 };
 
-use alloy::providers::Provider;
+use alloy::{eips::BlockNumberOrTag, providers::Provider};
 use alloy_primitives::StorageValue;
+use futures::future::try_join_all;
 use revm::{
     db::DatabaseRef,
     interpreter::analysis::to_analysed,
-    primitives::{AccountInfo, Address, Bytecode, B256, U256},
+    primitives::{AccountInfo, Address, Bytecode, EvmState, B256, U256},
 };
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use super::{
     super::account_storage::{AccountStorage, StateUpdate},
     engine_db_interface::EngineDatabaseInterface,
+    rpc_cache::RpcCache,
 };
 
-/// A wrapper over an actual SimulationDB that allows overriding specific storage slots
+/// Per-simulation override of an account's basic info, similar to `eth_call` state overrides.
+///
+/// Fields left as `None` keep the value of the underlying database. Storage is overridden
+/// separately, slot by slot.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccountOverride {
+    /// Native token balance of the account
+    pub balance: Option<U256>,
+    /// Nonce of the account
+    pub nonce: Option<u64>,
+    /// Bytecode of the account, replacing the deployed code if any
+    pub code: Option<Bytecode>,
+}
+
+impl AccountOverride {
+    /// Overrides every field of an account, giving it the given code and neither balance nor
+    /// nonce.
+    ///
+    /// The account doesn't need to exist in the underlying database, which makes this suitable
+    /// for synthetic accounts that only exist for a simulation.
+    pub fn new_account(code: Bytecode) -> Self {
+        AccountOverride { balance: Some(U256::ZERO), nonce: Some(0), code: Some(code) }
+    }
+
+    /// Whether every field of the account is overridden, so the underlying database doesn't need
+    /// to be queried.
+    fn overrides_all(&self) -> bool {
+        self.balance.is_some() && self.nonce.is_some() && self.code.is_some()
+    }
+
+    /// Applies the override to the account info of the underlying database. `code_hash` is the
+    /// hash of the overridden code, if any.
+    fn apply(&self, info: Option<AccountInfo>, code_hash: Option<B256>) -> AccountInfo {
+        let mut info = info.unwrap_or_default();
+        if let Some(balance) = self.balance {
+            info.balance = balance;
+        }
+        if let Some(nonce) = self.nonce {
+            info.nonce = nonce;
+        }
+        if let (Some(code), Some(code_hash)) = (&self.code, code_hash) {
+            info.code_hash = code_hash;
+            info.code = Some(code.clone());
+        }
+        info
+    }
+
+    /// Restores the overridden fields of `info` to their values in `original`, so that the
+    /// override doesn't outlive the transaction it was given for.
+    fn restore(&self, info: &mut AccountInfo, original: AccountInfo) {
+        if self.balance.is_some() {
+            info.balance = original.balance;
+        }
+        if self.nonce.is_some() {
+            info.nonce = original.nonce;
+        }
+        if self.code.is_some() {
+            info.code_hash = original.code_hash;
+            info.code = original.code;
+        }
+    }
+}
+
+/// A wrapper over an actual SimulationDB that allows overriding specific storage slots and
+/// accounts
 pub struct OverriddenSimulationDB<'a, DB: DatabaseRef> {
     /// Wrapped database. Will be queried if a requested item is not found in the overrides.
     pub inner_db: &'a DB,
     /// A mapping from account address to storage.
     /// Storage is a mapping from slot index to slot value.
     pub overrides: &'a HashMap<Address, HashMap<U256, U256>>,
+    /// A mapping from account address to balance, nonce and code overrides.
+    account_overrides: Option<&'a HashMap<Address, AccountOverride>>,
+    /// Hashes of the overridden codes by account address, computed once when the overrides are
+    /// set
+    code_hashes: HashMap<Address, B256>,
+    /// Overridden codes by their hash
+    codes_by_hash: HashMap<B256, &'a Bytecode>,
 }
 
 impl<'a, DB: DatabaseRef> OverriddenSimulationDB<'a, DB> {
@@ -39,7 +112,35 @@ impl<'a, DB: DatabaseRef> OverriddenSimulationDB<'a, DB> {
     ///
     /// A new instance of OverriddenSimulationDB.
     pub fn new(inner_db: &'a DB, overrides: &'a HashMap<Address, HashMap<U256, U256>>) -> Self {
-        OverriddenSimulationDB { inner_db, overrides }
+        OverriddenSimulationDB {
+            inner_db,
+            overrides,
+            account_overrides: None,
+            code_hashes: HashMap::new(),
+            codes_by_hash: HashMap::new(),
+        }
+    }
+
+    /// Sets the balance, nonce and code overrides of accounts.
+    pub fn with_account_overrides(
+        mut self,
+        account_overrides: &'a HashMap<Address, AccountOverride>,
+    ) -> Self {
+        for (address, account_override) in account_overrides {
+            if let Some(code) = &account_override.code {
+                let code_hash = code.hash_slow();
+                self.code_hashes
+                    .insert(*address, code_hash);
+                self.codes_by_hash
+                    .insert(code_hash, code);
+            }
+        }
+        self.account_overrides = Some(account_overrides);
+        self
+    }
+
+    fn account_override(&self, address: &Address) -> Option<&'a AccountOverride> {
+        self.account_overrides?.get(address)
     }
 }
 
@@ -47,12 +148,25 @@ impl<DB: DatabaseRef> DatabaseRef for OverriddenSimulationDB<'_, DB> {
     type Error = DB::Error;
 
     fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
-        self.inner_db.basic_ref(address)
+        let Some(account_override) = self.account_override(&address) else {
+            return self.inner_db.basic_ref(address);
+        };
+        debug!(%address, "Requested overridden account {:x?}", address);
+        let info = if account_override.overrides_all() {
+            None
+        } else {
+            self.inner_db.basic_ref(address)?
+        };
+        Ok(Some(account_override.apply(info, self.code_hashes.get(&address).copied())))
     }
 
     fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
-        self.inner_db
-            .code_by_hash_ref(code_hash)
+        match self.codes_by_hash.get(&code_hash) {
+            Some(code) => Ok((*code).clone()),
+            None => self
+                .inner_db
+                .code_by_hash_ref(code_hash),
+        }
     }
 
     fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
@@ -77,10 +191,214 @@ impl<DB: DatabaseRef> DatabaseRef for OverriddenSimulationDB<'_, DB> {
     }
 }
 
+/// Account changes accumulated over a sequence of simulated transactions.
+///
+/// Used together with `OverlaySimulationDB` to carry the state changes of one transaction over to
+/// the next one without modifying the underlying database.
+#[derive(Debug, Clone, Default)]
+pub struct StateOverlay {
+    /// Latest account info of every account touched by an applied transaction.
+    accounts: HashMap<Address, AccountInfo>,
+    /// Storage slots changed by the applied transactions.
+    storage: HashMap<Address, HashMap<U256, U256>>,
+    /// Accounts whose storage was reset (created or self-destructed). Slots of these accounts not
+    /// present in `storage` are zero, regardless of the underlying database.
+    cleared_storage: HashSet<Address>,
+}
+
+impl StateOverlay {
+    /// Applies the state resulting from a transaction to the overlay.
+    ///
+    /// Only touched accounts and changed storage slots are recorded. Balances, nonces and codes
+    /// set by the `account_overrides` of the transaction are not recorded: the overridden fields
+    /// keep the value they had before the transaction, read from the overlay on top of
+    /// `inner_db`. Accounts with every field overridden are not read, their info is left as is.
+    pub fn apply<DB: DatabaseRef>(
+        &mut self,
+        state: &EvmState,
+        account_overrides: Option<&HashMap<Address, AccountOverride>>,
+        inner_db: &DB,
+    ) -> Result<(), DB::Error> {
+        for (address, account) in state {
+            if !account.is_touched() {
+                continue;
+            }
+            if account.is_selfdestructed() || account.is_created() {
+                self.storage.remove(address);
+                self.cleared_storage.insert(*address);
+            }
+            if account.is_selfdestructed() {
+                self.accounts
+                    .insert(*address, AccountInfo::default());
+                continue;
+            }
+            match account_overrides.and_then(|o| o.get(address)) {
+                Some(account_override) if account_override.overrides_all() => {}
+                Some(account_override) => {
+                    let original = OverlaySimulationDB::new(inner_db, self)
+                        .basic_ref(*address)?
+                        .unwrap_or_default();
+                    let mut info = account.info.clone();
+                    account_override.restore(&mut info, original);
+                    self.accounts.insert(*address, info);
+                }
+                None => {
+                    self.accounts
+                        .insert(*address, account.info.clone());
+                }
+            }
+            let slots = self
+                .storage
+                .entry(*address)
+                .or_default();
+            for (index, slot) in &account.storage {
+                if slot.is_changed() {
+                    slots.insert(*index, slot.present_value);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A database wrapper that serves the state recorded in a `StateOverlay` and falls back to the
+/// wrapped database for everything else.
+pub struct OverlaySimulationDB<'a, DB: DatabaseRef> {
+    /// Wrapped database. Will be queried if a requested item is not found in the overlay.
+    pub inner_db: &'a DB,
+    /// State changes applied on top of the wrapped database.
+    pub overlay: &'a StateOverlay,
+}
+
+impl<'a, DB: DatabaseRef> OverlaySimulationDB<'a, DB> {
+    /// Creates a new OverlaySimulationDB
+    pub fn new(inner_db: &'a DB, overlay: &'a StateOverlay) -> Self {
+        OverlaySimulationDB { inner_db, overlay }
+    }
+}
+
+impl<DB: DatabaseRef> DatabaseRef for OverlaySimulationDB<'_, DB> {
+    type Error = DB::Error;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        match self.overlay.accounts.get(&address) {
+            Some(info) => Ok(Some(info.clone())),
+            None => self.inner_db.basic_ref(address),
+        }
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        let overlay_code = self
+            .overlay
+            .accounts
+            .values()
+            .find(|info| info.code_hash == code_hash)
+            .and_then(|info| info.code.clone());
+        match overlay_code {
+            Some(code) => Ok(code),
+            None => self
+                .inner_db
+                .code_by_hash_ref(code_hash),
+        }
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        if let Some(value) = self
+            .overlay
+            .storage
+            .get(&address)
+            .and_then(|slots| slots.get(&index))
+        {
+            return Ok(*value);
+        }
+        if self
+            .overlay
+            .cleared_storage
+            .contains(&address)
+        {
+            return Ok(U256::ZERO);
+        }
+        self.inner_db
+            .storage_ref(address, index)
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        self.inner_db.block_hash_ref(number)
+    }
+}
+
+/// A read-only view of a `SimulationDB` that records the accounts and storage slots which are
+/// not known locally, instead of querying them from the node.
+///
+/// Created with `SimulationDB::access_recorder`.
+pub struct AccessRecorder<'a, P: Provider + Debug> {
+    db: &'a SimulationDB<P>,
+    accesses: RwLock<HashMap<Address, HashSet<U256>>>,
+}
+
+impl<P: Provider + Debug> AccessRecorder<'_, P> {
+    /// Returns the accounts and storage slots that were accessed but not known locally.
+    pub fn into_accesses(self) -> HashMap<Address, HashSet<U256>> {
+        self.accesses.into_inner().unwrap()
+    }
+}
+
+impl<P: Provider + Debug> DatabaseRef for AccessRecorder<'_, P> {
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        if let Some(account) = self
+            .db
+            .account_storage
+            .read()
+            .unwrap()
+            .get_account_info(&address)
+        {
+            return Ok(Some(account.clone()));
+        }
+        self.accesses
+            .write()
+            .unwrap()
+            .entry(address)
+            .or_default();
+        Ok(None)
+    }
+
+    fn code_by_hash_ref(&self, _code_hash: B256) -> Result<Bytecode, Self::Error> {
+        Err("Code by hash is not implemented".into())
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        let account_storage = self.db.account_storage.read().unwrap();
+        if let Some(value) = account_storage.get_storage(&address, &index) {
+            return Ok(value);
+        }
+        if account_storage.is_mocked_account(&address) != Some(true) {
+            self.accesses
+                .write()
+                .unwrap()
+                .entry(address)
+                .or_default()
+                .insert(index);
+        }
+        Ok(U256::ZERO)
+    }
+
+    /// Block hashes are not prefetched, unknown hashes read as zero.
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        Ok(self
+            .db
+            .known_block_hash(number)
+            .unwrap_or_default())
+    }
+}
+
 #[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, Default)]
 pub struct BlockHeader {
     pub number: u64,
     pub hash: B256,
+    /// Hash of the previous block. Zero if unknown.
+    pub parent_hash: B256,
     pub timestamp: u64,
 }
 
@@ -95,6 +413,10 @@ pub struct SimulationDB<P: Provider + Debug> {
     block: Option<BlockHeader>,
     /// Tokio runtime to execute async code
     pub runtime: Option<Arc<tokio::runtime::Runtime>>,
+    /// Optional on-disk cache of the data fetched from the node
+    rpc_cache: Option<RpcCache>,
+    /// Block hashes fetched from the node, by block number
+    block_hashes: Arc<RwLock<HashMap<u64, B256>>>,
 }
 
 impl<P: Provider + Debug + 'static> SimulationDB<P> {
@@ -108,6 +430,8 @@ impl<P: Provider + Debug + 'static> SimulationDB<P> {
             account_storage: Arc::new(RwLock::new(AccountStorage::new())),
             block,
             runtime,
+            rpc_cache: None,
+            block_hashes: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -116,6 +440,20 @@ impl<P: Provider + Debug + 'static> SimulationDB<P> {
         self.block = block;
     }
 
+    /// Set an on-disk cache for the data fetched from the node.
+    ///
+    /// Queried accounts and storage slots are looked up in the cache before querying the node,
+    /// and stored in it afterwards. The cache is only used if a block is set, as data fetched for
+    /// the latest block can't be keyed.
+    pub fn set_rpc_cache(&mut self, rpc_cache: Option<RpcCache>) {
+        self.rpc_cache = rpc_cache;
+    }
+
+    /// Returns the cache and block number to use for cache lookups, if any.
+    fn cache_key(&self) -> Option<(&RpcCache, u64)> {
+        Some((self.rpc_cache.as_ref()?, self.block?.number))
+    }
+
     /// Update the simulation state.
     ///
     /// Updates the underlying smart contract storage. Any previously missed account,
@@ -189,26 +527,7 @@ impl<P: Provider + Debug + 'static> SimulationDB<P> {
         &self,
         address: Address,
     ) -> Result<AccountInfo, <SimulationDB<P> as DatabaseRef>::Error> {
-        debug!("Querying account info of {:x?} at block {:?}", address, self.block);
-
-        let (balance, nonce, code) = self.block_on(async {
-            let mut balance_request = self.client.get_balance(address);
-            let mut nonce_request = self
-                .client
-                .get_transaction_count(address);
-            let mut code_request = self.client.get_code_at(address);
-
-            if let Some(block) = &self.block {
-                balance_request = balance_request.number(block.number);
-                nonce_request = nonce_request.number(block.number);
-                code_request = code_request.number(block.number);
-            }
-
-            tokio::join!(balance_request, nonce_request, code_request,)
-        });
-        let code = to_analysed(Bytecode::new_raw(revm::primitives::Bytes::copy_from_slice(&code?)));
-
-        Ok(AccountInfo::new(balance?, nonce?, code.hash_slow(), code))
+        self.block_on(self.fetch_account_info(address))
     }
 
     /// Queries a value from storage at the specified index for a given Ethereum account.
@@ -227,19 +546,180 @@ impl<P: Provider + Debug + 'static> SimulationDB<P> {
         address: Address,
         index: U256,
     ) -> Result<StorageValue, <SimulationDB<P> as DatabaseRef>::Error> {
-        let storage = self.block_on(async {
-            let mut request = self
-                .client
-                .get_storage_at(address, index);
-            if let Some(block) = &self.block {
-                request = request.number(block.number);
+        self.block_on(self.fetch_storage(address, index))
+    }
+
+    /// Fetches the accounts and storage slots accessed by a transaction concurrently, and stores
+    /// them locally.
+    ///
+    /// Accounts and slots already known locally are skipped, as well as slots of mocked accounts.
+    /// Use together with `access_recorder` to collect the accesses of a transaction without
+    /// querying the node one slot at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `accesses` - Mapping from the accessed accounts to their accessed storage slots.
+    pub fn prefetch(
+        &self,
+        accesses: &HashMap<Address, HashSet<U256>>,
+    ) -> Result<(), <SimulationDB<P> as DatabaseRef>::Error> {
+        let (missing_accounts, missing_slots) = {
+            let account_storage = self.account_storage.read().unwrap();
+            let missing_accounts = accesses
+                .keys()
+                .filter(|address| !account_storage.account_present(address))
+                .copied()
+                .collect::<Vec<_>>();
+            let missing_slots = accesses
+                .iter()
+                .filter(|(address, _)| account_storage.is_mocked_account(address) != Some(true))
+                .flat_map(|(address, slots)| {
+                    slots
+                        .iter()
+                        .map(move |index| (*address, *index))
+                })
+                .filter(|(address, index)| {
+                    account_storage
+                        .get_storage(address, index)
+                        .is_none()
+                })
+                .collect::<Vec<_>>();
+            (missing_accounts, missing_slots)
+        };
+        if missing_accounts.is_empty() && missing_slots.is_empty() {
+            return Ok(());
+        }
+        debug!(
+            "Prefetching {} accounts and {} storage slots",
+            missing_accounts.len(),
+            missing_slots.len()
+        );
+
+        let (accounts, slots) = self.block_on(async {
+            futures::try_join!(
+                try_join_all(
+                    missing_accounts
+                        .iter()
+                        .map(|address| self.fetch_account_info(*address))
+                ),
+                try_join_all(
+                    missing_slots
+                        .iter()
+                        .map(|(address, index)| self.fetch_storage(*address, *index))
+                ),
+            )
+        })?;
+
+        let mut account_storage = self.account_storage.write().unwrap();
+        for (address, account_info) in missing_accounts
+            .into_iter()
+            .zip(accounts)
+        {
+            account_storage.init_account(address, account_info, None, false);
+        }
+        for ((address, index), value) in missing_slots.into_iter().zip(slots) {
+            account_storage.set_temp_storage(address, index, value);
+        }
+        Ok(())
+    }
+
+    /// Returns a database that records the accounts and storage slots a transaction accesses,
+    /// without querying the node.
+    ///
+    /// Data known locally is served as usual, unknown accounts are treated as non-existent and
+    /// unknown slots as empty. Since control flow may depend on the missing data, the
+    /// transaction should be re-run after prefetching until no new accesses are recorded.
+    pub fn access_recorder(&self) -> AccessRecorder<'_, P> {
+        AccessRecorder { db: self, accesses: Default::default() }
+    }
+
+    async fn fetch_account_info(
+        &self,
+        address: Address,
+    ) -> Result<AccountInfo, <SimulationDB<P> as DatabaseRef>::Error> {
+        if let Some((cache, block)) = self.cache_key() {
+            match cache.get_account(block, &address) {
+                Ok(Some(account_info)) => return Ok(account_info),
+                Ok(None) => {}
+                Err(err) => warn!(?err, "Failed to read account {:x?} from RPC cache", address),
             }
-            request.await.unwrap()
-        });
+        }
+
+        debug!("Querying account info of {:x?} at block {:?}", address, self.block);
+
+        let mut balance_request = self.client.get_balance(address);
+        let mut nonce_request = self
+            .client
+            .get_transaction_count(address);
+        let mut code_request = self.client.get_code_at(address);
+
+        if let Some(block) = &self.block {
+            balance_request = balance_request.number(block.number);
+            nonce_request = nonce_request.number(block.number);
+            code_request = code_request.number(block.number);
+        }
+
+        let (balance, nonce, code) = tokio::join!(balance_request, nonce_request, code_request,);
+        let code = to_analysed(Bytecode::new_raw(revm::primitives::Bytes::copy_from_slice(&code?)));
+        let account_info = AccountInfo::new(balance?, nonce?, code.hash_slow(), code);
+
+        if let Some((cache, block)) = self.cache_key() {
+            if let Err(err) = cache.put_account(block, &address, &account_info) {
+                warn!(?err, "Failed to write account {:x?} to RPC cache", address);
+            }
+        }
+
+        Ok(account_info)
+    }
+
+    async fn fetch_storage(
+        &self,
+        address: Address,
+        index: U256,
+    ) -> Result<StorageValue, <SimulationDB<P> as DatabaseRef>::Error> {
+        if let Some((cache, block)) = self.cache_key() {
+            match cache.get_storage(block, &address, index) {
+                Ok(Some(value)) => return Ok(value),
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(?err, "Failed to read slot {} of {:x?} from RPC cache", index, address)
+                }
+            }
+        }
+
+        let mut request = self
+            .client
+            .get_storage_at(address, index);
+        if let Some(block) = &self.block {
+            request = request.number(block.number);
+        }
+        let storage = request.await?;
+
+        if let Some((cache, block)) = self.cache_key() {
+            if let Err(err) = cache.put_storage(block, &address, index, storage) {
+                warn!(?err, "Failed to write slot {} of {:x?} to RPC cache", index, address);
+            }
+        }
 
         Ok(storage)
     }
 
+    /// Returns the hash of block `number`, without querying the node.
+    fn known_block_hash(&self, number: u64) -> Option<B256> {
+        match self.block {
+            Some(header) if header.number == number => Some(header.hash),
+            Some(header) if header.number == number + 1 && header.parent_hash != B256::ZERO => {
+                Some(header.parent_hash)
+            }
+            _ => self
+                .block_hashes
+                .read()
+                .unwrap()
+                .get(&number)
+                .copied(),
+        }
+    }
+
     fn block_on<F: core::future::Future>(&self, f: F) -> F::Output {
         // If we get here and have to block the current thread, we really
         // messed up indexing / filling the storage. In that case this will save us
@@ -431,13 +911,27 @@ where
         }
     }
 
-    /// If block header is set, returns the hash. Otherwise returns a zero hash
-    /// instead of querying a node.
-    fn block_hash_ref(&self, _number: u64) -> Result<B256, Self::Error> {
-        match &self.block {
-            Some(header) => Ok(header.hash),
-            None => Ok(B256::ZERO),
+    /// Returns the hash of block `number`.
+    ///
+    /// The hash of the current block is taken from its header, other hashes are queried from the
+    /// node once and cached.
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        if let Some(hash) = self.known_block_hash(number) {
+            return Ok(hash);
         }
+        debug!("Querying hash of block {}", number);
+        let hash = self
+            .block_on(
+                self.client
+                    .get_block_by_number(BlockNumberOrTag::Number(number), false),
+            )?
+            .map(|block| block.header.hash)
+            .ok_or_else(|| format!("Block {} not found", number))?;
+        self.block_hashes
+            .write()
+            .unwrap()
+            .insert(number, hash);
+        Ok(hash)
     }
 }
 
@@ -504,6 +998,7 @@ mod tests {
             )
             .unwrap(),
             timestamp: 234,
+            ..Default::default()
         };
         db.set_block(Some(block));
         let address = Address::from_str("0x168b93113fe5902c87afaecE348581A1481d0f93").unwrap();
@@ -565,7 +1060,8 @@ mod tests {
         let update = StateUpdate { storage: Some(new_storage), balance: Some(new_balance) };
         let mut updates = HashMap::default();
         updates.insert(address, update);
-        let new_block = BlockHeader { number: 1, hash: B256::default(), timestamp: 234 };
+        let new_block =
+            BlockHeader { number: 1, hash: B256::default(), timestamp: 234, ..Default::default() };
 
         let reverse_update = db.update_state(&updates, new_block);
 
@@ -689,4 +1185,125 @@ mod tests {
             "Overridden slot of an overridden non-existent account should hold an overriden value."
         );
     }
+
+    #[rstest]
+    fn test_overridden_db_accounts() {
+        let db = SimulationDB::new(get_client(), get_runtime(), None);
+        let address1 = Address::from_str("0000000000000000000000000000000000000001").unwrap();
+        let address2 = Address::from_str("0000000000000000000000000000000000000002").unwrap();
+        let code = Bytecode::new_raw(revm::primitives::Bytes::from_static(&[0x60, 0x00]));
+        db.init_account(
+            address1,
+            AccountInfo { balance: U256::from(100), nonce: 5, ..Default::default() },
+            None,
+            false,
+        );
+        db.init_account(address2, AccountInfo::default(), None, false);
+        // Not initialized, fully overridden accounts are not queried from the node
+        let address3 = Address::from_str("0000000000000000000000000000000000000003").unwrap();
+        let account_overrides: HashMap<Address, AccountOverride> = [
+            (address1, AccountOverride { balance: Some(U256::from(1000)), ..Default::default() }),
+            (
+                address2,
+                AccountOverride { nonce: Some(1), code: Some(code.clone()), ..Default::default() },
+            ),
+            (address3, AccountOverride::new_account(code.clone())),
+        ]
+        .into_iter()
+        .collect();
+        let overrides = HashMap::new();
+
+        let overridden_db =
+            OverriddenSimulationDB::new(&db, &overrides).with_account_overrides(&account_overrides);
+
+        let info1 = overridden_db
+            .basic_ref(address1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(info1.balance, U256::from(1000));
+        assert_eq!(info1.nonce, 5, "Non-overridden fields should hold original values.");
+        let info2 = overridden_db
+            .basic_ref(address2)
+            .unwrap()
+            .unwrap();
+        assert_eq!(info2.nonce, 1);
+        assert_eq!(info2.code_hash, code.hash_slow());
+        assert_eq!(
+            overridden_db
+                .code_by_hash_ref(code.hash_slow())
+                .unwrap(),
+            code
+        );
+        let info3 = overridden_db
+            .basic_ref(address3)
+            .unwrap()
+            .unwrap();
+        assert_eq!((info3.balance, info3.nonce), (U256::ZERO, 0));
+        assert_eq!(info3.code_hash, code.hash_slow());
+    }
+
+    #[test]
+    fn test_query_from_rpc_cache() {
+        let runtime = get_runtime().unwrap();
+        // The node is unreachable, so all data has to come from the cache.
+        let client = runtime.block_on(async {
+            ProviderBuilder::new()
+                .on_builtin("http://127.0.0.1:1")
+                .await
+                .unwrap()
+        });
+        let block = BlockHeader { number: 20_000_000, ..Default::default() };
+        let dir = tempfile::tempdir().unwrap();
+        let cache = RpcCache::new(dir.path(), 1);
+        let address = Address::from_str("0x6b175474e89094c44da98b954eedeac495271d0f").unwrap();
+        cache
+            .put_account(
+                block.number,
+                &address,
+                &AccountInfo { balance: U256::from(10), ..Default::default() },
+            )
+            .unwrap();
+        cache
+            .put_storage(block.number, &address, U256::from(1), U256::from(123))
+            .unwrap();
+        let mut db = SimulationDB::new(Arc::new(client), Some(runtime), Some(block));
+        db.set_rpc_cache(Some(cache));
+
+        let account_info = db.basic_ref(address).unwrap().unwrap();
+        let slot = db
+            .storage_ref(address, U256::from(1))
+            .unwrap();
+
+        assert_eq!(account_info.balance, U256::from(10));
+        assert_eq!(slot, U256::from(123));
+    }
+
+    #[test]
+    fn test_block_hash() {
+        let runtime = get_runtime().unwrap();
+        let client = runtime.block_on(async {
+            ProviderBuilder::new()
+                .on_builtin("http://127.0.0.1:1")
+                .await
+                .unwrap()
+        });
+        let block = BlockHeader {
+            number: 20_000_000,
+            hash: B256::repeat_byte(1),
+            parent_hash: B256::repeat_byte(2),
+            timestamp: 0,
+        };
+        let db = SimulationDB::new(Arc::new(client), Some(runtime), Some(block));
+
+        assert_eq!(db.block_hash_ref(block.number).unwrap(), block.hash);
+        assert_eq!(
+            db.block_hash_ref(block.number - 1)
+                .unwrap(),
+            block.parent_hash
+        );
+        // Other hashes are queried from the node, which is unreachable here
+        assert!(db
+            .block_hash_ref(block.number - 2)
+            .is_err());
+    }
 }
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     sync::{Arc, RwLock},
 };
 
@@ -12,11 +12,14 @@ use thiserror::Error;
 use tracing::{debug, error, info, instrument, warn};
 
 use crate::evm::{
-    account_storage::{AccountStorage, StateUpdate},
+    account_storage::{Account, AccountStorage, StateUpdate},
     engine_db::{engine_db_interface::EngineDatabaseInterface, simulation_db::BlockHeader},
     tycho_models::{AccountUpdate, ChangeType},
 };
 
+/// Number of recent block hashes kept, matching the range accessible through `BLOCKHASH`.
+const BLOCK_HASH_HISTORY: u64 = 256;
+
 /// Perform bytecode analysis on the code of an account.
 pub fn to_analysed(account_info: AccountInfo) -> AccountInfo {
     AccountInfo {
@@ -45,16 +48,164 @@ pub enum PreCachedDBError {
     MissingAccount(Address),
     #[error("Block needs to be set")]
     BlockNotSet(),
+    #[error("State of block {0} is not available")]
+    BlockNotAvailable(u64),
+    #[error("Code with hash {0} not found")]
+    MissingCode(B256),
     #[error("Tycho Client error: {0}")]
     TychoClientError(#[from] TychoClientError),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct PreCachedDBInner {
     /// Storage for accounts
     accounts: AccountStorage,
     /// Current block
     block: Option<BlockHeader>,
+    /// Values overwritten by the updates of the most recent blocks, oldest first
+    history: VecDeque<BlockRevert>,
+    /// Number of past blocks whose state is kept in `history`
+    retention: usize,
+    /// Hashes of the most recent blocks, by block number
+    block_hashes: BTreeMap<u64, B256>,
+}
+
+impl PreCachedDBInner {
+    /// Returns the reverts to apply to the latest state to get the state at `block_number`, oldest
+    /// first.
+    fn reverts_to(&self, block_number: u64) -> Result<Vec<&BlockRevert>, PreCachedDBError> {
+        if self.block.map(|header| header.number) == Some(block_number) {
+            return Ok(Vec::new());
+        }
+        self.history
+            .iter()
+            .position(|revert| revert.block.map(|header| header.number) == Some(block_number))
+            .map(|start| self.history.range(start..).collect())
+            .ok_or(PreCachedDBError::BlockNotAvailable(block_number))
+    }
+
+    /// Sets the current block, starting a new revert if the block changed.
+    fn set_block(&mut self, block: Option<BlockHeader>) {
+        if let Some(new_block) = block {
+            self.record_block_hash(&new_block);
+            let is_new_block = self.block.map(|header| header.number) != Some(new_block.number);
+            if self.retention > 0 && is_new_block {
+                self.history.push_back(BlockRevert {
+                    block: self.block,
+                    applied_at: new_block.number,
+                    ..Default::default()
+                });
+                self.prune_history();
+            }
+        }
+        self.block = block;
+    }
+
+    /// Records the hashes of `block` and of its parent, dropping the hashes that are too old to be
+    /// accessed anymore.
+    fn record_block_hash(&mut self, block: &BlockHeader) {
+        self.block_hashes
+            .insert(block.number, block.hash);
+        if block.number > 0 && block.parent_hash != B256::ZERO {
+            self.block_hashes
+                .entry(block.number - 1)
+                .or_insert(block.parent_hash);
+        }
+        let latest = self
+            .block_hashes
+            .keys()
+            .next_back()
+            .copied()
+            .unwrap_or(block.number);
+        self.block_hashes
+            .retain(|number, _| number + BLOCK_HASH_HISTORY >= latest);
+    }
+
+    /// Returns the hash of block `number`, or a zero hash if the block was not recorded.
+    fn block_hash(&self, number: u64) -> B256 {
+        self.block_hashes
+            .get(&number)
+            .copied()
+            .unwrap_or_else(|| {
+                debug!(number, "Block hash not found");
+                B256::ZERO
+            })
+    }
+
+    fn prune_history(&mut self) {
+        while self.history.len() > self.retention {
+            self.history.pop_front();
+        }
+    }
+}
+
+/// The values overwritten by the updates of a block, i.e. the state of the previous block for
+/// every account and slot that changed.
+#[derive(Clone, Debug, Default)]
+struct BlockRevert {
+    /// Block whose state is restored by this revert
+    block: Option<BlockHeader>,
+    /// Number of the block whose updates are reverted
+    applied_at: u64,
+    /// Previous account infos. `None` if the account did not exist.
+    accounts: HashMap<Address, Option<AccountInfo>>,
+    /// Previous values of the updated storage slots
+    storage: HashMap<Address, HashMap<U256, U256>>,
+    /// Deleted accounts. Slots of these accounts not present in `storage` were zero.
+    cleared_storage: HashSet<Address>,
+}
+
+impl BlockRevert {
+    /// Records the current info and slot values of an account, unless already recorded in this
+    /// block.
+    fn record_update<'a>(
+        &mut self,
+        accounts: &AccountStorage,
+        address: &Address,
+        slots: impl IntoIterator<Item = &'a U256>,
+    ) {
+        let Some(info) = accounts.get_account_info(address) else {
+            return;
+        };
+        self.accounts
+            .entry(*address)
+            .or_insert_with(|| Some(info.clone()));
+        let storage = self
+            .storage
+            .entry(*address)
+            .or_default();
+        for index in slots {
+            storage
+                .entry(*index)
+                .or_insert_with(|| {
+                    accounts
+                        .get_permanent_storage(address, index)
+                        .unwrap_or_default()
+                });
+        }
+    }
+
+    fn record_creation(&mut self, accounts: &AccountStorage, address: &Address) {
+        if !accounts.account_present(address) {
+            self.accounts
+                .entry(*address)
+                .or_insert(None);
+        }
+    }
+
+    fn record_deletion(&mut self, address: &Address, account: Account) {
+        self.accounts
+            .entry(*address)
+            .or_insert(Some(account.info));
+        let storage = self
+            .storage
+            .entry(*address)
+            .or_default();
+        for (index, value) in account.permanent_storage {
+            storage.entry(index).or_insert(value);
+        }
+        self.cleared_storage.insert(*address);
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -70,12 +221,45 @@ pub struct PreCachedDB {
 impl PreCachedDB {
     /// Create a new PreCachedDB instance
     pub fn new() -> Result<Self, PreCachedDBError> {
-        Ok(PreCachedDB {
-            inner: Arc::new(RwLock::new(PreCachedDBInner {
-                accounts: AccountStorage::new(),
-                block: None,
-            })),
-        })
+        Ok(PreCachedDB { inner: Arc::new(RwLock::new(PreCachedDBInner::default())) })
+    }
+
+    /// Sets the number of past blocks whose state is kept, so that it can be read through
+    /// `at_block`. Defaults to 0, i.e. only the latest state is kept.
+    pub fn set_retention(&self, blocks: usize) {
+        let mut write_guard = self.inner.write().unwrap();
+        write_guard.retention = blocks;
+        write_guard.prune_history();
+    }
+
+    /// Returns a read-only view of the state at a past block.
+    ///
+    /// The block must be the current block or one of the last `retention` blocks the database
+    /// was updated with.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PreCachedDBError::BlockNotAvailable` if the state of the block is not kept.
+    pub fn at_block(&self, block_number: u64) -> Result<PinnedPreCachedDB, PreCachedDBError> {
+        let read_guard = self.inner.read().unwrap();
+        let block = if read_guard
+            .block
+            .map(|header| header.number) ==
+            Some(block_number)
+        {
+            read_guard.block
+        } else {
+            read_guard
+                .history
+                .iter()
+                .find_map(|revert| {
+                    revert
+                        .block
+                        .filter(|header| header.number == block_number)
+                })
+        }
+        .ok_or(PreCachedDBError::BlockNotAvailable(block_number))?;
+        Ok(PinnedPreCachedDB { db: self.clone(), block })
     }
 
     #[instrument(skip_all)]
@@ -84,16 +268,27 @@ impl PreCachedDB {
         // write to the storage.
         let mut write_guard = self.inner.write().unwrap();
 
-        write_guard.block = block;
+        write_guard.set_block(block);
+        let inner = &mut *write_guard;
+        let current_block = inner.block.map(|header| header.number);
 
         for update in account_updates {
+            // Only set if the state of past blocks is kept
+            let revert = inner
+                .history
+                .back_mut()
+                .filter(|revert| Some(revert.applied_at) == current_block);
             match update.change {
                 ChangeType::Update => {
                     info!(%update.address, "Updating account");
 
+                    if let Some(revert) = revert {
+                        revert.record_update(&inner.accounts, &update.address, update.slots.keys());
+                    }
+
                     // If the account is not present, the internal storage will handle throwing
                     // an exception.
-                    write_guard.accounts.update_account(
+                    inner.accounts.update_account(
                         &update.address,
                         &StateUpdate {
                             storage: Some(update.slots.clone()),
@@ -104,7 +299,14 @@ impl PreCachedDB {
                 ChangeType::Deletion => {
                     info!(%update.address, "Deleting account");
 
-                    warn!(%update.address, "Deletion not implemented");
+                    if let Some(account) = inner
+                        .accounts
+                        .remove_account(&update.address)
+                    {
+                        if let Some(revert) = revert {
+                            revert.record_deletion(&update.address, account);
+                        }
+                    }
                 }
                 ChangeType::Creation => {
                     info!(%update.address, "Creating account");
@@ -118,8 +320,11 @@ impl PreCachedDB {
                     ));
                     let balance = update.balance.expect("account balance");
 
+                    if let Some(revert) = revert {
+                        revert.record_creation(&inner.accounts, &update.address);
+                    }
                     // Initialize the account.
-                    write_guard.accounts.init_account(
+                    inner.accounts.init_account(
                         update.address,
                         AccountInfo::new(balance, 0, code.hash_slow(), code),
                         Some(update.slots.clone()),
@@ -176,15 +381,28 @@ impl PreCachedDB {
         let mut write_guard = self.inner.write().unwrap();
 
         let mut revert_updates = HashMap::new();
-        write_guard.block = Some(block);
+        write_guard.set_block(Some(block));
+        let inner = &mut *write_guard;
 
         for (address, update_info) in updates.iter() {
             let mut revert_entry = StateUpdate::default();
 
-            if let Some(current_account) = write_guard
-                .accounts
-                .get_account_info(address)
+            if let Some(block_revert) = inner
+                .history
+                .back_mut()
+                .filter(|revert| revert.applied_at == block.number)
             {
+                block_revert.record_update(
+                    &inner.accounts,
+                    address,
+                    update_info
+                        .storage
+                        .iter()
+                        .flat_map(HashMap::keys),
+                );
+            }
+
+            if let Some(current_account) = inner.accounts.get_account_info(address) {
                 revert_entry.balance = Some(current_account.balance);
             }
 
@@ -196,7 +414,7 @@ impl PreCachedDB {
                     .unwrap()
                     .keys()
                 {
-                    if let Some(s) = write_guard
+                    if let Some(s) = inner
                         .accounts
                         .get_storage(address, index)
                     {
@@ -206,7 +424,7 @@ impl PreCachedDB {
                 revert_entry.storage = Some(revert_storage);
             }
             revert_updates.insert(*address, revert_entry);
-            write_guard
+            inner
                 .accounts
                 .update_account(address, update_info);
         }
@@ -336,12 +554,125 @@ impl DatabaseRef for PreCachedDB {
         }
     }
 
-    /// If block header is set, returns the hash. Otherwise returns a zero hash.
-    fn block_hash_ref(&self, _number: u64) -> Result<B256, Self::Error> {
-        match self.inner.read().unwrap().block {
-            Some(header) => Ok(header.hash),
-            None => Ok(B256::default()),
+    /// Returns the hash of one of the last 256 blocks the database was updated with, or of their
+    /// parents.
+    ///
+    /// Blocks that were not seen, or are too old, have a zero hash, like blocks out of the range
+    /// of `BLOCKHASH`.
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        Ok(self
+            .inner
+            .read()
+            .unwrap()
+            .block_hash(number))
+    }
+}
+
+/// A read-only view of a `PreCachedDB` pinned to a past block.
+///
+/// Created with `PreCachedDB::at_block`. Reads fail with `PreCachedDBError::BlockNotAvailable`
+/// once the block falls out of the retention window of the database.
+#[derive(Clone, Debug)]
+pub struct PinnedPreCachedDB {
+    db: PreCachedDB,
+    block: BlockHeader,
+}
+
+impl PinnedPreCachedDB {
+    /// The block this view is pinned to
+    pub fn block(&self) -> BlockHeader {
+        self.block
+    }
+}
+
+impl EngineDatabaseInterface for PinnedPreCachedDB {
+    type Error = String;
+
+    /// The view is read-only, accounts can't be initialized through it.
+    fn init_account(
+        &self,
+        address: Address,
+        _account: AccountInfo,
+        _permanent_storage: Option<HashMap<U256, U256>>,
+        _mocked: bool,
+    ) {
+        warn!(%address, "Tried to initialize an account on a read-only pinned database");
+    }
+
+    fn clear_temp_storage(&mut self) {
+        debug!("Temp storage in TychoDB is never set, nothing to clear");
+    }
+}
+
+impl DatabaseRef for PinnedPreCachedDB {
+    type Error = PreCachedDBError;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        let read_guard = self.db.inner.read().unwrap();
+        let reverts = read_guard.reverts_to(self.block.number)?;
+        let info = match reverts
+            .iter()
+            .find_map(|revert| revert.accounts.get(&address))
+        {
+            Some(info) => info.clone(),
+            None => read_guard
+                .accounts
+                .get_account_info(&address)
+                .cloned(),
+        };
+        info.map(Some)
+            .ok_or(PreCachedDBError::MissingAccount(address))
+    }
+
+    /// Codes are always loaded along with their account, so they can't be looked up by hash.
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        Err(PreCachedDBError::MissingCode(code_hash))
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        // Fails if the account did not exist at the pinned block
+        self.basic_ref(address)?;
+        let read_guard = self.db.inner.read().unwrap();
+        for revert in read_guard.reverts_to(self.block.number)? {
+            if let Some(value) = revert
+                .storage
+                .get(&address)
+                .and_then(|storage| storage.get(&index))
+            {
+                return Ok(*value);
+            }
+            if revert
+                .cleared_storage
+                .contains(&address)
+            {
+                return Ok(U256::ZERO);
+            }
         }
+        // The slot did not change since the pinned block. As we only store non-zero values, a
+        // missing slot is zero.
+        Ok(read_guard
+            .accounts
+            .get_storage(&address, &index)
+            .unwrap_or_default())
+    }
+
+    /// Returns the hash of a block up to the pinned one, see `PreCachedDB::block_hash_ref`.
+    ///
+    /// Blocks after the pinned one have a zero hash.
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        if number == self.block.number {
+            return Ok(self.block.hash);
+        }
+        if number > self.block.number {
+            debug!(number, pinned = self.block.number, "Block hash not found");
+            return Ok(B256::ZERO);
+        }
+        Ok(self
+            .db
+            .inner
+            .read()
+            .unwrap()
+            .block_hash(number))
     }
 }
 
@@ -362,6 +693,7 @@ mod tests {
             inner: Arc::new(RwLock::new(PreCachedDBInner {
                 accounts: AccountStorage::new(),
                 block: None,
+                ..Default::default()
             })),
         }
     }
@@ -518,6 +850,7 @@ mod tests {
             inner: Arc::new(RwLock::new(PreCachedDBInner {
                 accounts: AccountStorage::new(),
                 block: None,
+                ..Default::default()
             })),
         };
 
@@ -572,6 +905,169 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_update_deletion() {
+        let mock_db = PreCachedDB::new().unwrap();
+        let address = Address::from_str("0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D").unwrap();
+        let creation = AccountUpdate::new(
+            address,
+            Chain::Ethereum,
+            HashMap::from([(U256::from(1), U256::from(5))]),
+            Some(U256::from(500)),
+            Some(Vec::<u8>::new()),
+            ChangeType::Creation,
+        );
+        mock_db.update(vec![creation], None);
+
+        let deletion = AccountUpdate::new(
+            address,
+            Chain::Ethereum,
+            HashMap::new(),
+            None,
+            None,
+            ChangeType::Deletion,
+        );
+        mock_db.update(vec![deletion], None);
+
+        assert!(matches!(mock_db.basic_ref(address), Err(PreCachedDBError::MissingAccount(_))));
+        assert_eq!(mock_db.get_storage(&address, &U256::from(1)), None);
+    }
+
+    fn block_header(number: u64) -> BlockHeader {
+        BlockHeader {
+            number,
+            hash: B256::left_padding_from(&number.to_be_bytes()),
+            timestamp: number,
+            ..Default::default()
+        }
+    }
+
+    fn slot_update(address: Address, value: u64, change: ChangeType) -> AccountUpdate {
+        AccountUpdate::new(
+            address,
+            Chain::Ethereum,
+            HashMap::from([(U256::from(1), U256::from(value))]),
+            Some(U256::from(value)),
+            Some(Vec::<u8>::new()),
+            change,
+        )
+    }
+
+    #[test]
+    fn test_at_block() {
+        let db = PreCachedDB::new().unwrap();
+        db.set_retention(2);
+        let address = Address::from_str("0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D").unwrap();
+        let created = Address::from_str("0x0000000000000000000000000000000000000002").unwrap();
+
+        db.update(vec![slot_update(address, 5, ChangeType::Creation)], Some(block_header(1)));
+        db.update(vec![slot_update(address, 6, ChangeType::Update)], Some(block_header(2)));
+        // Several updates within the same block are reverted together
+        db.update(vec![slot_update(address, 7, ChangeType::Update)], Some(block_header(3)));
+        db.update(vec![slot_update(address, 8, ChangeType::Update)], Some(block_header(3)));
+        db.update(vec![slot_update(created, 1, ChangeType::Creation)], Some(block_header(3)));
+
+        let slot_at = |block: u64| {
+            db.at_block(block)
+                .unwrap()
+                .storage_ref(address, U256::from(1))
+                .unwrap()
+        };
+        assert_eq!(slot_at(1), U256::from(5));
+        assert_eq!(slot_at(2), U256::from(6));
+        assert_eq!(slot_at(3), U256::from(8));
+        let pinned = db.at_block(1).unwrap();
+        assert_eq!(
+            pinned
+                .basic_ref(address)
+                .unwrap()
+                .unwrap()
+                .balance,
+            U256::from(5)
+        );
+        assert_eq!(pinned.block_hash_ref(1).unwrap(), block_header(1).hash);
+        // Later blocks are unknown to the pinned view
+        assert_eq!(pinned.block_hash_ref(2).unwrap(), B256::ZERO);
+        assert_eq!(
+            db.at_block(2)
+                .unwrap()
+                .block_hash_ref(1)
+                .unwrap(),
+            block_header(1).hash
+        );
+        assert!(matches!(pinned.basic_ref(created), Err(PreCachedDBError::MissingAccount(_))));
+        assert!(matches!(
+            pinned.code_by_hash_ref(B256::ZERO),
+            Err(PreCachedDBError::MissingCode(_))
+        ));
+
+        // Block 1 falls out of the retention window
+        db.update(vec![slot_update(address, 9, ChangeType::Update)], Some(block_header(4)));
+
+        assert!(matches!(db.at_block(1), Err(PreCachedDBError::BlockNotAvailable(1))));
+        assert!(matches!(
+            pinned.storage_ref(address, U256::from(1)),
+            Err(PreCachedDBError::BlockNotAvailable(1))
+        ));
+        assert_eq!(slot_at(2), U256::from(6));
+    }
+
+    #[test]
+    fn test_block_hash() {
+        let db = PreCachedDB::new().unwrap();
+        let address = Address::from_str("0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D").unwrap();
+
+        for number in 1..=300 {
+            db.update(
+                vec![slot_update(address, number, ChangeType::Update)],
+                Some(block_header(number)),
+            );
+        }
+
+        assert_eq!(db.block_hash_ref(300).unwrap(), block_header(300).hash);
+        assert_eq!(db.block_hash_ref(44).unwrap(), block_header(44).hash);
+        // Older blocks are not tracked anymore, they have a zero hash like unknown blocks
+        assert_eq!(db.block_hash_ref(43).unwrap(), B256::ZERO);
+        assert_eq!(db.block_hash_ref(301).unwrap(), B256::ZERO);
+    }
+
+    #[test]
+    fn test_block_hash_records_parent() {
+        let db = PreCachedDB::new().unwrap();
+        assert_eq!(db.block_hash_ref(1).unwrap(), B256::ZERO);
+
+        let parent_hash = B256::repeat_byte(9);
+        db.update(vec![], Some(BlockHeader { parent_hash, ..block_header(10) }));
+
+        assert_eq!(db.block_hash_ref(10).unwrap(), block_header(10).hash);
+        assert_eq!(db.block_hash_ref(9).unwrap(), parent_hash);
+    }
+
+    #[test]
+    fn test_at_block_deleted_account() {
+        let db = PreCachedDB::new().unwrap();
+        db.set_retention(1);
+        let address = Address::from_str("0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D").unwrap();
+        db.update(vec![slot_update(address, 5, ChangeType::Creation)], Some(block_header(1)));
+
+        db.update(vec![slot_update(address, 0, ChangeType::Deletion)], Some(block_header(2)));
+
+        let pinned = db.at_block(1).unwrap();
+        assert_eq!(
+            pinned
+                .storage_ref(address, U256::from(1))
+                .unwrap(),
+            U256::from(5)
+        );
+        assert_eq!(
+            pinned
+                .storage_ref(address, U256::from(2))
+                .unwrap(),
+            U256::ZERO
+        );
+        assert!(db.basic_ref(address).is_err());
+    }
+
     /// This test requires a running TychoDB instance.
     ///
     /// To run this test, start TychoDB with the following command:
@@ -0,0 +1,200 @@
+//! Decoding of well-known events emitted during simulations.
+//!
+//! Logs emitted by a simulated transaction are returned as raw `Log`s on the `SimulationResult`.
+//! This module decodes the ones we know about (ERC20 transfers and approvals, and Uniswap V2/V3
+//! pool events) so callers can inspect actual token movements.
+use alloy_primitives::{Address, I256, U256};
+use alloy_sol_types::{sol, SolEvent};
+use revm::primitives::Log;
+
+sol! {
+    event Transfer(address indexed from, address indexed to, uint256 value);
+    event Approval(address indexed owner, address indexed spender, uint256 value);
+}
+
+mod uniswap_v2 {
+    alloy_sol_types::sol! {
+        event Swap(
+            address indexed sender,
+            uint256 amount0In,
+            uint256 amount1In,
+            uint256 amount0Out,
+            uint256 amount1Out,
+            address indexed to
+        );
+        event Sync(uint112 reserve0, uint112 reserve1);
+    }
+}
+
+mod uniswap_v3 {
+    alloy_sol_types::sol! {
+        event Swap(
+            address indexed sender,
+            address indexed recipient,
+            int256 amount0,
+            int256 amount1,
+            uint160 sqrtPriceX96,
+            uint128 liquidity,
+            int24 tick
+        );
+    }
+}
+
+/// A decoded event. Each variant carries the address of the contract that emitted it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodedEvent {
+    /// ERC20 `Transfer(address,address,uint256)`
+    Transfer { token: Address, from: Address, to: Address, value: U256 },
+    /// ERC20 `Approval(address,address,uint256)`
+    Approval { token: Address, owner: Address, spender: Address, value: U256 },
+    /// Uniswap V2 `Swap(address,uint256,uint256,uint256,uint256,address)`
+    UniswapV2Swap {
+        pool: Address,
+        sender: Address,
+        amount0_in: U256,
+        amount1_in: U256,
+        amount0_out: U256,
+        amount1_out: U256,
+        to: Address,
+    },
+    /// Uniswap V2 `Sync(uint112,uint112)`
+    UniswapV2Sync { pool: Address, reserve0: U256, reserve1: U256 },
+    /// Uniswap V3 `Swap(address,address,int256,int256,uint160,uint128,int24)`
+    UniswapV3Swap {
+        pool: Address,
+        sender: Address,
+        recipient: Address,
+        amount0: I256,
+        amount1: I256,
+        sqrt_price_x96: U256,
+        liquidity: u128,
+        tick: i32,
+    },
+}
+
+/// Decodes a log into a `DecodedEvent`.
+///
+/// Returns `None` if the log does not match any of the known events.
+pub fn decode_log(log: &Log) -> Option<DecodedEvent> {
+    let topic0 = *log.data.topics().first()?;
+    let address = log.address;
+    if topic0 == Transfer::SIGNATURE_HASH {
+        let event = Transfer::decode_log_data(&log.data, true).ok()?;
+        Some(DecodedEvent::Transfer {
+            token: address,
+            from: event.from,
+            to: event.to,
+            value: event.value,
+        })
+    } else if topic0 == Approval::SIGNATURE_HASH {
+        let event = Approval::decode_log_data(&log.data, true).ok()?;
+        Some(DecodedEvent::Approval {
+            token: address,
+            owner: event.owner,
+            spender: event.spender,
+            value: event.value,
+        })
+    } else if topic0 == uniswap_v2::Swap::SIGNATURE_HASH {
+        let event = uniswap_v2::Swap::decode_log_data(&log.data, true).ok()?;
+        Some(DecodedEvent::UniswapV2Swap {
+            pool: address,
+            sender: event.sender,
+            amount0_in: event.amount0In,
+            amount1_in: event.amount1In,
+            amount0_out: event.amount0Out,
+            amount1_out: event.amount1Out,
+            to: event.to,
+        })
+    } else if topic0 == uniswap_v2::Sync::SIGNATURE_HASH {
+        let event = uniswap_v2::Sync::decode_log_data(&log.data, true).ok()?;
+        Some(DecodedEvent::UniswapV2Sync {
+            pool: address,
+            reserve0: U256::from(event.reserve0),
+            reserve1: U256::from(event.reserve1),
+        })
+    } else if topic0 == uniswap_v3::Swap::SIGNATURE_HASH {
+        let event = uniswap_v3::Swap::decode_log_data(&log.data, true).ok()?;
+        Some(DecodedEvent::UniswapV3Swap {
+            pool: address,
+            sender: event.sender,
+            recipient: event.recipient,
+            amount0: event.amount0,
+            amount1: event.amount1,
+            sqrt_price_x96: U256::from(event.sqrtPriceX96),
+            liquidity: event.liquidity,
+            tick: event.tick.as_i32(),
+        })
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use alloy_primitives::{LogData, B256};
+    use alloy_sol_types::SolValue;
+
+    use super::*;
+
+    fn address_topic(address: Address) -> B256 {
+        B256::left_padding_from(address.as_slice())
+    }
+
+    #[test]
+    fn test_decode_transfer() {
+        let token = Address::from_str("0x6b175474e89094c44da98b954eedeac495271d0f").unwrap();
+        let from = Address::from_str("0x0000000000000000000000000000000000000001").unwrap();
+        let to = Address::from_str("0x0000000000000000000000000000000000000002").unwrap();
+        let log = Log {
+            address: token,
+            data: LogData::new_unchecked(
+                vec![Transfer::SIGNATURE_HASH, address_topic(from), address_topic(to)],
+                U256::from(1000).abi_encode().into(),
+            ),
+        };
+
+        let decoded = decode_log(&log);
+
+        assert_eq!(
+            decoded,
+            Some(DecodedEvent::Transfer { token, from, to, value: U256::from(1000) })
+        );
+    }
+
+    #[test]
+    fn test_decode_uniswap_v2_sync() {
+        let pool = Address::from_str("0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc").unwrap();
+        let log = Log {
+            address: pool,
+            data: LogData::new_unchecked(
+                vec![uniswap_v2::Sync::SIGNATURE_HASH],
+                (U256::from(1), U256::from(2))
+                    .abi_encode_params()
+                    .into(),
+            ),
+        };
+
+        let decoded = decode_log(&log);
+
+        assert_eq!(
+            decoded,
+            Some(DecodedEvent::UniswapV2Sync {
+                pool,
+                reserve0: U256::from(1),
+                reserve1: U256::from(2)
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_unknown_event() {
+        let log = Log {
+            address: Address::ZERO,
+            data: LogData::new_unchecked(vec![B256::ZERO], Default::default()),
+        };
+
+        assert_eq!(decode_log(&log), None);
+    }
+}
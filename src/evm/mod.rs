@@ -4,9 +4,13 @@ use tycho_core::keccak256;
 pub mod account_storage;
 pub mod decoder;
 pub mod engine_db;
+pub mod events;
+pub mod presets;
 pub mod protocol;
 pub mod simulation;
 pub mod stream;
+#[cfg(test)]
+pub(crate) mod test_utils;
 pub mod traces;
 pub mod tycho_models;
 
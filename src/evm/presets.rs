@@ -0,0 +1,199 @@
+//! Chain specific setup of simulation engines.
+//!
+//! Besides the precompiles of the EVM spec, some chains expose additional system contracts,
+//! either as precompiles implemented by the node (e.g. `ArbSys` on Arbitrum) or as regular
+//! contracts deployed at genesis (e.g. the OP stack predeploys). An `EnginePreset` collects the
+//! environment, accounts and precompiles an engine needs to simulate contracts of a chain.
+use std::collections::HashMap;
+
+use alloy_primitives::{address, Address, U256};
+use alloy_sol_types::{sol, SolCall};
+use revm::{
+    precompile::PrecompileWithAddress,
+    primitives::{
+        AccountInfo, Bytes, Env, Precompile, PrecompileError, PrecompileOutput, PrecompileResult,
+        KECCAK_EMPTY,
+    },
+};
+use tycho_core::models::Chain;
+
+use crate::{evm::simulation::SimulationEnvironment, protocol::errors::SimulationError};
+
+sol! {
+    interface ArbSys {
+        function arbBlockNumber() external view returns (uint256);
+        function arbChainID() external view returns (uint256);
+    }
+}
+
+/// Address of the `ArbSys` precompile on Arbitrum
+pub const ARB_SYS_ADDRESS: Address = address!("0000000000000000000000000000000000000064");
+
+/// Gas charged by the `ArbSys` methods implemented here
+const ARB_SYS_GAS: u64 = 100;
+
+/// An account set up on the engine before any simulation.
+#[derive(Debug, Clone)]
+pub struct SystemAccount {
+    pub address: Address,
+    pub info: AccountInfo,
+    /// Storage of the account. It is permanent, i.e. it can only be changed manually.
+    pub storage: Option<HashMap<U256, U256>>,
+}
+
+impl SystemAccount {
+    /// An account without balance, code or storage
+    pub fn empty(address: Address) -> Self {
+        Self {
+            address,
+            info: AccountInfo {
+                balance: Default::default(),
+                nonce: 0,
+                code_hash: KECCAK_EMPTY,
+                code: None,
+            },
+            storage: None,
+        }
+    }
+}
+
+/// Environment, accounts and precompiles to set up on a simulation engine.
+///
+/// Use `EnginePreset::for_chain` to get the preset of a chain, and extend it with
+/// `with_system_account` and `with_precompile` if needed.
+#[derive(Debug, Clone)]
+pub struct EnginePreset {
+    /// Environment of the chain, see `SimulationEngine::environment`
+    pub env: SimulationEnvironment,
+    /// Accounts initialized on the engine's database when it is created
+    pub system_accounts: Vec<SystemAccount>,
+    /// Precompiles registered in addition to the ones of the EVM spec. An empty account is
+    /// initialized at their address, so that calling them doesn't require any state.
+    pub precompiles: Vec<PrecompileWithAddress>,
+}
+
+impl EnginePreset {
+    /// Returns the preset of `chain`.
+    ///
+    /// * The environment is the one of `SimulationEnvironment::try_from(chain)`.
+    /// * All chains initialize the zero address and the identity precompile (`0x04`), which are
+    ///   needed for pre-compilation.
+    /// * Arbitrum additionally registers the `ArbSys` precompile. Only `arbBlockNumber` and
+    ///   `arbChainID` are supported, calls to other methods revert.
+    ///
+    /// Predeploys of the OP stack (e.g. the L1 block info contract on Base) are regular contracts
+    /// with state that changes every block. They need to be provided by the database like any
+    /// other contract, or set with `with_system_account`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SimulationError::InvalidInput` for chains that don't execute EVM bytecode (zkSync
+    /// and Starknet).
+    pub fn for_chain(chain: Chain) -> Result<Self, SimulationError> {
+        let preset = Self {
+            env: SimulationEnvironment::try_from(chain)?,
+            system_accounts: vec![
+                SystemAccount::empty(Address::ZERO),
+                SystemAccount::empty(Address::with_last_byte(4)),
+            ],
+            precompiles: Vec::new(),
+        };
+        Ok(match chain {
+            Chain::Arbitrum => preset
+                .with_precompile(PrecompileWithAddress(ARB_SYS_ADDRESS, Precompile::Env(arb_sys))),
+            _ => preset,
+        })
+    }
+
+    /// Adds an account to initialize on the engine.
+    pub fn with_system_account(mut self, account: SystemAccount) -> Self {
+        self.system_accounts.push(account);
+        self
+    }
+
+    /// Adds a precompile to register with the EVM. It replaces any precompile of the EVM spec at
+    /// the same address.
+    pub fn with_precompile(mut self, precompile: PrecompileWithAddress) -> Self {
+        self.precompiles.push(precompile);
+        self
+    }
+}
+
+/// Implementation of the read-only methods of Arbitrum's `ArbSys` precompile that only depend
+/// on the environment.
+fn arb_sys(input: &Bytes, gas_limit: u64, env: &Env) -> PrecompileResult {
+    if gas_limit < ARB_SYS_GAS {
+        return Err(PrecompileError::OutOfGas.into());
+    }
+    let value = match input.get(..4) {
+        Some(selector) if selector == ArbSys::arbBlockNumberCall::SELECTOR => env.block.number,
+        Some(selector) if selector == ArbSys::arbChainIDCall::SELECTOR => {
+            U256::from(env.cfg.chain_id)
+        }
+        _ => return Err(PrecompileError::Other("Unsupported ArbSys method".to_string()).into()),
+    };
+    Ok(PrecompileOutput::new(ARB_SYS_GAS, Bytes::copy_from_slice(&value.to_be_bytes::<32>())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_chain() {
+        let arbitrum = EnginePreset::for_chain(Chain::Arbitrum).unwrap();
+        let base = EnginePreset::for_chain(Chain::Base).unwrap();
+
+        assert_eq!(arbitrum.env, SimulationEnvironment::try_from(Chain::Arbitrum).unwrap());
+        assert_eq!(
+            arbitrum
+                .precompiles
+                .iter()
+                .map(|precompile| precompile.0)
+                .collect::<Vec<_>>(),
+            vec![ARB_SYS_ADDRESS]
+        );
+        assert_eq!(base.env.chain_id, 8453);
+        assert!(base.precompiles.is_empty());
+        assert!(EnginePreset::for_chain(Chain::ZkSync).is_err());
+        assert!(EnginePreset::for_chain(Chain::Starknet).is_err());
+    }
+
+    #[test]
+    fn test_arb_sys() {
+        let mut env = Env::default();
+        env.block.number = U256::from(1234);
+        env.cfg.chain_id = 42161;
+
+        let block_number = arb_sys(
+            &ArbSys::arbBlockNumberCall {}
+                .abi_encode()
+                .into(),
+            1000,
+            &env,
+        )
+        .unwrap()
+        .bytes;
+        let chain_id = arb_sys(
+            &ArbSys::arbChainIDCall {}
+                .abi_encode()
+                .into(),
+            1000,
+            &env,
+        )
+        .unwrap()
+        .bytes;
+
+        assert_eq!(U256::from_be_slice(&block_number), U256::from(1234));
+        assert_eq!(U256::from_be_slice(&chain_id), U256::from(42161));
+        assert!(arb_sys(&Bytes::from_static(&[1, 2, 3, 4]), 1000, &env).is_err());
+        assert!(arb_sys(
+            &ArbSys::arbChainIDCall {}
+                .abi_encode()
+                .into(),
+            10,
+            &env
+        )
+        .is_err());
+    }
+}
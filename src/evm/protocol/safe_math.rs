@@ -15,6 +15,9 @@ pub fn safe_mul_u256(a: U256, b: U256) -> Result<U256, SimulationError> {
 }
 
 pub fn safe_div_u256(a: U256, b: U256) -> Result<U256, SimulationError> {
+    if b.is_zero() {
+        return Err(SimulationError::MathOverflow("Division by zero".to_string()));
+    }
     let res = a.checked_div(b);
     _construc_result_u256(res)
 }
@@ -31,7 +34,7 @@ pub fn safe_sub_u256(a: U256, b: U256) -> Result<U256, SimulationError> {
 
 pub fn div_mod_u256(a: U256, b: U256) -> Result<(U256, U256), SimulationError> {
     if b.is_zero() {
-        return Err(SimulationError::FatalError("Division by zero".to_string()));
+        return Err(SimulationError::MathOverflow("Division by zero".to_string()));
     }
     let result = a / b;
     let rest = a % b;
@@ -40,7 +43,7 @@ pub fn div_mod_u256(a: U256, b: U256) -> Result<(U256, U256), SimulationError> {
 
 pub fn _construc_result_u256(res: Option<U256>) -> Result<U256, SimulationError> {
     match res {
-        None => Err(SimulationError::FatalError("U256 arithmetic overflow".to_string())),
+        None => Err(SimulationError::MathOverflow("U256 arithmetic overflow".to_string())),
         Some(value) => Ok(value),
     }
 }
@@ -51,6 +54,9 @@ pub fn safe_mul_u512(a: U512, b: U512) -> Result<U512, SimulationError> {
 }
 
 pub fn safe_div_u512(a: U512, b: U512) -> Result<U512, SimulationError> {
+    if b.is_zero() {
+        return Err(SimulationError::MathOverflow("Division by zero".to_string()));
+    }
     let res = a.checked_div(b);
     _construc_result_u512(res)
 }
@@ -67,7 +73,7 @@ pub fn safe_sub_u512(a: U512, b: U512) -> Result<U512, SimulationError> {
 
 pub fn div_mod_u512(a: U512, b: U512) -> Result<(U512, U512), SimulationError> {
     if b.is_zero() {
-        return Err(SimulationError::FatalError("Division by zero".to_string()));
+        return Err(SimulationError::MathOverflow("Division by zero".to_string()));
     }
     let result = a / b;
     let rest = a % b;
@@ -76,7 +82,7 @@ pub fn div_mod_u512(a: U512, b: U512) -> Result<(U512, U512), SimulationError> {
 
 pub fn _construc_result_u512(res: Option<U512>) -> Result<U512, SimulationError> {
     match res {
-        None => Err(SimulationError::FatalError("U256 arithmetic overflow".to_string())),
+        None => Err(SimulationError::MathOverflow("U512 arithmetic overflow".to_string())),
         Some(value) => Ok(value),
     }
 }
@@ -87,6 +93,9 @@ pub fn safe_mul_i256(a: I256, b: I256) -> Result<I256, SimulationError> {
 }
 
 pub fn safe_div_i256(a: I256, b: I256) -> Result<I256, SimulationError> {
+    if b.is_zero() {
+        return Err(SimulationError::MathOverflow("Division by zero".to_string()));
+    }
     let res = a.checked_div(b);
     _construc_result_i256(res)
 }
@@ -103,7 +112,7 @@ pub fn safe_sub_i256(a: I256, b: I256) -> Result<I256, SimulationError> {
 
 pub fn _construc_result_i256(res: Option<I256>) -> Result<I256, SimulationError> {
     match res {
-        None => Err(SimulationError::FatalError("U256 arithmetic overflow".to_string())),
+        None => Err(SimulationError::MathOverflow("I256 arithmetic overflow".to_string())),
         Some(value) => Ok(value),
     }
 }
@@ -382,4 +391,18 @@ mod safe_math_tests {
             assert_eq!(res.unwrap(), expected);
         }
     }
+
+    #[rstest]
+    #[case(safe_div_u256(u256("1"), u256("0")).map(|_| ()), "Division by zero")]
+    #[case(div_mod_u512(U512::from(1), U512::ZERO).map(|_| ()), "Division by zero")]
+    #[case(safe_div_i256(i256("1"), i256("0")).map(|_| ()), "Division by zero")]
+    #[case(safe_add_u512(U512_MAX, U512::from(1)).map(|_| ()), "U512 arithmetic overflow")]
+    #[case(safe_sub_i256(I256::MIN, i256("1")).map(|_| ()), "I256 arithmetic overflow")]
+    fn test_error_messages(#[case] res: Result<(), SimulationError>, #[case] expected: &str) {
+        assert!(
+            matches!(&res, Err(SimulationError::MathOverflow(msg)) if msg == expected),
+            "{:?}",
+            res
+        );
+    }
 }
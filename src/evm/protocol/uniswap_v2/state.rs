@@ -13,8 +13,8 @@ use crate::{
     models::Token,
     protocol::{
         errors::{SimulationError, TransitionError},
-        models::GetAmountOutResult,
-        state::ProtocolSim,
+        models::{DepthLevel, GetAmountOutResult},
+        state::{check_price_move, ProtocolSim},
     },
 };
 
@@ -74,7 +74,7 @@ impl ProtocolSim for UniswapV2State {
         let reserve_buy = if zero2one { self.reserve1 } else { self.reserve0 };
 
         if reserve_sell == U256::from(0u64) || reserve_buy == U256::from(0u64) {
-            return Err(SimulationError::RecoverableError("No liquidity".to_string()));
+            return Err(SimulationError::InsufficientLiquidity { partial_result: None });
         }
 
         let amount_in_with_fee = safe_mul_u256(amount_in, U256::from(997))?;
@@ -100,6 +100,43 @@ impl ProtocolSim for UniswapV2State {
         ))
     }
 
+    fn get_depth(
+        &self,
+        token_in: &Token,
+        token_out: &Token,
+        price_move_bps: u32,
+    ) -> Result<Option<DepthLevel>, SimulationError> {
+        check_price_move(price_move_bps)?;
+        let zero2one = token_in.address < token_out.address;
+        let reserve_sell = if zero2one { self.reserve0 } else { self.reserve1 };
+        let reserve_buy = if zero2one { self.reserve1 } else { self.reserve0 };
+        if reserve_sell == U256::from(0u64) || reserve_buy == U256::from(0u64) {
+            return Ok(None);
+        }
+
+        // Selling `a` with the fee factor `g` moves the price by `m` if
+        // (x + g * a) * (x + a) = x^2 / (1 - m), where `x` is the sell reserve.
+        let fee_factor = 0.997;
+        let price_move = price_move_bps as f64 / 10_000.0;
+        let amount_factor = (((1.0 + fee_factor) * (1.0 + fee_factor) +
+            4.0 * fee_factor * price_move / (1.0 - price_move))
+            .sqrt() -
+            (1.0 + fee_factor)) /
+            (2.0 * fee_factor);
+        let scale = U256::from(1_000_000_000_000_000_000u64);
+        let amount_in = safe_div_u256(
+            safe_mul_u256(reserve_sell, U256::from((amount_factor * 1e18) as u128))?,
+            scale,
+        )?;
+        let amount_out = if amount_in == U256::from(0u64) {
+            BigUint::from(0u64)
+        } else {
+            self.get_amount_out(u256_to_biguint(amount_in), token_in, token_out)?
+                .amount
+        };
+        Ok(Some(DepthLevel { price_move_bps, amount_in: u256_to_biguint(amount_in), amount_out }))
+    }
+
     fn delta_transition(
         &mut self,
         delta: ProtocolStateDelta,
@@ -154,11 +191,40 @@ mod tests {
 
     use approx::assert_ulps_eq;
     use num_traits::One;
-    use rstest::rstest;
+    use rstest::{fixture, rstest};
     use tycho_core::hex_bytes::Bytes;
 
     use super::*;
 
+    /// State of the USDC/WETH pool, with USDC as token 0
+    #[fixture]
+    fn usdc_weth_state() -> UniswapV2State {
+        UniswapV2State::new(
+            U256::from_str("36925554990922").unwrap(),
+            U256::from_str("30314846538607556521556").unwrap(),
+        )
+    }
+
+    #[fixture]
+    fn usdc() -> Token {
+        Token::new(
+            "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+            6,
+            "USDC",
+            10_000.to_biguint().unwrap(),
+        )
+    }
+
+    #[fixture]
+    fn weth() -> Token {
+        Token::new(
+            "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2",
+            18,
+            "WETH",
+            10_000.to_biguint().unwrap(),
+        )
+    }
+
     #[rstest]
     #[case::same_dec(
         U256::from_str("6770398782322527849696614").unwrap(),
@@ -239,30 +305,19 @@ mod tests {
         let res = state.get_amount_out(amount_in, &t0, &t1);
         assert!(res.is_err());
         let err = res.err().unwrap();
-        assert!(matches!(err, SimulationError::FatalError(_)));
+        assert!(matches!(err, SimulationError::MathOverflow(_)));
     }
 
     #[rstest]
     #[case(true, 0.0008209719947624441f64)]
     #[case(false, 1218.0683462769755f64)]
-    fn test_spot_price(#[case] zero_to_one: bool, #[case] exp: f64) {
-        let state = UniswapV2State::new(
-            U256::from_str("36925554990922").unwrap(),
-            U256::from_str("30314846538607556521556").unwrap(),
-        );
-        let usdc = Token::new(
-            "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
-            6,
-            "USDC",
-            10_000.to_biguint().unwrap(),
-        );
-        let weth = Token::new(
-            "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2",
-            18,
-            "WETH",
-            10_000.to_biguint().unwrap(),
-        );
-
+    fn test_spot_price(
+        #[from(usdc_weth_state)] state: UniswapV2State,
+        usdc: Token,
+        weth: Token,
+        #[case] zero_to_one: bool,
+        #[case] exp: f64,
+    ) {
         let res = if zero_to_one {
             state.spot_price(&usdc, &weth).unwrap()
         } else {
@@ -272,13 +327,81 @@ mod tests {
         assert_ulps_eq!(res, exp);
     }
 
-    #[test]
-    fn test_fee() {
-        let state = UniswapV2State::new(
-            U256::from_str("36925554990922").unwrap(),
-            U256::from_str("30314846538607556521556").unwrap(),
-        );
+    #[rstest]
+    fn test_get_amount_out_with_price_impact(
+        #[from(usdc_weth_state)] state: UniswapV2State,
+        usdc: Token,
+        weth: Token,
+    ) {
+        let res = state
+            .get_amount_out_with_price_impact(BigUint::from(10u64).pow(18), &weth, &usdc)
+            .unwrap();
+
+        assert_eq!(res.result.amount, BigUint::from(1214374202u64));
+        assert_ulps_eq!(res.spot_price, 1218.0683462769755f64);
+        assert_ulps_eq!(res.execution_price, 1214.374202f64, max_ulps = 4);
+        assert!((res.price_impact_bps - 30.3278899600886).abs() < 1e-6);
+        assert!((res.marginal_price - 1217.98810960784).abs() < 1e-6);
+    }
 
+    #[rstest]
+    #[case::sell_weth(false, 100)]
+    #[case::sell_usdc(true, 500)]
+    fn test_get_depth(
+        #[from(usdc_weth_state)] state: UniswapV2State,
+        usdc: Token,
+        weth: Token,
+        #[case] zero_to_one: bool,
+        #[case] price_move_bps: u32,
+    ) {
+        let (token_in, token_out) = if zero_to_one { (&usdc, &weth) } else { (&weth, &usdc) };
+
+        let level = state
+            .get_depth(token_in, token_out, price_move_bps)
+            .unwrap()
+            .unwrap();
+
+        // The closed form matches the marginal price after the trade
+        let result = state
+            .get_amount_out(level.amount_in.clone(), token_in, token_out)
+            .unwrap();
+        let price_before = state
+            .spot_price(token_in, token_out)
+            .unwrap();
+        let price_after = result
+            .new_state
+            .spot_price(token_in, token_out)
+            .unwrap();
+        let price_move = (price_before - price_after) / price_before * 10_000.0;
+        assert!((price_move - price_move_bps as f64).abs() < 1e-6);
+        assert_eq!(level.amount_out, result.amount);
+    }
+
+    #[rstest]
+    fn test_liquidity_depth(
+        #[from(usdc_weth_state)] state: UniswapV2State,
+        usdc: Token,
+        weth: Token,
+    ) {
+        let depth = state
+            .liquidity_depth(&weth, &usdc, &[500, 100, 200])
+            .unwrap();
+
+        let moves: Vec<u32> = depth
+            .a_to_b
+            .iter()
+            .map(|level| level.price_move_bps)
+            .collect();
+        assert_eq!(moves, vec![100, 200, 500]);
+        assert_eq!(depth.b_to_a.len(), 3);
+        assert!(depth.a_to_b[0].amount_in < depth.a_to_b[1].amount_in);
+        assert!(state
+            .liquidity_depth(&weth, &usdc, &[10_000])
+            .is_err());
+    }
+
+    #[rstest]
+    fn test_fee(#[from(usdc_weth_state)] state: UniswapV2State) {
         let res = state.fee();
 
         assert_ulps_eq!(res, 0.003);
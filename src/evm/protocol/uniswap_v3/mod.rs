@@ -1,4 +1,5 @@
 //! Uniswap V3 Decentralized Exchange
 pub mod enums;
+pub mod position;
 pub mod state;
 pub mod tycho_decoder;
@@ -0,0 +1,53 @@
+//! Liquidity positions on Uniswap V3 pools.
+//!
+//! Positions are simulated on a `UniswapV3State` with `mint`, `burn` and `position_fees`. Like
+//! `get_amount_out`, these leave the state untouched and return the state after the change.
+use num_bigint::BigUint;
+
+use crate::protocol::state::ProtocolSim;
+
+/// Liquidity provided over a range of ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// Lower tick of the range, inclusive. Has to be aligned with the pool's tick spacing.
+    pub tick_lower: i32,
+    /// Upper tick of the range, exclusive. Has to be aligned with the pool's tick spacing.
+    pub tick_upper: i32,
+    pub liquidity: u128,
+}
+
+impl Position {
+    pub fn new(tick_lower: i32, tick_upper: i32, liquidity: u128) -> Self {
+        Position { tick_lower, tick_upper, liquidity }
+    }
+}
+
+/// Result of minting or burning a position
+///
+/// # Fields
+///
+/// * `amount0`: BigUint, the amount of token 0 paid to the pool on mints, or received from the pool
+///   on burns
+/// * `amount1`: BigUint, the amount of token 1 paid to the pool on mints, or received from the pool
+///   on burns
+/// * `new_state`: the state of the pool after the change
+#[derive(Debug)]
+pub struct PositionChangeResult {
+    pub amount0: BigUint,
+    pub amount1: BigUint,
+    pub new_state: Box<dyn ProtocolSim>,
+}
+
+/// Fees earned by a position over a sequence of swaps
+///
+/// # Fields
+///
+/// * `fees0`: BigUint, the fees earned in token 0
+/// * `fees1`: BigUint, the fees earned in token 1
+/// * `new_state`: the state of the pool after all swaps
+#[derive(Debug)]
+pub struct PositionFeesResult {
+    pub fees0: BigUint,
+    pub fees1: BigUint,
+    pub new_state: Box<dyn ProtocolSim>,
+}
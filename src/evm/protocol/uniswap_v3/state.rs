@@ -5,15 +5,19 @@ use num_bigint::BigUint;
 use tracing::trace;
 use tycho_core::{dto::ProtocolStateDelta, Bytes};
 
-use super::enums::FeeAmount;
+use super::{
+    enums::FeeAmount,
+    position::{Position, PositionChangeResult, PositionFeesResult},
+};
 use crate::{
     evm::protocol::{
         safe_math::{safe_add_u256, safe_sub_u256},
         u256_num::u256_to_biguint,
         utils::uniswap::{
             i24_be_bytes_to_i32, liquidity_math,
-            sqrt_price_math::sqrt_price_q96_to_f64,
-            swap_math,
+            solidity_math::mul_div,
+            sqrt_price_math::{get_amount0_delta, get_amount1_delta, sqrt_price_q96_to_f64},
+            swap_math, swap_to_price,
             tick_list::{TickInfo, TickList, TickListErrorKind},
             tick_math::{
                 get_sqrt_ratio_at_tick, get_tick_at_sqrt_ratio, MAX_SQRT_RATIO, MAX_TICK,
@@ -25,7 +29,7 @@ use crate::{
     models::Token,
     protocol::{
         errors::{SimulationError, TransitionError},
-        models::GetAmountOutResult,
+        models::{DepthLevel, GetAmountOutResult, SwapToPriceResult},
         state::ProtocolSim,
     },
 };
@@ -74,9 +78,21 @@ impl UniswapV3State {
         zero_for_one: bool,
         amount_specified: I256,
         sqrt_price_limit: Option<U256>,
+    ) -> Result<SwapResults, SimulationError> {
+        self.swap_with_steps(zero_for_one, amount_specified, sqrt_price_limit, |_, _| {})
+    }
+
+    /// Same as `swap`, calling `on_step` with the swap state at the start of each step, i.e. the
+    /// active tick and liquidity, and the step's computation.
+    fn swap_with_steps(
+        &self,
+        zero_for_one: bool,
+        amount_specified: I256,
+        sqrt_price_limit: Option<U256>,
+        mut on_step: impl FnMut(&SwapState, &StepComputation),
     ) -> Result<SwapResults, SimulationError> {
         if self.liquidity == 0 {
-            return Err(SimulationError::RecoverableError("No liquidity".to_string()));
+            return Err(SimulationError::InsufficientLiquidity { partial_result: None });
         }
         let price_limit = if let Some(limit) = sqrt_price_limit {
             limit
@@ -119,16 +135,20 @@ impl UniswapV3State {
                         new_state.liquidity = state.liquidity;
                         new_state.tick = state.tick;
                         new_state.sqrt_price = state.sqrt_price;
-                        return Err(SimulationError::InvalidInput(
-                            "Ticks exceeded".into(),
-                            Some(GetAmountOutResult::new(
+                        return Err(SimulationError::InsufficientLiquidity {
+                            partial_result: Some(GetAmountOutResult::new(
                                 u256_to_biguint(state.amount_calculated.abs().into_raw()),
                                 u256_to_biguint(gas_used),
                                 Box::new(new_state),
                             )),
-                        ));
+                        });
+                    }
+                    kind => {
+                        return Err(SimulationError::MathOverflow(format!(
+                            "No initialized tick found next to tick {}: {:?}",
+                            state.tick, kind
+                        )))
                     }
-                    _ => return Err(SimulationError::FatalError("Unknown error".to_string())),
                 },
             };
 
@@ -153,6 +173,7 @@ impl UniswapV3State {
                 amount_out,
                 fee_amount,
             };
+            on_step(&state, &step);
             if exact_input {
                 state.amount_remaining -= I256::checked_from_sign_and_abs(
                     Sign::Positive,
@@ -188,6 +209,7 @@ impl UniswapV3State {
             gas_used = safe_add_u256(gas_used, U256::from(2000))?;
         }
         Ok(SwapResults {
+            amount_remaining: state.amount_remaining,
             amount_calculated: state.amount_calculated,
             sqrt_price: state.sqrt_price,
             liquidity: state.liquidity,
@@ -196,6 +218,254 @@ impl UniswapV3State {
         })
     }
 
+    /// Returns the amounts needed to move the pool's price to `target_price`.
+    ///
+    /// The price is the price of `token_in` in `token_out`, as returned by `spot_price`. Selling
+    /// `token_in` lowers its price, so the target has to be below the current price.
+    ///
+    /// # Errors
+    ///
+    /// * `InvalidInput` if the target price is not below the current price or out of the range of
+    ///   valid prices.
+    /// * `InsufficientLiquidity` if the pool runs out of liquidity before reaching the price. The
+    ///   partial result contains the trade up to the last initialized tick.
+    pub fn swap_to_price(
+        &self,
+        token_in: &Token,
+        token_out: &Token,
+        target_price: f64,
+    ) -> Result<SwapToPriceResult, SimulationError> {
+        swap_to_price::swap_to_price(
+            self.sqrt_price,
+            token_in,
+            token_out,
+            target_price,
+            |zero_for_one, limit| self.swap_to_limit(zero_for_one, limit),
+        )
+    }
+
+    /// Returns the amounts needed to move the pool's sqrt price to `sqrt_price_target`.
+    ///
+    /// Exact counterpart of `swap_to_price`, taking the target as a sqrt price in Q96
+    /// representation. Selling token 0 (`zero_for_one`) lowers the sqrt price, selling token 1
+    /// raises it.
+    ///
+    /// # Errors
+    ///
+    /// Same as `swap_to_price`.
+    pub fn swap_to_sqrt_price(
+        &self,
+        zero_for_one: bool,
+        sqrt_price_target: U256,
+    ) -> Result<SwapToPriceResult, SimulationError> {
+        swap_to_price::swap_to_sqrt_price(
+            self.sqrt_price,
+            zero_for_one,
+            sqrt_price_target,
+            |zero_for_one, limit| self.swap_to_limit(zero_for_one, limit),
+        )
+    }
+
+    /// Sells an unlimited amount up to `sqrt_price_limit`, returning the results of the swap and
+    /// the state of the pool after it.
+    fn swap_to_limit(
+        &self,
+        zero_for_one: bool,
+        sqrt_price_limit: U256,
+    ) -> Result<(SwapResults, Box<dyn ProtocolSim>), SimulationError> {
+        let result = self.swap(zero_for_one, I256::MAX, Some(sqrt_price_limit))?;
+        let mut new_state = self.clone();
+        new_state.liquidity = result.liquidity;
+        new_state.tick = result.tick;
+        new_state.sqrt_price = result.sqrt_price;
+        Ok((result, Box::new(new_state)))
+    }
+
+    /// Simulates minting `position`.
+    ///
+    /// Returns the amounts of token 0 and token 1 to pay to the pool, rounded up, and the state
+    /// with the position's liquidity added.
+    ///
+    /// # Errors
+    ///
+    /// `InvalidInput` if the position's range is invalid or not aligned with the tick spacing, or
+    /// if its liquidity is zero.
+    pub fn mint(&self, position: &Position) -> Result<PositionChangeResult, SimulationError> {
+        let liquidity_delta = self.liquidity_delta(position)?;
+        self.modify_position(position, liquidity_delta)
+    }
+
+    /// Simulates burning `position`.
+    ///
+    /// Returns the amounts of token 0 and token 1 received from the pool, rounded down, and the
+    /// state with the position's liquidity removed. Fees earned by the position are not included,
+    /// see `position_fees`.
+    ///
+    /// # Errors
+    ///
+    /// `InvalidInput` if the position is invalid, or if it's in range and its liquidity exceeds
+    /// the liquidity in range. The net liquidity of a tick is shared by all the positions starting
+    /// or ending at it, so the ticks can't tell whether an out of range position exists and the
+    /// liquidity of such positions is not validated.
+    pub fn burn(&self, position: &Position) -> Result<PositionChangeResult, SimulationError> {
+        let liquidity_delta = self.liquidity_delta(position)?;
+        self.modify_position(position, -liquidity_delta)
+    }
+
+    /// Estimates the fees earned by `position` over a sequence of swaps.
+    ///
+    /// The position has to be part of the state already, e.g. by using the state returned by
+    /// `mint`. The swaps are given as the amount sold, the token sold and the token bought, and are
+    /// applied one after the other. In each step of a swap in which the position is in range, it
+    /// earns its share of the liquidity in range of the step's fees.
+    ///
+    /// # Errors
+    ///
+    /// `InvalidInput` if the position is invalid or if a swapped amount doesn't fit in an `int256`,
+    /// and any error of `get_amount_out` for the swaps.
+    pub fn position_fees(
+        &self,
+        position: &Position,
+        swaps: &[(BigUint, Token, Token)],
+    ) -> Result<PositionFeesResult, SimulationError> {
+        let liquidity = U256::from(self.liquidity_delta(position)?);
+        let mut fees = [U256::ZERO, U256::ZERO];
+        let mut state = self.clone();
+        for (amount_in, token_in, token_out) in swaps {
+            let zero_for_one = token_in < token_out;
+            let amount_specified = U256::try_from_be_slice(&amount_in.to_bytes_be())
+                .and_then(|amount| I256::checked_from_sign_and_abs(Sign::Positive, amount))
+                .ok_or_else(|| {
+                    SimulationError::InvalidInput(
+                        format!("Amount {} is too large", amount_in),
+                        None,
+                    )
+                })?;
+
+            // Fees and liquidity in range of the steps in which the position is in range
+            let mut fee_shares = Vec::new();
+            let result = state.swap_with_steps(
+                zero_for_one,
+                amount_specified,
+                None,
+                |swap_state, step| {
+                    if position.tick_lower <= swap_state.tick &&
+                        swap_state.tick < position.tick_upper &&
+                        swap_state.liquidity > 0
+                    {
+                        fee_shares.push((step.fee_amount, swap_state.liquidity));
+                    }
+                },
+            )?;
+
+            let fee_index = if zero_for_one { 0 } else { 1 };
+            for (fee_amount, liquidity_in_range) in fee_shares {
+                let share = mul_div(fee_amount, liquidity, U256::from(liquidity_in_range))?;
+                fees[fee_index] = safe_add_u256(fees[fee_index], share)?;
+            }
+            state.liquidity = result.liquidity;
+            state.tick = result.tick;
+            state.sqrt_price = result.sqrt_price;
+        }
+
+        Ok(PositionFeesResult {
+            fees0: u256_to_biguint(fees[0]),
+            fees1: u256_to_biguint(fees[1]),
+            new_state: Box::new(state),
+        })
+    }
+
+    /// Validates `position` and returns its liquidity as a liquidity delta.
+    fn liquidity_delta(&self, position: &Position) -> Result<i128, SimulationError> {
+        let spacing = self.ticks.tick_spacing() as i32;
+        if position.tick_lower >= position.tick_upper ||
+            position.tick_lower < MIN_TICK ||
+            position.tick_upper > MAX_TICK
+        {
+            return Err(SimulationError::InvalidInput(
+                format!("Invalid tick range [{}, {})", position.tick_lower, position.tick_upper),
+                None,
+            ));
+        }
+        if position.tick_lower % spacing != 0 || position.tick_upper % spacing != 0 {
+            return Err(SimulationError::InvalidInput(
+                format!(
+                    "Ticks {} and {} are not aligned with tick spacing {}",
+                    position.tick_lower, position.tick_upper, spacing
+                ),
+                None,
+            ));
+        }
+        match i128::try_from(position.liquidity) {
+            Ok(liquidity) if liquidity > 0 => Ok(liquidity),
+            _ => Err(SimulationError::InvalidInput(
+                format!("Invalid position liquidity {}", position.liquidity),
+                None,
+            )),
+        }
+    }
+
+    /// Applies a liquidity change to the position's range, following `Pool._modifyPosition` of
+    /// the pool contract.
+    fn modify_position(
+        &self,
+        position: &Position,
+        liquidity_delta: i128,
+    ) -> Result<PositionChangeResult, SimulationError> {
+        let round_up = liquidity_delta > 0;
+        let sqrt_price_lower = get_sqrt_ratio_at_tick(position.tick_lower)?;
+        let sqrt_price_upper = get_sqrt_ratio_at_tick(position.tick_upper)?;
+        let mut new_state = self.clone();
+
+        let (amount0, amount1) = if self.tick < position.tick_lower {
+            // The range is above the current price, it only holds token 0
+            let amount0 = get_amount0_delta(
+                sqrt_price_lower,
+                sqrt_price_upper,
+                position.liquidity,
+                round_up,
+            )?;
+            (amount0, U256::ZERO)
+        } else if self.tick < position.tick_upper {
+            if liquidity_delta < 0 && self.liquidity < position.liquidity {
+                return Err(SimulationError::InvalidInput(
+                    format!(
+                        "Can't burn liquidity {} exceeding the liquidity in range {}",
+                        position.liquidity, self.liquidity
+                    ),
+                    None,
+                ));
+            }
+            new_state.liquidity =
+                liquidity_math::add_liquidity_delta(self.liquidity, liquidity_delta);
+            let amount0 =
+                get_amount0_delta(self.sqrt_price, sqrt_price_upper, position.liquidity, round_up)?;
+            let amount1 =
+                get_amount1_delta(sqrt_price_lower, self.sqrt_price, position.liquidity, round_up)?;
+            (amount0, amount1)
+        } else {
+            // The range is below the current price, it only holds token 1
+            let amount1 = get_amount1_delta(
+                sqrt_price_lower,
+                sqrt_price_upper,
+                position.liquidity,
+                round_up,
+            )?;
+            (U256::ZERO, amount1)
+        };
+        new_state.ticks.apply_liquidity_change(
+            position.tick_lower,
+            position.tick_upper,
+            liquidity_delta,
+        );
+
+        Ok(PositionChangeResult {
+            amount0: u256_to_biguint(amount0),
+            amount1: u256_to_biguint(amount1),
+            new_state: Box::new(new_state),
+        })
+    }
+
     fn get_sqrt_ratio_target(
         sqrt_price_next: U256,
         sqrt_price_limit: U256,
@@ -262,6 +532,21 @@ impl ProtocolSim for UniswapV3State {
         ))
     }
 
+    fn get_depth(
+        &self,
+        token_in: &Token,
+        token_out: &Token,
+        price_move_bps: u32,
+    ) -> Result<Option<DepthLevel>, SimulationError> {
+        swap_to_price::get_depth(
+            self.sqrt_price,
+            token_in,
+            token_out,
+            price_move_bps,
+            |zero_for_one, limit| self.swap_to_limit(zero_for_one, limit),
+        )
+    }
+
     fn delta_transition(
         &mut self,
         delta: ProtocolStateDelta,
@@ -382,32 +667,16 @@ mod tests {
     };
 
     use num_bigint::ToBigUint;
+    use rstest::rstest;
     use tycho_core::hex_bytes::Bytes;
 
     use super::*;
+    use crate::evm::test_utils::{assert_close, token_x, token_y};
 
     #[test]
     fn test_get_amount_out_full_range_liquidity() {
-        let token_x = Token::new(
-            "0x6b175474e89094c44da98b954eedeac495271d0f",
-            18,
-            "X",
-            10_000.to_biguint().unwrap(),
-        );
-        let token_y = Token::new(
-            "0xf1ca9cb74685755965c7458528a36934df52a3ef",
-            18,
-            "Y",
-            10_000.to_biguint().unwrap(),
-        );
-
-        let pool = UniswapV3State::new(
-            8330443394424070888454257,
-            U256::from_str("188562464004052255423565206602").unwrap(),
-            FeeAmount::Medium,
-            17342,
-            vec![TickInfo::new(0, 0), TickInfo::new(46080, 0)],
-        );
+        let (token_x, token_y) = (token_x(), token_y());
+        let pool = wide_range_pool();
         let sell_amount = BigUint::from_str("11_000_000000000000000000").unwrap();
         let expected = BigUint::from_str("61927070842678722935941").unwrap();
 
@@ -424,6 +693,32 @@ mod tests {
         exp: BigUint,
     }
 
+    #[test]
+    fn test_get_depth() {
+        let (token_x, token_y) = (token_x(), token_y());
+        let pool = wide_range_pool();
+        let spot_price = pool
+            .spot_price(&token_x, &token_y)
+            .unwrap();
+
+        let level = pool
+            .get_depth(&token_x, &token_y, 100)
+            .unwrap()
+            .unwrap();
+        let res = pool
+            .get_amount_out(level.amount_in, &token_x, &token_y)
+            .unwrap();
+        let new_price = res
+            .new_state
+            .spot_price(&token_x, &token_y)
+            .unwrap();
+
+        // Swapping the amount in without the price limit may round differently
+        assert_close(&res.amount, &level.amount_out, 1);
+        let price_move_bps = (1.0 - new_price / spot_price) * 10_000.0;
+        assert!((price_move_bps - 100.0).abs() < 1e-3, "moved {price_move_bps} bps");
+    }
+
     #[test]
     fn test_get_amount_out() {
         let wbtc = Token::new(
@@ -570,25 +865,206 @@ mod tests {
             .unwrap_err();
 
         match err {
-            SimulationError::InvalidInput(ref _err, ref amount_out_result) => {
-                match amount_out_result {
-                    Some(amount_out_result) => {
-                        assert_eq!(amount_out_result.amount, exp);
-                        let new_state = amount_out_result
-                            .new_state
-                            .as_any()
-                            .downcast_ref::<UniswapV3State>()
-                            .unwrap();
-                        assert_ne!(new_state.tick, pool.tick);
-                        assert_ne!(new_state.liquidity, pool.liquidity);
-                    }
-                    _ => panic!("Partial amount out result is None. Expected partial result."),
+            SimulationError::InsufficientLiquidity { ref partial_result } => match partial_result {
+                Some(amount_out_result) => {
+                    assert_eq!(amount_out_result.amount, exp);
+                    let new_state = amount_out_result
+                        .new_state
+                        .as_any()
+                        .downcast_ref::<UniswapV3State>()
+                        .unwrap();
+                    assert_ne!(new_state.tick, pool.tick);
+                    assert_ne!(new_state.liquidity, pool.liquidity);
                 }
-            }
-            _ => panic!("Test failed: was expecting a SimulationError::InsufficientData"),
+                _ => panic!("Partial amount out result is None. Expected partial result."),
+            },
+            _ => panic!("Test failed: was expecting a SimulationError::InsufficientLiquidity"),
         }
     }
 
+    #[test]
+    fn test_swap_to_price() {
+        let (token_x, token_y) = (token_x(), token_y());
+        let pool = wide_range_pool();
+        let target_price = pool
+            .spot_price(&token_x, &token_y)
+            .unwrap() *
+            0.98;
+
+        let res = pool
+            .swap_to_price(&token_x, &token_y, target_price)
+            .unwrap();
+        let new_price = res
+            .new_state
+            .spot_price(&token_x, &token_y)
+            .unwrap();
+        let amount_out = pool
+            .get_amount_out(res.amount_in.clone(), &token_x, &token_y)
+            .unwrap()
+            .amount;
+
+        assert!((new_price / target_price - 1.0).abs() < 1e-9);
+        // Swapping the amount in without the price limit may round differently
+        assert_close(&amount_out, &res.amount_out, 1);
+        assert!(matches!(
+            pool.swap_to_price(&token_x, &token_y, target_price / 0.9),
+            Err(SimulationError::InvalidInput(_, None))
+        ));
+        assert!(matches!(
+            pool.swap_to_price(&token_x, &token_y, 0.0),
+            Err(SimulationError::InvalidInput(_, None))
+        ));
+    }
+
+    /// Pool with liquidity in range and ticks 0 and 46080 initialized without net liquidity, so
+    /// crossing them doesn't change the liquidity in range
+    fn wide_range_pool() -> UniswapV3State {
+        UniswapV3State::new(
+            8330443394424070888454257,
+            U256::from_str("188562464004052255423565206602").unwrap(),
+            FeeAmount::Medium,
+            17342,
+            vec![TickInfo::new(0, 0), TickInfo::new(46080, 0)],
+        )
+    }
+
+    fn downcast(state: &dyn ProtocolSim) -> &UniswapV3State {
+        state
+            .as_any()
+            .downcast_ref::<UniswapV3State>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_mint_burn() {
+        let pool = wide_range_pool();
+        let position = Position::new(17280, 17400, 1_000_000_000_000_000_000);
+
+        let minted = pool.mint(&position).unwrap();
+        let minted_state = downcast(minted.new_state.as_ref());
+        let burned = minted_state.burn(&position).unwrap();
+
+        assert!(minted.amount0 > BigUint::ZERO);
+        assert!(minted.amount1 > BigUint::ZERO);
+        assert_eq!(minted_state.liquidity, pool.liquidity + position.liquidity);
+        assert_eq!(
+            minted_state
+                .ticks
+                .get_tick(17280)
+                .unwrap()
+                .net_liquidity,
+            position.liquidity as i128
+        );
+        // Mints round up and burns round down
+        assert!(&minted.amount0 - &burned.amount0 <= BigUint::from(1u32));
+        assert!(&minted.amount1 - &burned.amount1 <= BigUint::from(1u32));
+        assert_eq!(downcast(burned.new_state.as_ref()), &pool);
+    }
+
+    #[rstest]
+    #[case::above_price(Position::new(18000, 18060, 1_000_000), true, false)]
+    #[case::below_price(Position::new(16000, 16080, 1_000_000), false, true)]
+    fn test_mint_out_of_range(
+        #[case] position: Position,
+        #[case] has_amount0: bool,
+        #[case] has_amount1: bool,
+    ) {
+        let pool = wide_range_pool();
+
+        let minted = pool.mint(&position).unwrap();
+
+        assert_eq!(minted.amount0 > BigUint::ZERO, has_amount0);
+        assert_eq!(minted.amount1 > BigUint::ZERO, has_amount1);
+        assert_eq!(
+            downcast(minted.new_state.as_ref()).liquidity,
+            pool.liquidity,
+            "liquidity in range changed"
+        );
+    }
+
+    #[rstest]
+    #[case::empty_range(Position::new(17400, 17400, 1))]
+    #[case::inverted_range(Position::new(17400, 17280, 1))]
+    #[case::unaligned(Position::new(17281, 17400, 1))]
+    #[case::out_of_bounds(Position::new(MIN_TICK - 60, 17400, 1))]
+    #[case::zero_liquidity(Position::new(17280, 17400, 0))]
+    fn test_mint_invalid_position(#[case] position: Position) {
+        let pool = wide_range_pool();
+
+        assert!(matches!(pool.mint(&position), Err(SimulationError::InvalidInput(_, None))));
+    }
+
+    #[test]
+    fn test_burn_exceeding_liquidity() {
+        let pool = wide_range_pool();
+
+        let res = pool.burn(&Position::new(17280, 17400, pool.liquidity + 1));
+
+        assert!(matches!(res, Err(SimulationError::InvalidInput(_, None))));
+    }
+
+    #[test]
+    fn test_position_fees() {
+        let (token_x, token_y) = (token_x(), token_y());
+        let pool = wide_range_pool();
+        // Provides as much liquidity as the pool, so it earns half of the fees
+        let position = Position::new(0, 46080, pool.liquidity);
+        let out_of_range = Position::new(30000, 30060, 1_000_000);
+        let minted = pool.mint(&position).unwrap();
+        let state = downcast(minted.new_state.as_ref())
+            .mint(&out_of_range)
+            .unwrap()
+            .new_state;
+        let state = downcast(state.as_ref());
+        let sell_x = BigUint::from_str("1_000_000000000000000000").unwrap();
+        let sell_y = BigUint::from_str("2_000_000000000000000000").unwrap();
+        let swaps = vec![
+            (sell_x.clone(), token_x.clone(), token_y.clone()),
+            (sell_y.clone(), token_y.clone(), token_x.clone()),
+        ];
+
+        let fees = state
+            .position_fees(&position, &swaps)
+            .unwrap();
+        let no_fees = state
+            .position_fees(&out_of_range, &swaps)
+            .unwrap();
+
+        let expected_fees0 = &sell_x * 3u32 / 1000u32 / 2u32;
+        let expected_fees1 = &sell_y * 3u32 / 1000u32 / 2u32;
+        // Fees are rounded in each step of the swaps
+        assert_close(&fees.fees0, &expected_fees0, 10);
+        assert_close(&fees.fees1, &expected_fees1, 10);
+        assert_eq!(no_fees.fees0, BigUint::ZERO);
+        assert_eq!(no_fees.fees1, BigUint::ZERO);
+        let after_first_swap = state
+            .get_amount_out(sell_x, &token_x, &token_y)
+            .unwrap()
+            .new_state;
+        let expected_state = after_first_swap
+            .get_amount_out(sell_y, &token_y, &token_x)
+            .unwrap()
+            .new_state;
+        assert!(fees
+            .new_state
+            .eq(expected_state.as_ref()));
+    }
+
+    #[rstest]
+    #[case::above_int256(BigUint::from(1u32) << 255u32)]
+    #[case::above_uint256(BigUint::from(1u32) << 256u32)]
+    fn test_position_fees_amount_too_large(#[case] amount: BigUint) {
+        let (token_x, token_y) = (token_x(), token_y());
+        let pool = wide_range_pool();
+        let position = Position::new(0, 46080, 1_000_000);
+        let minted = pool.mint(&position).unwrap();
+
+        let res = downcast(minted.new_state.as_ref())
+            .position_fees(&position, &[(amount, token_x, token_y)]);
+
+        assert!(matches!(res, Err(SimulationError::InvalidInput(_, None))));
+    }
+
     #[test]
     fn test_delta_transition() {
         let mut pool = UniswapV3State::new(
@@ -12,7 +12,7 @@ use crate::{
         utils::uniswap::{
             i24_be_bytes_to_i32, liquidity_math,
             sqrt_price_math::sqrt_price_q96_to_f64,
-            swap_math,
+            swap_math, swap_to_price,
             tick_list::{TickInfo, TickList, TickListErrorKind},
             tick_math::{
                 get_sqrt_ratio_at_tick, get_tick_at_sqrt_ratio, MAX_SQRT_RATIO, MAX_TICK,
@@ -24,7 +24,7 @@ use crate::{
     models::Token,
     protocol::{
         errors::{SimulationError, TransitionError},
-        models::GetAmountOutResult,
+        models::{DepthLevel, GetAmountOutResult, SwapToPriceResult},
         state::ProtocolSim,
     },
 };
@@ -87,7 +87,7 @@ impl UniswapV4State {
         sqrt_price_limit: Option<U256>,
     ) -> Result<SwapResults, SimulationError> {
         if self.liquidity == 0 {
-            return Err(SimulationError::RecoverableError("No liquidity".to_string()));
+            return Err(SimulationError::InsufficientLiquidity { partial_result: None });
         }
         let price_limit = if let Some(limit) = sqrt_price_limit {
             limit
@@ -130,16 +130,20 @@ impl UniswapV4State {
                         new_state.liquidity = state.liquidity;
                         new_state.tick = state.tick;
                         new_state.sqrt_price = state.sqrt_price;
-                        return Err(SimulationError::InvalidInput(
-                            "Ticks exceeded".into(),
-                            Some(GetAmountOutResult::new(
+                        return Err(SimulationError::InsufficientLiquidity {
+                            partial_result: Some(GetAmountOutResult::new(
                                 u256_to_biguint(state.amount_calculated.abs().into_raw()),
                                 u256_to_biguint(gas_used),
                                 Box::new(new_state),
                             )),
-                        ));
+                        });
+                    }
+                    kind => {
+                        return Err(SimulationError::MathOverflow(format!(
+                            "No initialized tick found next to tick {}: {:?}",
+                            state.tick, kind
+                        )))
                     }
-                    _ => return Err(SimulationError::FatalError("Unknown error".to_string())),
                 },
             };
 
@@ -200,6 +204,7 @@ impl UniswapV4State {
             gas_used = safe_add_u256(gas_used, U256::from(2000))?;
         }
         Ok(SwapResults {
+            amount_remaining: state.amount_remaining,
             amount_calculated: state.amount_calculated,
             sqrt_price: state.sqrt_price,
             liquidity: state.liquidity,
@@ -208,6 +213,69 @@ impl UniswapV4State {
         })
     }
 
+    /// Returns the amounts needed to move the pool's price to `target_price`.
+    ///
+    /// The price is the price of `token_in` in `token_out`, as returned by `spot_price`. Selling
+    /// `token_in` lowers its price, so the target has to be below the current price.
+    ///
+    /// # Errors
+    ///
+    /// * `InvalidInput` if the target price is not below the current price or out of the range of
+    ///   valid prices.
+    /// * `InsufficientLiquidity` if the pool runs out of liquidity before reaching the price. The
+    ///   partial result contains the trade up to the last initialized tick.
+    pub fn swap_to_price(
+        &self,
+        token_in: &Token,
+        token_out: &Token,
+        target_price: f64,
+    ) -> Result<SwapToPriceResult, SimulationError> {
+        swap_to_price::swap_to_price(
+            self.sqrt_price,
+            token_in,
+            token_out,
+            target_price,
+            |zero_for_one, limit| self.swap_to_limit(zero_for_one, limit),
+        )
+    }
+
+    /// Returns the amounts needed to move the pool's sqrt price to `sqrt_price_target`.
+    ///
+    /// Exact counterpart of `swap_to_price`, taking the target as a sqrt price in Q96
+    /// representation. Selling token 0 (`zero_for_one`) lowers the sqrt price, selling token 1
+    /// raises it.
+    ///
+    /// # Errors
+    ///
+    /// Same as `swap_to_price`.
+    pub fn swap_to_sqrt_price(
+        &self,
+        zero_for_one: bool,
+        sqrt_price_target: U256,
+    ) -> Result<SwapToPriceResult, SimulationError> {
+        swap_to_price::swap_to_sqrt_price(
+            self.sqrt_price,
+            zero_for_one,
+            sqrt_price_target,
+            |zero_for_one, limit| self.swap_to_limit(zero_for_one, limit),
+        )
+    }
+
+    /// Sells an unlimited amount up to `sqrt_price_limit`, returning the results of the swap and
+    /// the state of the pool after it.
+    fn swap_to_limit(
+        &self,
+        zero_for_one: bool,
+        sqrt_price_limit: U256,
+    ) -> Result<(SwapResults, Box<dyn ProtocolSim>), SimulationError> {
+        let result = self.swap(zero_for_one, I256::MAX, Some(sqrt_price_limit))?;
+        let mut new_state = self.clone();
+        new_state.liquidity = result.liquidity;
+        new_state.tick = result.tick;
+        new_state.sqrt_price = result.sqrt_price;
+        Ok((result, Box::new(new_state)))
+    }
+
     fn get_sqrt_ratio_target(
         sqrt_price_next: U256,
         sqrt_price_limit: U256,
@@ -280,6 +348,21 @@ impl ProtocolSim for UniswapV4State {
         ))
     }
 
+    fn get_depth(
+        &self,
+        token_in: &Token,
+        token_out: &Token,
+        price_move_bps: u32,
+    ) -> Result<Option<DepthLevel>, SimulationError> {
+        swap_to_price::get_depth(
+            self.sqrt_price,
+            token_in,
+            token_out,
+            price_move_bps,
+            |zero_for_one, limit| self.swap_to_limit(zero_for_one, limit),
+        )
+    }
+
     fn delta_transition(
         &mut self,
         delta: ProtocolStateDelta,
@@ -385,7 +468,89 @@ mod tests {
     use tycho_client::feed::synchronizer::ComponentWithState;
 
     use super::*;
-    use crate::protocol::models::TryFromWithBlock;
+    use crate::{
+        evm::test_utils::{assert_close, token_x, token_y},
+        protocol::models::TryFromWithBlock,
+    };
+
+    /// Pool with liquidity in range and ticks 0 and 46080 initialized without net liquidity, so
+    /// crossing them doesn't change the liquidity in range
+    fn wide_range_pool() -> UniswapV4State {
+        UniswapV4State::new(
+            8330443394424070888454257,
+            U256::from_str("188562464004052255423565206602").unwrap(),
+            UniswapV4Fees::new(0, 0, 3000),
+            17342,
+            60,
+            vec![TickInfo::new(0, 0), TickInfo::new(46080, 0)],
+        )
+    }
+
+    #[test]
+    fn test_swap_to_price() {
+        let (token_x, token_y) = (token_x(), token_y());
+        let pool = wide_range_pool();
+        let target_price = pool
+            .spot_price(&token_y, &token_x)
+            .unwrap() *
+            0.98;
+
+        let res = pool
+            .swap_to_price(&token_y, &token_x, target_price)
+            .unwrap();
+        let new_price = res
+            .new_state
+            .spot_price(&token_y, &token_x)
+            .unwrap();
+        let amount_out = pool
+            .get_amount_out(res.amount_in.clone(), &token_y, &token_x)
+            .unwrap()
+            .amount;
+
+        assert!((new_price / target_price - 1.0).abs() < 1e-9);
+        // Swapping the amount in without the price limit may round differently
+        assert_close(&amount_out, &res.amount_out, 1);
+        assert!(matches!(
+            pool.swap_to_price(&token_y, &token_x, target_price / 0.9),
+            Err(SimulationError::InvalidInput(_, None))
+        ));
+        assert!(matches!(
+            pool.swap_to_price(&token_y, &token_x, 0.0),
+            Err(SimulationError::InvalidInput(_, None))
+        ));
+    }
+
+    #[test]
+    fn test_get_depth() {
+        let (token_x, token_y) = (token_x(), token_y());
+        let pool = wide_range_pool();
+        let spot_price = pool
+            .spot_price(&token_x, &token_y)
+            .unwrap();
+
+        let level = pool
+            .get_depth(&token_x, &token_y, 100)
+            .unwrap()
+            .unwrap();
+        let res = pool
+            .get_amount_out(level.amount_in, &token_x, &token_y)
+            .unwrap();
+        let new_price = res
+            .new_state
+            .spot_price(&token_x, &token_y)
+            .unwrap();
+
+        // Swapping the amount in without the price limit may round differently
+        assert_close(&res.amount, &level.amount_out, 1);
+        let price_move_bps = (1.0 - new_price / spot_price) * 10_000.0;
+        assert!((price_move_bps - 100.0).abs() < 1e-3, "moved {price_move_bps} bps");
+        // Moving the price by 90% crosses the lowest tick
+        assert_eq!(
+            pool.get_depth(&token_x, &token_y, 9000)
+                .unwrap(),
+            None
+        );
+    }
 
     #[test]
     fn test_delta_transition() {
@@ -2,9 +2,10 @@ use alloy_primitives::{I256, U256};
 use tycho_core::Bytes;
 
 pub(crate) mod liquidity_math;
-mod solidity_math;
+pub(crate) mod solidity_math;
 pub(crate) mod sqrt_price_math;
 pub(crate) mod swap_math;
+pub(crate) mod swap_to_price;
 pub mod tick_list;
 pub(crate) mod tick_math;
 
@@ -30,6 +31,7 @@ pub(crate) struct StepComputation {
 
 #[derive(Debug)]
 pub(crate) struct SwapResults {
+    pub(crate) amount_remaining: I256,
     pub(crate) amount_calculated: I256,
     pub(crate) sqrt_price: U256,
     pub(crate) liquidity: u128,
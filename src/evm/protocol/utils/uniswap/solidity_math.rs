@@ -16,7 +16,7 @@ pub(super) fn mul_div_rounding_up(a: U256, b: U256, denom: U256) -> Result<U256,
     truncate_to_u256(result)
 }
 
-pub(super) fn mul_div(a: U256, b: U256, denom: U256) -> Result<U256, SimulationError> {
+pub(crate) fn mul_div(a: U256, b: U256, denom: U256) -> Result<U256, SimulationError> {
     let a_big = U512::from(a);
     let b_big = U512::from(b);
     let product = safe_mul_u512(a_big, b_big)?;
@@ -30,7 +30,7 @@ fn truncate_to_u256(value: U512) -> Result<U256, SimulationError> {
 
     // Check if the upper 256 bits are non-zero
     if limbs[4] != 0 || limbs[5] != 0 || limbs[6] != 0 || limbs[7] != 0 {
-        return Err(SimulationError::FatalError("Overflow: Value exceeds 256 bits".to_string()));
+        return Err(SimulationError::MathOverflow("Value exceeds 256 bits".to_string()));
     }
 
     // Extract the lower 256 bits
@@ -58,7 +58,7 @@ mod tests {
 
         let result = mul_div_rounding_up(a, b, denom);
 
-        assert!(matches!(result, Err(SimulationError::FatalError(_))));
+        assert!(matches!(result, Err(SimulationError::MathOverflow(_))));
     }
 
     #[test]
@@ -78,6 +78,6 @@ mod tests {
 
         let result = mul_div(a, b, denom);
 
-        assert!(matches!(result, Err(SimulationError::FatalError(_))));
+        assert!(matches!(result, Err(SimulationError::MathOverflow(_))));
     }
 }
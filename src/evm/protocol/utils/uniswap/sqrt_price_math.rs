@@ -1,10 +1,15 @@
 use alloy_primitives::U256;
+use num_bigint::BigUint;
+use num_traits::FromPrimitive;
 
-use super::solidity_math::{mul_div, mul_div_rounding_up};
+use super::{
+    solidity_math::{mul_div, mul_div_rounding_up},
+    tick_math::{MAX_SQRT_RATIO, MIN_SQRT_RATIO},
+};
 use crate::{
     evm::protocol::{
         safe_math::{div_mod_u256, safe_add_u256, safe_div_u256, safe_mul_u256, safe_sub_u256},
-        u256_num::u256_to_f64,
+        u256_num::{biguint_to_u256, u256_to_f64},
     },
     protocol::errors::SimulationError,
 };
@@ -31,7 +36,7 @@ fn div_rounding_up(a: U256, b: U256) -> Result<U256, SimulationError> {
     }
 }
 
-pub(super) fn get_amount0_delta(
+pub(crate) fn get_amount0_delta(
     a: U256,
     b: U256,
     liquidity: u128,
@@ -51,7 +56,7 @@ pub(super) fn get_amount0_delta(
     }
 }
 
-pub(super) fn get_amount1_delta(
+pub(crate) fn get_amount1_delta(
     a: U256,
     b: U256,
     liquidity: u128,
@@ -168,6 +173,44 @@ pub(crate) fn sqrt_price_q96_to_f64(x: U256, token_0_decimals: u32, token_1_deci
     price.powi(2) * token_correction
 }
 
+/// Converts a price of token 0 in token 1 to its approximate sqrt price in Q96 representation
+///
+/// Inverse of `sqrt_price_q96_to_f64`. Returns `None` if the price is not a positive finite number
+/// or if its sqrt price is not below `MAX_SQRT_RATIO`.
+pub(crate) fn f64_to_sqrt_price_q96(
+    price: f64,
+    token_0_decimals: u32,
+    token_1_decimals: u32,
+) -> Option<U256> {
+    if !price.is_finite() || price <= 0.0 {
+        return None;
+    }
+    let token_correction = 10f64.powi(token_0_decimals as i32 - token_1_decimals as i32);
+
+    let sqrt_price = (price / token_correction).sqrt() * 2.0f64.powi(96);
+    if !sqrt_price.is_finite() || sqrt_price >= u256_to_f64(MAX_SQRT_RATIO) {
+        return None;
+    }
+    BigUint::from_f64(sqrt_price).map(|x| biguint_to_u256(&x))
+}
+
+/// Returns the sqrt price after the price of the sold token moved down by `price_move_bps`.
+///
+/// Selling token 0 lowers the price of token 0 in token 1, i.e. the sqrt price. Selling token 1
+/// raises it. The result is clamped to the valid range of sqrt prices.
+pub(crate) fn sqrt_price_after_move(
+    sqrt_price: U256,
+    price_move_bps: u32,
+    zero_for_one: bool,
+) -> U256 {
+    let scale = U256::from(1_000_000_000_000_000_000u64);
+    // sqrt(1 - move), scaled by 1e18
+    let factor = U256::from(((1.0 - price_move_bps as f64 / 10_000.0).sqrt() * 1e18) as u128);
+    let sqrt_price_target =
+        if zero_for_one { sqrt_price * factor / scale } else { sqrt_price * scale / factor };
+    sqrt_price_target.clamp(MIN_SQRT_RATIO + U256::from(1), MAX_SQRT_RATIO - U256::from(1))
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -347,4 +390,27 @@ mod tests {
 
         assert_ulps_eq!(res, exp, epsilon = f64::EPSILON);
     }
+
+    #[rstest]
+    #[case::same_decimals(1.5, 18, 18)]
+    #[case::usdc_weth(1.0 / 3_000.0, 6, 18)]
+    #[case::weth_usdc(3_000.0, 18, 6)]
+    fn test_f64_to_sqrt_price_q96(#[case] price: f64, #[case] t0d: u32, #[case] t1d: u32) {
+        let sqrt_price = f64_to_sqrt_price_q96(price, t0d, t1d).unwrap();
+
+        assert_ulps_eq!(sqrt_price_q96_to_f64(sqrt_price, t0d, t1d) / price, 1.0, epsilon = 1e-12);
+    }
+
+    #[rstest]
+    #[case::zero(0.0)]
+    #[case::negative(-1.0)]
+    #[case::nan(f64::NAN)]
+    #[case::infinite(f64::INFINITY)]
+    #[case::above_max_sqrt_ratio(1e39)]
+    #[case::huge(1e200)]
+    #[case::inverted_tiny(1.0 / 1e-200)]
+    #[case::max(f64::MAX)]
+    fn test_f64_to_sqrt_price_q96_invalid(#[case] price: f64) {
+        assert_eq!(f64_to_sqrt_price_q96(price, 18, 18), None);
+    }
 }
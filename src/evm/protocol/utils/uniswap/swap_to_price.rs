@@ -0,0 +1,108 @@
+use alloy_primitives::{I256, U256};
+use tracing::trace;
+
+use super::{
+    sqrt_price_math::{f64_to_sqrt_price_q96, sqrt_price_after_move},
+    tick_math::{MAX_SQRT_RATIO, MIN_SQRT_RATIO},
+    SwapResults,
+};
+use crate::{
+    evm::protocol::u256_num::u256_to_biguint,
+    models::Token,
+    protocol::{
+        errors::SimulationError,
+        models::{DepthLevel, SwapToPriceResult},
+        state::{check_price_move, ProtocolSim},
+    },
+};
+
+/// Returns the amounts needed to move a pool's price to `target_price`.
+///
+/// `swap` sells an unlimited amount of token 0 (if its first argument is true) or token 1 until
+/// the pool's sqrt price reaches the given limit, and returns the results along with the state of
+/// the pool after the swap.
+pub(crate) fn swap_to_price(
+    sqrt_price: U256,
+    token_in: &Token,
+    token_out: &Token,
+    target_price: f64,
+    swap: impl FnOnce(bool, U256) -> Result<(SwapResults, Box<dyn ProtocolSim>), SimulationError>,
+) -> Result<SwapToPriceResult, SimulationError> {
+    let zero_for_one = token_in < token_out;
+    let sqrt_price_target = if zero_for_one {
+        f64_to_sqrt_price_q96(target_price, token_in.decimals as u32, token_out.decimals as u32)
+    } else {
+        f64_to_sqrt_price_q96(
+            1.0 / target_price,
+            token_out.decimals as u32,
+            token_in.decimals as u32,
+        )
+    }
+    .ok_or_else(|| {
+        SimulationError::InvalidInput(format!("Invalid target price {target_price}"), None)
+    })?;
+    swap_to_sqrt_price(sqrt_price, zero_for_one, sqrt_price_target, swap)
+}
+
+/// Returns the amounts needed to move a pool's sqrt price to `sqrt_price_target`.
+///
+/// Fails with `InvalidInput` if the target can't be reached by selling the given token.
+pub(crate) fn swap_to_sqrt_price(
+    sqrt_price: U256,
+    zero_for_one: bool,
+    sqrt_price_target: U256,
+    swap: impl FnOnce(bool, U256) -> Result<(SwapResults, Box<dyn ProtocolSim>), SimulationError>,
+) -> Result<SwapToPriceResult, SimulationError> {
+    let reachable = if zero_for_one {
+        sqrt_price_target > MIN_SQRT_RATIO && sqrt_price_target < sqrt_price
+    } else {
+        sqrt_price_target < MAX_SQRT_RATIO && sqrt_price_target > sqrt_price
+    };
+    if !reachable {
+        return Err(SimulationError::InvalidInput(
+            format!(
+                "Sqrt price {sqrt_price_target} can't be reached from {sqrt_price} by selling token {}",
+                if zero_for_one { 0 } else { 1 }
+            ),
+            None,
+        ));
+    }
+
+    let (result, new_state) = swap(zero_for_one, sqrt_price_target)?;
+
+    trace!(?sqrt_price_target, ?zero_for_one, ?result, "SWAP TO PRICE");
+    Ok(SwapToPriceResult::new(
+        u256_to_biguint((I256::MAX - result.amount_remaining).into_raw()),
+        u256_to_biguint(
+            result
+                .amount_calculated
+                .abs()
+                .into_raw(),
+        ),
+        u256_to_biguint(result.gas_used),
+        new_state,
+    ))
+}
+
+/// Returns the trade moving a pool's price by `price_move_bps`, or `None` if the pool runs out of
+/// liquidity before.
+pub(crate) fn get_depth(
+    sqrt_price: U256,
+    token_in: &Token,
+    token_out: &Token,
+    price_move_bps: u32,
+    swap: impl FnOnce(bool, U256) -> Result<(SwapResults, Box<dyn ProtocolSim>), SimulationError>,
+) -> Result<Option<DepthLevel>, SimulationError> {
+    check_price_move(price_move_bps)?;
+    let zero_for_one = token_in < token_out;
+    let sqrt_price_target = sqrt_price_after_move(sqrt_price, price_move_bps, zero_for_one);
+    match swap_to_sqrt_price(sqrt_price, zero_for_one, sqrt_price_target, swap) {
+        Ok(result) => Ok(Some(DepthLevel {
+            price_move_bps,
+            amount_in: result.amount_in,
+            amount_out: result.amount_out,
+        })),
+        Err(SimulationError::InsufficientLiquidity { .. }) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
@@ -87,13 +87,17 @@ impl TickList {
         Ok(true)
     }
 
-    #[allow(dead_code)]
-    fn apply_liquidity_change(&mut self, lower: i32, upper: i32, delta: i128) {
+    pub(crate) fn tick_spacing(&self) -> u16 {
+        self.tick_spacing
+    }
+
+    /// Adds `delta` to the net liquidity of the `lower` tick and subtracts it from the `upper`
+    /// tick, as minting (positive delta) or burning (negative delta) a position does.
+    pub(crate) fn apply_liquidity_change(&mut self, lower: i32, upper: i32, delta: i128) {
         self.upsert_tick(lower, delta);
         self.upsert_tick(upper, -delta);
     }
 
-    #[allow(dead_code)]
     fn upsert_tick(&mut self, tick: i32, delta: i128) {
         match self
             .ticks
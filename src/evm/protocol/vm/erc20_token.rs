@@ -1,9 +1,13 @@
 use std::{collections::HashMap, fmt::Debug};
 
-use alloy_primitives::{Address, U256};
-use alloy_sol_types::SolValue;
+use alloy_primitives::{keccak256, Address, B256, U256};
+use alloy_sol_types::{sol, SolCall, SolValue};
 use lazy_static::lazy_static;
-use revm::DatabaseRef;
+use revm::{
+    interpreter::{opcode, Interpreter},
+    Database, DatabaseRef, EvmContext, Inspector,
+};
+use tracing::debug;
 
 use super::{
     constants::EXTERNAL_ACCOUNT, tycho_simulation_contract::TychoSimulationContract,
@@ -12,7 +16,7 @@ use super::{
 use crate::{
     evm::{
         engine_db::{engine_db_interface::EngineDatabaseInterface, simulation_db::BlockHeader},
-        simulation::SimulationEngine,
+        simulation::{SimulationEngine, SimulationParameters},
         ContractCompiler, SlotId,
     },
     protocol::errors::SimulationError,
@@ -25,11 +29,80 @@ pub struct ERC20Slots {
     pub balance_map: SlotId,
     // Base slot for the allowance map
     pub allowance_map: SlotId,
+    // Location of the balance within a balance map entry
+    balance_layout: SlotLayout,
+    // Location of the allowance within an allowance map entry
+    allowance_layout: SlotLayout,
 }
 
 impl ERC20Slots {
+    /// Slots of a token storing balances and allowances as whole map entries.
     pub fn new(balance: SlotId, allowance: SlotId) -> Self {
-        Self { balance_map: balance, allowance_map: allowance }
+        Self {
+            balance_map: balance,
+            allowance_map: allowance,
+            balance_layout: SlotLayout::default(),
+            allowance_layout: SlotLayout::default(),
+        }
+    }
+
+    /// Sets where the balance and the allowance are located within their map entries.
+    pub fn with_layouts(
+        mut self,
+        balance_layout: SlotLayout,
+        allowance_layout: SlotLayout,
+    ) -> Self {
+        self.balance_layout = balance_layout;
+        self.allowance_layout = allowance_layout;
+        self
+    }
+
+    /// Location of the balance within a balance map entry
+    pub fn balance_layout(&self) -> SlotLayout {
+        self.balance_layout
+    }
+
+    /// Location of the allowance within an allowance map entry
+    pub fn allowance_layout(&self) -> SlotLayout {
+        self.allowance_layout
+    }
+}
+
+/// Location of a value within the storage of a mapping entry.
+///
+/// By default the value takes the whole slot of the entry. Tokens storing balances in a struct
+/// (e.g. together with a nonce or flags) use an offset from the entry's slot and may pack the
+/// value with other ones in a single slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlotLayout {
+    /// Offset of the value's slot from the slot of the mapping entry
+    pub offset: SlotId,
+    /// Position of the lowest bit of the value within its slot
+    pub shift: usize,
+    /// Width of the value in bits
+    pub bits: usize,
+}
+
+impl Default for SlotLayout {
+    fn default() -> Self {
+        Self { offset: SlotId::ZERO, shift: 0, bits: 256 }
+    }
+}
+
+impl SlotLayout {
+    fn mask(&self) -> U256 {
+        if self.bits >= 256 {
+            U256::MAX
+        } else {
+            (U256::from(1) << self.bits) - U256::from(1)
+        }
+    }
+
+    /// Returns the slot value storing `value`, capped at the maximum value that fits.
+    ///
+    /// Other values packed in the same slot are set to zero.
+    pub fn encode(&self, value: U256) -> U256 {
+        value.min(self.mask()) << self.shift
     }
 }
 
@@ -40,6 +113,8 @@ pub(crate) struct ERC20OverwriteFactory {
     overwrites: Overwrites,
     balance_slot: SlotId,
     allowance_slot: SlotId,
+    balance_layout: SlotLayout,
+    allowance_layout: SlotLayout,
     compiler: ContractCompiler,
 }
 
@@ -54,21 +129,25 @@ impl ERC20OverwriteFactory {
             overwrites: HashMap::new(),
             balance_slot: token_slots.balance_map,
             allowance_slot: token_slots.allowance_map,
+            balance_layout: token_slots.balance_layout,
+            allowance_layout: token_slots.allowance_layout,
             compiler,
         }
     }
 
     pub fn set_balance(&mut self, balance: U256, owner: Address) {
-        let storage_index = get_storage_slot_index_at_key(owner, self.balance_slot, self.compiler);
+        let storage_index = get_storage_slot_index_at_key(owner, self.balance_slot, self.compiler) +
+            self.balance_layout.offset;
         self.overwrites
-            .insert(storage_index, balance);
+            .insert(storage_index, self.balance_layout.encode(balance));
     }
 
     pub fn set_allowance(&mut self, allowance: U256, spender: Address, owner: Address) {
         let owner_slot = get_storage_slot_index_at_key(owner, self.allowance_slot, self.compiler);
-        let storage_index = get_storage_slot_index_at_key(spender, owner_slot, self.compiler);
+        let storage_index = get_storage_slot_index_at_key(spender, owner_slot, self.compiler) +
+            self.allowance_layout.offset;
         self.overwrites
-            .insert(storage_index, allowance);
+            .insert(storage_index, self.allowance_layout.encode(allowance));
     }
 
     #[cfg(test)]
@@ -94,6 +173,254 @@ lazy_static! {
 }
 type U256Return = U256;
 
+sol! {
+    function balanceOf(address account) external view returns (uint256);
+    function allowance(address owner, address spender) external view returns (uint256);
+}
+
+/// Maximum offset of a value within a struct stored in a mapping
+const MAX_STRUCT_OFFSET: u64 = 16;
+
+/// Detection of storage slots for token balances and allowances.
+///
+/// Uses `trace_slots` and falls back to `brute_force_slots` for the tokens it can't handle, e.g.
+/// tokens computing balances instead of reading them from storage.
+pub(crate) fn detect_slots<D: EngineDatabaseInterface + Clone + Debug>(
+    token_addr: &Address,
+    block: &BlockHeader,
+    engine: &SimulationEngine<D>,
+) -> Result<(ERC20Slots, ContractCompiler), SimulationError>
+where
+    <D as DatabaseRef>::Error: std::fmt::Debug,
+    <D as EngineDatabaseInterface>::Error: std::fmt::Debug,
+{
+    match trace_slots(token_addr, block, engine) {
+        Some(slots) => Ok(slots),
+        None => {
+            debug!(%token_addr, "Failed to trace token slots, falling back to brute force");
+            brute_force_slots(token_addr, block, engine)
+        }
+    }
+}
+
+/// Tracer-based detection of storage slots for token balances and allowances.
+///
+/// Calls `balanceOf` and `allowance` while recording the storage slots they read from the token
+/// and the preimages of the hashes they compute. A read slot is the balance (or allowance) slot if
+/// it was derived from the queried addresses, which gives the base slot of the map and the
+/// compiler layout. Unlike `brute_force_slots`, this works for any base slot, e.g. with ERC-7201
+/// namespaced storage, and for balances stored in a struct.
+///
+/// The bits of the slot holding the value are found by overwriting the slot with a value whose
+/// bytes encode their position, so balances packed with other values are supported as well.
+/// Every detected slot is checked by overwriting it and reading the value back.
+///
+/// # Returns
+///
+/// The detected slots and compiler, or `None` if either slot couldn't be detected.
+pub(crate) fn trace_slots<D: EngineDatabaseInterface + Clone + Debug>(
+    token_addr: &Address,
+    block: &BlockHeader,
+    engine: &SimulationEngine<D>,
+) -> Option<(ERC20Slots, ContractCompiler)>
+where
+    <D as DatabaseRef>::Error: std::fmt::Debug,
+    <D as EngineDatabaseInterface>::Error: std::fmt::Debug,
+{
+    let (balance_map, compiler, balance_layout) = trace_map_slot(
+        token_addr,
+        block,
+        engine,
+        balanceOfCall { account: *EXTERNAL_ACCOUNT }.abi_encode(),
+        &[*EXTERNAL_ACCOUNT],
+    )?;
+    let (allowance_map, allowance_compiler, allowance_layout) = trace_map_slot(
+        token_addr,
+        block,
+        engine,
+        allowanceCall { owner: *EXTERNAL_ACCOUNT, spender: *SPENDER }.abi_encode(),
+        &[*EXTERNAL_ACCOUNT, *SPENDER],
+    )?;
+    // Overwrites are computed with a single compiler layout for both maps
+    if compiler != allowance_compiler {
+        return None;
+    }
+
+    Some((
+        ERC20Slots::new(balance_map, allowance_map).with_layouts(balance_layout, allowance_layout),
+        compiler,
+    ))
+}
+
+/// Finds the map storing the value returned by the given call, where `keys` are the addresses
+/// the map is indexed with, outermost key last.
+fn trace_map_slot<D: EngineDatabaseInterface + Clone + Debug>(
+    token_addr: &Address,
+    block: &BlockHeader,
+    engine: &SimulationEngine<D>,
+    calldata: Vec<u8>,
+    keys: &[Address],
+) -> Option<(SlotId, ContractCompiler, SlotLayout)>
+where
+    <D as DatabaseRef>::Error: std::fmt::Debug,
+    <D as EngineDatabaseInterface>::Error: std::fmt::Debug,
+{
+    let params = |overrides: Option<HashMap<Address, Overwrites>>| SimulationParameters {
+        caller: *EXTERNAL_ACCOUNT,
+        to: *token_addr,
+        data: calldata.clone(),
+        value: U256::ZERO,
+        overrides,
+        account_overrides: None,
+        gas_limit: None,
+        block_number: block.number,
+        timestamp: block.timestamp,
+        env: engine.environment().clone(),
+    };
+
+    let mut recorder = SlotRecorder::new(*token_addr);
+    engine
+        .simulate_with_inspector(&params(None), &mut recorder)
+        .ok()?;
+
+    // The value is usually read last, e.g. after the implementation slot of a proxy
+    recorder
+        .sloads
+        .iter()
+        .rev()
+        .find_map(|slot| {
+            let (map_slot, compiler, offset) = recorder.resolve_map_slot(*slot, keys)?;
+            let read = |value: U256| {
+                let overrides = HashMap::from([(*token_addr, HashMap::from([(*slot, value)]))]);
+                let result = engine
+                    .simulate(&params(Some(overrides)))
+                    .ok()?;
+                U256Return::abi_decode(&result.result, true).ok()
+            };
+            let layout = probe_layout(read, offset)?;
+            Some((map_slot, compiler, layout))
+        })
+}
+
+/// Finds the bits of a slot that are returned by `read`, which reads the value after overwriting
+/// the slot with the given value.
+fn probe_layout(read: impl Fn(U256) -> Option<U256>, offset: SlotId) -> Option<SlotLayout> {
+    // Byte `i` of the probe (from the lowest) is `i + 1`, so the returned value tells which bytes
+    // were read.
+    let probe = U256::from_le_bytes(std::array::from_fn::<u8, 32, _>(|i| i as u8 + 1));
+    let value = read(probe)?.to_le_bytes::<32>();
+    let width = value
+        .iter()
+        .take_while(|byte| **byte != 0)
+        .count();
+    let shift = usize::from(value[0].checked_sub(1)?);
+    if width == 0 ||
+        value[width..]
+            .iter()
+            .any(|byte| *byte != 0) ||
+        (0..width).any(|i| usize::from(value[i]) != shift + i + 1)
+    {
+        return None;
+    }
+
+    let layout = SlotLayout { offset, shift: shift * 8, bits: width * 8 };
+    let marker = *MARKER_VALUE & layout.mask();
+    (read(layout.encode(marker))? == marker).then_some(layout)
+}
+
+/// Inspector recording the storage slots read from a contract, and the preimages of the hashes
+/// of two words (i.e. of a mapping key and slot) computed during execution.
+#[derive(Debug)]
+struct SlotRecorder {
+    contract: Address,
+    /// Slots read from the contract, in order
+    sloads: Vec<SlotId>,
+    /// Preimages of the computed hashes, by hash
+    preimages: HashMap<U256, [u8; 64]>,
+}
+
+impl SlotRecorder {
+    fn new(contract: Address) -> Self {
+        Self { contract, sloads: Vec::new(), preimages: HashMap::new() }
+    }
+
+    /// Returns the base slot of the map `slot` belongs to, the compiler layout of the map and the
+    /// offset of `slot` from the map entry, if `slot` was derived from `keys`.
+    fn resolve_map_slot(
+        &self,
+        slot: SlotId,
+        keys: &[Address],
+    ) -> Option<(SlotId, ContractCompiler, SlotId)> {
+        (0..MAX_STRUCT_OFFSET)
+            .map(U256::from)
+            .find_map(|offset| {
+                let (map_slot, compiler) = self.map_base_slot(slot.checked_sub(offset)?, keys)?;
+                Some((map_slot, compiler, offset))
+            })
+    }
+
+    /// Returns the base slot and compiler layout of the map containing `entry`, looked up with
+    /// `keys` (outermost key last).
+    fn map_base_slot(&self, entry: SlotId, keys: &[Address]) -> Option<(SlotId, ContractCompiler)> {
+        let (key, inner_keys) = keys.split_last()?;
+        let preimage = self.preimages.get(&entry)?;
+        let key = B256::left_padding_from(key.as_slice());
+        let (compiler, parent) = if preimage[..32] == key[..] {
+            (ContractCompiler::Solidity, &preimage[32..])
+        } else if preimage[32..] == key[..] {
+            (ContractCompiler::Vyper, &preimage[..32])
+        } else {
+            return None;
+        };
+        let parent = SlotId::from_be_slice(parent);
+        if inner_keys.is_empty() {
+            return Some((parent, compiler));
+        }
+        self.map_base_slot(parent, inner_keys)
+            .filter(|(_, inner_compiler)| *inner_compiler == compiler)
+    }
+}
+
+impl<DB: Database> Inspector<DB> for SlotRecorder {
+    fn step(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        match interp.current_opcode() {
+            opcode::SLOAD if interp.contract.target_address == self.contract => {
+                if let Ok(slot) = interp.stack().peek(0) {
+                    self.sloads.push(slot);
+                }
+            }
+            opcode::KECCAK256 => {
+                let (Ok(offset), Ok(size)) = (interp.stack().peek(0), interp.stack().peek(1))
+                else {
+                    return;
+                };
+                if size != U256::from(64) {
+                    return;
+                }
+                let Some(start) = usize::try_from(offset).ok() else {
+                    return;
+                };
+                let preimage = start.checked_add(64).and_then(|end| {
+                    interp
+                        .shared_memory
+                        .context_memory()
+                        .get(start..end)
+                });
+                if let Some(preimage) = preimage {
+                    let hash = U256::from_be_bytes(keccak256(preimage).0);
+                    self.preimages.insert(
+                        hash,
+                        preimage
+                            .try_into()
+                            .expect("Preimage is 64 bytes"),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 /// Brute-force detection of storage slots for token balances and allowances.
 ///
 /// This function attempts to determine the storage slots used by a token contract
@@ -153,21 +480,18 @@ where
             );
             overwrite_factory.set_balance(*MARKER_VALUE, *EXTERNAL_ACCOUNT);
 
-            let res = token_contract
-                .call(
-                    "balanceOf(address)",
-                    *EXTERNAL_ACCOUNT,
+            let balance = token_contract
+                .call_typed(
+                    &balanceOfCall { account: *EXTERNAL_ACCOUNT },
                     block.number,
                     Some(block.timestamp),
                     Some(overwrite_factory.get_overwrites()),
                     Some(*EXTERNAL_ACCOUNT),
                     U256::from(0u64),
                 )?
-                .return_value;
-            let decoded: U256Return = U256Return::abi_decode(&res, true).map_err(|e| {
-                SimulationError::FatalError(format!("Failed to decode swap return value: {:?}", e))
-            })?;
-            if decoded == *MARKER_VALUE {
+                .return_value
+                ._0;
+            if balance == *MARKER_VALUE {
                 balance_slot = Some(i);
                 compiler = compiler_flag;
                 break;
@@ -193,21 +517,18 @@ where
 
         overwrite_factory.set_allowance(*MARKER_VALUE, *SPENDER, *EXTERNAL_ACCOUNT);
 
-        let res = token_contract
-            .call(
-                "allowance(address,address)",
-                (*EXTERNAL_ACCOUNT, *SPENDER),
+        let allowance = token_contract
+            .call_typed(
+                &allowanceCall { owner: *EXTERNAL_ACCOUNT, spender: *SPENDER },
                 block.number,
                 Some(block.timestamp),
                 Some(overwrite_factory.get_overwrites()),
                 Some(*EXTERNAL_ACCOUNT),
                 U256::from(0u64),
             )?
-            .return_value;
-        let decoded: U256Return = U256Return::abi_decode(&res, true).map_err(|e| {
-            SimulationError::FatalError(format!("Failed to decode swap return value: {:?}", e))
-        })?;
-        if decoded == *MARKER_VALUE {
+            .return_value
+            ._0;
+        if allowance == *MARKER_VALUE {
             allowance_slot = Some(i);
             break;
         }
@@ -236,9 +557,13 @@ mod tests {
     };
     use chrono::NaiveDateTime;
     use dotenv::dotenv;
+    use revm::primitives::{AccountInfo, Bytecode};
 
     use super::*;
-    use crate::evm::engine_db::simulation_db::SimulationDB;
+    use crate::evm::{
+        engine_db::{create_engine, simulation_db::SimulationDB, tycho_db::PreCachedDB},
+        test_utils::assemble,
+    };
 
     fn setup_factory() -> ERC20OverwriteFactory {
         let token_address: Address = Address::from_slice(
@@ -373,4 +698,73 @@ mod tests {
         assert_eq!(ERC20Slots::new(U256::from(38), U256::from(39)), slots);
         assert_eq!(ContractCompiler::Vyper, compiler);
     }
+
+    #[test]
+    fn test_trace_slots_namespaced_packed() {
+        // Token using namespaced storage, returning `balanceOf` from a packed uint96 at bit 32 of
+        // the second slot of a struct, and `allowance` from a plain nested map.
+        let namespace =
+            U256::from_str("0x52c63247e1f47db19d5ce0460030c497f067ca4cebf71ba98eeadabe20bace00")
+                .unwrap();
+        let code = Bytecode::new_raw(assemble(&format!(
+            "
+            PUSH1 4 CALLDATALOAD PUSH1 0 MSTORE
+            PUSH1 0 CALLDATALOAD PUSH1 0xe0 SHR PUSH4 0x70a08231 EQ @balance_of JUMPI
+
+            ; allowance(address owner, address spender) at `namespace + 2`
+            PUSH32 {allowances} PUSH1 0x20 MSTORE PUSH1 0x40 PUSH1 0 KECCAK256 PUSH1 0x20 MSTORE
+            PUSH1 0x24 CALLDATALOAD PUSH1 0 MSTORE PUSH1 0x40 PUSH1 0 KECCAK256 SLOAD
+            PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+
+            ; balanceOf(address account), bits 32 to 128 of the second slot of the struct
+            balance_of:
+            PUSH32 {namespace} PUSH1 0x20 MSTORE PUSH1 0x40 PUSH1 0 KECCAK256 PUSH1 1 ADD SLOAD
+            PUSH1 0x20 SHR PUSH12 0xffffffffffffffffffffffff AND
+            PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+            ",
+            allowances = namespace + U256::from(2),
+        )));
+        let token = Address::from_str("0x0000000000000000000000000000000000001234").unwrap();
+        let engine = create_engine(PreCachedDB::new().unwrap(), false).unwrap();
+        engine.state.init_account(
+            token,
+            AccountInfo::new(U256::ZERO, 0, code.hash_slow(), code),
+            None,
+            false,
+        );
+        engine
+            .state
+            .init_account(*EXTERNAL_ACCOUNT, AccountInfo::default(), None, false);
+        let block = BlockHeader::default();
+
+        let (slots, compiler) = trace_slots(&token, &block, &engine).unwrap();
+
+        let balance_layout = SlotLayout { offset: U256::from(1), shift: 32, bits: 96 };
+        assert_eq!(
+            slots,
+            ERC20Slots::new(namespace, namespace + U256::from(2))
+                .with_layouts(balance_layout, SlotLayout::default())
+        );
+        assert_eq!(compiler, ContractCompiler::Solidity);
+
+        // Overwritten balances are capped to the width of the packed value
+        let mut overwrite_factory = ERC20OverwriteFactory::new(token, slots, compiler);
+        overwrite_factory.set_balance(U256::MAX, *EXTERNAL_ACCOUNT);
+        let balance = engine
+            .simulate(&SimulationParameters {
+                caller: *EXTERNAL_ACCOUNT,
+                to: token,
+                data: balanceOfCall { account: *EXTERNAL_ACCOUNT }.abi_encode(),
+                value: U256::ZERO,
+                overrides: Some(overwrite_factory.get_overwrites()),
+                account_overrides: None,
+                gas_limit: None,
+                block_number: 0,
+                timestamp: 0,
+                env: Default::default(),
+            })
+            .unwrap()
+            .result;
+        assert_eq!(U256::abi_decode(&balance, true).unwrap(), balance_layout.mask());
+    }
 }
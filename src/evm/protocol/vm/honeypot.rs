@@ -0,0 +1,810 @@
+//! Sellability checks for tokens.
+//!
+//! A token that can be bought but not sold (a "honeypot") traps any amount routed into it. The
+//! check funds a synthetic account with the token by overwriting its balance and sells the token
+//! through a Uniswap V2 or V3 pool, all simulated on the VM state. Like a router, the check pulls
+//! the tokens from the seller with `transferFrom`, so tokens restricting it are caught as well.
+use std::{collections::HashMap, fmt::Debug};
+
+use alloy_primitives::{address, aliases::U160, Address, I256, U256};
+use alloy_sol_types::{sol, SolCall, SolValue};
+use revm::{
+    primitives::{Bytecode, Bytes},
+    DatabaseRef,
+};
+
+use super::{
+    erc20_token::{detect_slots, ERC20OverwriteFactory, ERC20Slots, Overwrites},
+    utils::{coerce_error, ErrorRegistry},
+};
+use crate::{
+    evm::{
+        engine_db::{
+            engine_db_interface::EngineDatabaseInterface,
+            simulation_db::{AccountOverride, BlockHeader},
+        },
+        protocol::utils::{
+            bytes_to_address,
+            uniswap::tick_math::{MAX_SQRT_RATIO, MIN_SQRT_RATIO},
+        },
+        simulation::{
+            SimulationEngine, SimulationEngineError, SimulationParameters, SimulationResult,
+        },
+        ContractCompiler,
+    },
+    models::Token,
+    protocol::errors::SimulationError,
+};
+
+sol! {
+    function approve(address spender, uint256 amount) external returns (bool);
+    function transferFrom(address from, address to, uint256 amount) external returns (bool);
+    function balanceOf(address account) external view returns (uint256);
+    function token0() external view returns (address);
+    function token1() external view returns (address);
+}
+
+mod uniswap_v2 {
+    alloy_sol_types::sol! {
+        function getReserves() external view returns (uint112, uint112, uint32);
+        function swap(uint256 amount0Out, uint256 amount1Out, address to, bytes data) external;
+    }
+}
+
+mod uniswap_v3 {
+    alloy_sol_types::sol! {
+        function swap(
+            address recipient,
+            bool zeroForOne,
+            int256 amountSpecified,
+            uint160 sqrtPriceLimitX96,
+            bytes data
+        ) external returns (int256, int256);
+    }
+}
+
+/// Synthetic account sending the transactions. It acts as the router of the sale, pulling the
+/// tokens from the swapper.
+const OPERATOR: Address = address!("5e4de7c3fa32f2f1d8d1b1e28bd5e6e8bf5a0003");
+/// Synthetic contract holding and selling the tokens
+const SWAPPER: Address = address!("5e4de7c3fa32f2f1d8d1b1e28bd5e6e8bf5a0004");
+
+/// Runtime code of the swapper contract.
+///
+/// Calls with calldata `abi.encodePacked(bytes32(target), data)` are forwarded to `target`,
+/// returning (or reverting with) the result of the call. `uniswapV3SwapCallback` pays the positive
+/// delta in the token given as callback data.
+const SWAPPER_CODE: &str = "60003560e01c63fa461e3314603a5736602090038060206000376000600082600060\
+     006000355af13d600060003e6035573d6000fd5b3d6000f35b6004358060009013604a57506024355b63a9059cbb\
+     60e01b60005233600452602452602060006044600060006084355af1607357600080fd5b00";
+
+/// If the given amount can't be sold, the largest amount that can is searched down to this
+/// fraction of the amount
+const MAX_AMOUNT_PRECISION: u64 = 1000;
+
+/// A pool to sell a token through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SellPool {
+    UniswapV2(Address),
+    UniswapV3(Address),
+}
+
+impl SellPool {
+    fn address(&self) -> Address {
+        match self {
+            SellPool::UniswapV2(address) | SellPool::UniswapV3(address) => *address,
+        }
+    }
+}
+
+/// The result of a sellability check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SellCheck {
+    /// The token was sold for `amount_out` of the other token of the pool, as received by the
+    /// seller.
+    Sellable { amount_out: U256 },
+    /// The checked amount can't be sold, but `max_amount` can. Likely a max transaction limit.
+    ///
+    /// `max_amount` is the largest amount that can be sold, up to 0.1% of the checked amount.
+    MaxTransactionLimit { max_amount: U256 },
+    /// Approving the router reverted or returned `false`.
+    ApproveReverted(String),
+    /// Transferring the token to the pool with `transferFrom` reverted or returned `false`, for
+    /// any amount.
+    TransferRestricted(String),
+    /// The swap reverted, for any amount.
+    SwapReverted(String),
+}
+
+/// Step of a sale
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SellStep {
+    /// The swapper approves the router
+    Approve,
+    /// The router transfers the tokens from the swapper to the pool
+    Transfer,
+    /// The swap itself, and the calls reading the pool and the balances around it
+    Swap,
+}
+
+/// Outcome of a single sale
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SellOutcome {
+    Sold(U256),
+    Failed(SellStep, String),
+}
+
+/// Checks whether a token can be sold through a pool.
+///
+/// Sells `amount` of the token through the pool from a synthetic account. If the sale fails, the
+/// check bisects for the largest amount that can be sold, to tell a max transaction limit apart
+/// from a token that can't be sold at all.
+///
+/// # Parameters
+///
+/// * `token` - The token to sell.
+/// * `pool` - A pool trading the token. Its state has to be in the engine's database.
+/// * `amount` - The amount of the token to sell.
+/// * `slots` - The storage slots of the token, used to fund the seller. Detected if not given.
+/// * `block` - The block header at which the simulation is executed.
+/// * `engine` - The simulation engine.
+///
+/// # Errors
+///
+/// Returns an error if the token's storage slots can't be detected, if the pool can't be queried
+/// or if the state can't be fetched.
+pub fn check_sellability<D: EngineDatabaseInterface + Clone + Debug>(
+    token: &Token,
+    pool: SellPool,
+    amount: U256,
+    slots: Option<(ERC20Slots, ContractCompiler)>,
+    block: &BlockHeader,
+    engine: &SimulationEngine<D>,
+) -> Result<SellCheck, SimulationError>
+where
+    <D as DatabaseRef>::Error: Debug,
+    <D as EngineDatabaseInterface>::Error: Debug,
+{
+    let token_address = bytes_to_address(&token.address)?;
+    let (slots, compiler) = match slots {
+        Some(slots) => slots,
+        None => detect_slots(&token_address, block, engine)?,
+    };
+    let swapper_code =
+        Bytecode::new_raw(Bytes::from(hex::decode(SWAPPER_CODE).expect("Invalid swapper code")));
+    let synthetic_accounts = HashMap::from([
+        (OPERATOR, AccountOverride::new_account(Bytecode::new())),
+        (SWAPPER, AccountOverride::new_account(swapper_code)),
+    ]);
+
+    let seller =
+        Seller { token: token_address, slots, compiler, pool, synthetic_accounts, block, engine };
+    let (step, reason) = match seller.sell(amount)? {
+        SellOutcome::Sold(amount_out) => return Ok(SellCheck::Sellable { amount_out }),
+        SellOutcome::Failed(SellStep::Approve, reason) => {
+            return Ok(SellCheck::ApproveReverted(reason))
+        }
+        SellOutcome::Failed(step, reason) => (step, reason),
+    };
+    // The smallest amount checked is also the precision of the search
+    let min_amount = amount / U256::from(MAX_AMOUNT_PRECISION);
+    if min_amount.is_zero() || !matches!(seller.sell(min_amount)?, SellOutcome::Sold(_)) {
+        return Ok(match step {
+            SellStep::Transfer => SellCheck::TransferRestricted(reason),
+            _ => SellCheck::SwapReverted(reason),
+        });
+    }
+    let (mut sellable, mut unsellable) = (min_amount, amount);
+    while unsellable - sellable > min_amount {
+        let mid = sellable + (unsellable - sellable) / U256::from(2);
+        match seller.sell(mid)? {
+            SellOutcome::Sold(_) => sellable = mid,
+            SellOutcome::Failed(..) => unsellable = mid,
+        }
+    }
+    Ok(SellCheck::MaxTransactionLimit { max_amount: sellable })
+}
+
+/// Amount to request when selling `amount_in` to a Uniswap V2 pool with the given reserves,
+/// assuming the standard 0.3% fee
+fn get_amount_out_v2(amount_in: U256, reserve_in: U256, reserve_out: U256) -> U256 {
+    let amount_in_with_fee = amount_in * U256::from(997);
+    let denominator = reserve_in * U256::from(1000) + amount_in_with_fee;
+    if denominator.is_zero() {
+        return U256::ZERO;
+    }
+    amount_in_with_fee * reserve_out / denominator
+}
+
+fn decode_balance(result: &Bytes) -> Result<U256, SimulationError> {
+    U256::abi_decode(result, true)
+        .map_err(|e| SimulationError::FatalError(format!("Failed to decode balance: {:?}", e)))
+}
+
+struct Seller<'a, D: EngineDatabaseInterface + Clone + Debug>
+where
+    <D as DatabaseRef>::Error: Debug,
+    <D as EngineDatabaseInterface>::Error: Debug,
+{
+    token: Address,
+    slots: ERC20Slots,
+    compiler: ContractCompiler,
+    pool: SellPool,
+    /// Overrides creating the operator and the swapper, which only exist for the simulated
+    /// transactions
+    synthetic_accounts: HashMap<Address, AccountOverride>,
+    block: &'a BlockHeader,
+    engine: &'a SimulationEngine<D>,
+}
+
+impl<D: EngineDatabaseInterface + Clone + Debug> Seller<'_, D>
+where
+    <D as DatabaseRef>::Error: Debug,
+    <D as EngineDatabaseInterface>::Error: Debug,
+{
+    /// Sells `amount` of the token through the pool.
+    ///
+    /// The swapper is funded with twice the amount and approves the operator, which transfers the
+    /// amount from the swapper to the pool with `transferFrom`, as routers do. For V2 pools the
+    /// transferred amount is the input of the swap, V3 pools pull the amount sold in the swap
+    /// callback. The output is measured on the swapper's balance of the other token of the pool.
+    fn sell(&self, amount: U256) -> Result<SellOutcome, SimulationError> {
+        let pool = self.pool.address();
+        let mut overwrite_factory =
+            ERC20OverwriteFactory::new(self.token, self.slots.clone(), self.compiler);
+        overwrite_factory.set_balance(amount * U256::from(2), SWAPPER);
+        let overwrites = overwrite_factory.get_overwrites();
+        let txs = vec![
+            (
+                SellStep::Approve,
+                self.forward(
+                    self.token,
+                    approveCall { spender: OPERATOR, amount }.abi_encode(),
+                    None,
+                ),
+            ),
+            (
+                SellStep::Transfer,
+                SimulationParameters {
+                    overrides: Some(overwrites),
+                    ..self.params(
+                        self.token,
+                        transferFromCall { from: SWAPPER, to: pool, amount }.abi_encode(),
+                    )
+                },
+            ),
+        ];
+        let token0 = self.call_address(pool, token0Call {}.abi_encode(), "token0")?;
+        let zero_for_one = token0 == self.token;
+        let token_out = if zero_for_one {
+            self.call_address(pool, token1Call {}.abi_encode(), "token1")?
+        } else {
+            token0
+        };
+
+        let swap = match self.pool {
+            SellPool::UniswapV2(pair) => {
+                // The amount to request depends on the amount the pair actually received
+                let mut quote_txs = txs.clone();
+                quote_txs.push((
+                    SellStep::Swap,
+                    self.params(pair, uniswap_v2::getReservesCall {}.abi_encode()),
+                ));
+                quote_txs.push((
+                    SellStep::Swap,
+                    self.params(self.token, balanceOfCall { account: pair }.abi_encode()),
+                ));
+                let results = match self.run(&quote_txs)? {
+                    Ok(results) => results,
+                    Err(failure) => return Ok(failure),
+                };
+                let (reserve0, reserve1, _) =
+                    <(U256, U256, U256)>::abi_decode(&results[2].result, true).map_err(|e| {
+                        SimulationError::FatalError(format!("Failed to decode reserves: {:?}", e))
+                    })?;
+                let balance = decode_balance(&results[3].result)?;
+                let (reserve_in, reserve_out) =
+                    if zero_for_one { (reserve0, reserve1) } else { (reserve1, reserve0) };
+                let amount_in = balance.saturating_sub(reserve_in);
+                if amount_in.is_zero() {
+                    return Ok(SellOutcome::Failed(
+                        SellStep::Transfer,
+                        "Pool received no tokens".to_string(),
+                    ));
+                }
+                let amount_out = get_amount_out_v2(amount_in, reserve_in, reserve_out);
+                let (amount0_out, amount1_out) =
+                    if zero_for_one { (U256::ZERO, amount_out) } else { (amount_out, U256::ZERO) };
+                self.forward(
+                    pair,
+                    uniswap_v2::swapCall {
+                        amount0Out: amount0_out,
+                        amount1Out: amount1_out,
+                        to: SWAPPER,
+                        data: Default::default(),
+                    }
+                    .abi_encode(),
+                    None,
+                )
+            }
+            SellPool::UniswapV3(pool) => {
+                let sqrt_price_limit = if zero_for_one {
+                    MIN_SQRT_RATIO + U256::from(1)
+                } else {
+                    MAX_SQRT_RATIO - U256::from(1)
+                };
+                let amount_specified = I256::try_from(amount).map_err(|_| {
+                    SimulationError::InvalidInput(format!("Amount {} is too large", amount), None)
+                })?;
+                self.forward(
+                    pool,
+                    uniswap_v3::swapCall {
+                        recipient: SWAPPER,
+                        zeroForOne: zero_for_one,
+                        amountSpecified: amount_specified,
+                        sqrtPriceLimitX96: sqrt_price_limit.to::<U160>(),
+                        data: self.token.abi_encode().into(),
+                    }
+                    .abi_encode(),
+                    None,
+                )
+            }
+        };
+
+        let balance_of_swapper =
+            self.params(token_out, balanceOfCall { account: SWAPPER }.abi_encode());
+        let mut txs = txs;
+        txs.extend([
+            (SellStep::Swap, balance_of_swapper.clone()),
+            (SellStep::Swap, swap),
+            (SellStep::Swap, balance_of_swapper),
+        ]);
+        let results = match self.run(&txs)? {
+            Ok(results) => results,
+            Err(failure) => return Ok(failure),
+        };
+        let amount_out =
+            decode_balance(&results[4].result)?.saturating_sub(decode_balance(&results[2].result)?);
+        if amount_out.is_zero() {
+            return Ok(SellOutcome::Failed(
+                SellStep::Swap,
+                "Received no tokens from the swap".to_string(),
+            ));
+        }
+        Ok(SellOutcome::Sold(amount_out))
+    }
+
+    /// Simulates the transactions in a bundle.
+    ///
+    /// # Returns
+    ///
+    /// The results of the transactions, or the outcome describing the first failing step.
+    fn run(
+        &self,
+        txs: &[(SellStep, SimulationParameters)],
+    ) -> Result<Result<Vec<SimulationResult>, SellOutcome>, SimulationError> {
+        let params: Vec<_> = txs
+            .iter()
+            .map(|(_, params)| params.clone())
+            .collect();
+        let err = match self.engine.simulate_bundle(&params) {
+            Ok(results) => {
+                // Tokens not returning a value on approvals and transfers are accepted
+                let failed = txs
+                    .iter()
+                    .zip(&results)
+                    .find(|((step, _), result)| {
+                        *step != SellStep::Swap &&
+                            matches!(bool::abi_decode(&result.result, true), Ok(false))
+                    });
+                return Ok(match failed {
+                    Some(((step, _), _)) => {
+                        Err(SellOutcome::Failed(*step, "Returned false".to_string()))
+                    }
+                    None => Ok(results),
+                });
+            }
+            Err(SimulationEngineError::StorageError(msg)) => {
+                return Err(SimulationError::RecoverableError(msg))
+            }
+            Err(err) => err,
+        };
+        // Simulate growing prefixes of the bundle to find which transaction failed
+        let mut failure = (txs.len() - 1, err);
+        for end in 1..txs.len() {
+            if let Err(err) = self
+                .engine
+                .simulate_bundle(&params[..end])
+            {
+                failure = (end - 1, err);
+                break;
+            }
+        }
+        let (index, err) = failure;
+        if let SimulationEngineError::StorageError(msg) = err {
+            return Err(SimulationError::RecoverableError(msg));
+        }
+        let reason = coerce_error(&err, &ErrorRegistry::default(), None, None).to_string();
+        Ok(Err(SellOutcome::Failed(txs[index].0, reason)))
+    }
+
+    /// Calls a view function of `to`
+    fn call(&self, to: Address, data: Vec<u8>) -> Result<Bytes, SimulationError> {
+        let params = self.params(to, data);
+        self.engine
+            .simulate(&params)
+            .map(|result| result.result)
+            .map_err(|err| coerce_error(&err, &ErrorRegistry::default(), None, params.gas_limit))
+    }
+
+    /// Calls a view function of `to` returning an address
+    fn call_address(
+        &self,
+        to: Address,
+        data: Vec<u8>,
+        name: &str,
+    ) -> Result<Address, SimulationError> {
+        let result = self.call(to, data)?;
+        Address::abi_decode(&result, true)
+            .map_err(|e| SimulationError::FatalError(format!("Failed to decode {}: {:?}", name, e)))
+    }
+
+    /// Parameters of a call from the swapper to `target`, sent through the swapper's forwarding.
+    fn forward(
+        &self,
+        target: Address,
+        data: Vec<u8>,
+        overrides: Option<HashMap<Address, Overwrites>>,
+    ) -> SimulationParameters {
+        SimulationParameters {
+            overrides,
+            ..self.params(SWAPPER, [target.into_word().as_slice(), &data].concat())
+        }
+    }
+
+    fn params(&self, to: Address, data: Vec<u8>) -> SimulationParameters {
+        SimulationParameters {
+            caller: OPERATOR,
+            to,
+            data,
+            value: U256::ZERO,
+            overrides: None,
+            account_overrides: Some(self.synthetic_accounts.clone()),
+            gas_limit: None,
+            block_number: self.block.number,
+            timestamp: self.block.timestamp,
+            env: self.engine.environment().clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigUint;
+    use revm::primitives::AccountInfo;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::evm::{
+        engine_db::{create_engine, tycho_db::PreCachedDB},
+        protocol::vm::constants::ERC20_BYTECODE,
+        test_utils::assemble,
+    };
+
+    /// Moves `amount` from `from` to `to` in the balance map at slot 0, reverting if the amount
+    /// is above the limit at slot 2 (if set) or the balance of `from`.
+    ///
+    /// Expects the stack `from to amount` (top first) and consumes it.
+    fn move_balance(label: &str) -> String {
+        format!(
+            "
+            PUSH1 2 SLOAD DUP1 ISZERO @{label}_unlimited JUMPI
+            DUP4 GT @fail JUMPI @{label}_checked JUMP
+            {label}_unlimited: POP
+            {label}_checked:
+            DUP1 PUSH1 0 MSTORE PUSH1 0 PUSH1 0x20 MSTORE PUSH1 0x40 PUSH1 0 KECCAK256
+            DUP1 SLOAD DUP5 DUP2 LT @fail JUMPI
+            DUP5 SWAP1 SUB SWAP1 SSTORE POP
+            PUSH1 0 MSTORE PUSH1 0 PUSH1 0x20 MSTORE PUSH1 0x40 PUSH1 0 KECCAK256
+            DUP1 SLOAD DUP3 ADD SWAP1 SSTORE POP
+            "
+        )
+    }
+
+    /// Code of a token with balances in a map at slot 0 and allowances in a map at slot 1.
+    ///
+    /// Its restrictions are configured through storage: transfers above the amount at slot 2 revert
+    /// if it is set, `transferFrom` reverts if slot 3 is set and `approve` reverts if slot 4 is
+    /// set.
+    fn mock_token_code() -> Bytes {
+        assemble(&format!(
+            "
+            PUSH1 0 CALLDATALOAD PUSH1 0xe0 SHR
+            DUP1 PUSH4 0x70a08231 EQ @balance_of JUMPI
+            DUP1 PUSH4 0xa9059cbb EQ @transfer JUMPI
+            DUP1 PUSH4 0x23b872dd EQ @transfer_from JUMPI
+            DUP1 PUSH4 0x095ea7b3 EQ @approve JUMPI
+            fail: PUSH1 0 DUP1 REVERT
+
+            ; balanceOf(address account)
+            balance_of:
+            PUSH1 4 CALLDATALOAD PUSH1 0 MSTORE PUSH1 0 PUSH1 0x20 MSTORE
+            PUSH1 0x40 PUSH1 0 KECCAK256 SLOAD
+            PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+
+            ; transfer(address to, uint256 amount)
+            transfer:
+            PUSH1 0x24 CALLDATALOAD PUSH1 4 CALLDATALOAD CALLER
+            {transfer}
+            @return_true JUMP
+
+            ; transferFrom(address from, address to, uint256 amount), spending the caller's allowance
+            transfer_from:
+            PUSH1 3 SLOAD @fail JUMPI
+            PUSH1 4 CALLDATALOAD PUSH1 0 MSTORE PUSH1 1 PUSH1 0x20 MSTORE PUSH1 0x40 PUSH1 0 KECCAK256
+            PUSH1 0x20 MSTORE CALLER PUSH1 0 MSTORE PUSH1 0x40 PUSH1 0 KECCAK256
+            DUP1 SLOAD PUSH1 0x44 CALLDATALOAD DUP2 DUP2 GT @fail JUMPI
+            SWAP1 SUB SWAP1 SSTORE
+            PUSH1 0x44 CALLDATALOAD PUSH1 0x24 CALLDATALOAD PUSH1 4 CALLDATALOAD
+            {transfer_from}
+            @return_true JUMP
+
+            ; approve(address spender, uint256 amount)
+            approve:
+            PUSH1 4 SLOAD @fail JUMPI
+            CALLER PUSH1 0 MSTORE PUSH1 1 PUSH1 0x20 MSTORE PUSH1 0x40 PUSH1 0 KECCAK256
+            PUSH1 0x20 MSTORE PUSH1 4 CALLDATALOAD PUSH1 0 MSTORE PUSH1 0x40 PUSH1 0 KECCAK256
+            PUSH1 0x24 CALLDATALOAD SWAP1 SSTORE
+
+            return_true: PUSH1 1 PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+            ",
+            transfer = move_balance("transfer"),
+            transfer_from = move_balance("transfer_from"),
+        ))
+    }
+
+    /// Transfers `amount` of `token` to the address at calldata offset `to_offset`, skipping
+    /// zero amounts.
+    ///
+    /// Expects the stack `token amount` (top first) and consumes it.
+    fn send_token(label: &str, to_offset: &str) -> String {
+        format!(
+            "
+            DUP2 ISZERO @{label}_skip JUMPI
+            PUSH4 0xa9059cbb PUSH1 0xe0 SHL PUSH1 0 MSTORE
+            PUSH1 {to_offset} CALLDATALOAD PUSH1 4 MSTORE DUP2 PUSH1 0x24 MSTORE
+            PUSH1 0 PUSH1 0 PUSH1 0x44 PUSH1 0 PUSH1 0 DUP6 GAS CALL ISZERO @fail JUMPI
+            {label}_skip: POP POP
+            "
+        )
+    }
+
+    /// Code of a Uniswap V2 pair of the tokens at slots 0 and 1, with the reserves at slots 2 and
+    /// 3. Swaps send the requested amounts without any checks.
+    fn mock_pair_code() -> Bytes {
+        assemble(&format!(
+            "
+            PUSH1 0 CALLDATALOAD PUSH1 0xe0 SHR
+            DUP1 PUSH4 0x0dfe1681 EQ @token0 JUMPI
+            DUP1 PUSH4 0xd21220a7 EQ @token1 JUMPI
+            DUP1 PUSH4 0x0902f1ac EQ @get_reserves JUMPI
+            DUP1 PUSH4 0x022c0d9f EQ @swap JUMPI
+            fail: PUSH1 0 DUP1 REVERT
+
+            token0: PUSH1 0 SLOAD PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+            token1: PUSH1 1 SLOAD PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+
+            get_reserves:
+            PUSH1 2 SLOAD PUSH1 0 MSTORE PUSH1 3 SLOAD PUSH1 0x20 MSTORE PUSH1 0 PUSH1 0x40 MSTORE
+            PUSH1 0x60 PUSH1 0 RETURN
+
+            ; swap(uint256 amount0Out, uint256 amount1Out, address to, bytes data)
+            swap:
+            PUSH1 4 CALLDATALOAD PUSH1 0 SLOAD
+            {send0}
+            PUSH1 0x24 CALLDATALOAD PUSH1 1 SLOAD
+            {send1}
+            STOP
+            ",
+            send0 = send_token("send0", "0x44"),
+            send1 = send_token("send1", "0x44"),
+        ))
+    }
+
+    /// Code of a Uniswap V3 pool of the tokens at slots 0 and 1, quoting a constant price of 1
+    /// and charging a 0.3% fee. Only exact input swaps are supported.
+    fn mock_v3_pool_code() -> Bytes {
+        assemble(
+            "
+            PUSH1 0 CALLDATALOAD PUSH1 0xe0 SHR
+            DUP1 PUSH4 0x0dfe1681 EQ @token0 JUMPI
+            DUP1 PUSH4 0xd21220a7 EQ @token1 JUMPI
+            DUP1 PUSH4 0x128acb08 EQ @swap JUMPI
+            fail: PUSH1 0 DUP1 REVERT
+
+            token0: PUSH1 0 SLOAD PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+            token1: PUSH1 1 SLOAD PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+
+            ; swap(address recipient, bool zeroForOne, int256 amountSpecified, uint160 limit,
+            ;      bytes data)
+            swap:
+            PUSH1 0x44 CALLDATALOAD PUSH1 0 DUP2 SGT ISZERO @fail JUMPI
+            PUSH2 997 DUP2 MUL PUSH2 1000 SWAP1 DIV
+            PUSH1 0x24 CALLDATALOAD PUSH1 1 SLOAD PUSH1 0 SLOAD
+            DUP3 @sorted JUMPI SWAP1
+            sorted: ; token_in token_out zero_for_one amount_out amount_in
+
+            ; send the output to the recipient
+            PUSH4 0xa9059cbb PUSH1 0xe0 SHL PUSH1 0 MSTORE
+            PUSH1 4 CALLDATALOAD PUSH1 4 MSTORE DUP4 PUSH1 0x24 MSTORE
+            PUSH1 0 PUSH1 0 PUSH1 0x44 PUSH1 0 PUSH1 0 DUP7 GAS CALL ISZERO @fail JUMPI
+
+            ; balance of the input token before the callback
+            PUSH4 0x70a08231 PUSH1 0xe0 SHL PUSH1 0 MSTORE ADDRESS PUSH1 4 MSTORE
+            PUSH1 0x20 PUSH1 0 PUSH1 0x24 PUSH1 0 DUP5 GAS STATICCALL ISZERO @fail JUMPI
+            PUSH1 0 MLOAD
+
+            ; uniswapV3SwapCallback(int256 amount0Delta, int256 amount1Delta, bytes data)
+            PUSH4 0xfa461e33 PUSH1 0xe0 SHL PUSH1 0 MSTORE
+            DUP6 DUP6 PUSH1 0 SUB DUP6 @callback_sorted JUMPI SWAP1
+            callback_sorted:
+            PUSH1 0x24 MSTORE PUSH1 4 MSTORE
+            PUSH1 0x60 PUSH1 0x44 MSTORE PUSH1 0x20 PUSH1 0x64 MSTORE
+            PUSH1 0xc4 CALLDATALOAD PUSH1 0x84 MSTORE
+            PUSH1 0 PUSH1 0 PUSH1 0xa4 PUSH1 0 PUSH1 0 CALLER GAS CALL ISZERO @fail JUMPI
+
+            ; the pool has to be paid the input amount
+            PUSH4 0x70a08231 PUSH1 0xe0 SHL PUSH1 0 MSTORE ADDRESS PUSH1 4 MSTORE
+            PUSH1 0x20 PUSH1 0 PUSH1 0x24 PUSH1 0 DUP6 GAS STATICCALL ISZERO @fail JUMPI
+            PUSH1 0 MLOAD
+            DUP7 DUP3 ADD DUP2 LT @fail JUMPI
+
+            ; return (amount0, amount1)
+            DUP7 DUP7 PUSH1 0 SUB DUP7 @result_sorted JUMPI SWAP1
+            result_sorted:
+            PUSH1 0x20 MSTORE PUSH1 0 MSTORE PUSH1 0x40 PUSH1 0 RETURN
+            ",
+        )
+    }
+
+    /// Token sold in the tests
+    const TOKEN: Address = address!("0000000000000000000000000000000000001234");
+    /// Token the sold token is traded against
+    const QUOTE_TOKEN: Address = address!("0000000000000000000000000000000000004321");
+    const PAIR: Address = address!("0000000000000000000000000000000000005678");
+    const V3_POOL: Address = address!("0000000000000000000000000000000000008765");
+
+    /// Runtime code and storage of a token
+    enum TokenSetup {
+        /// A standard ERC20 token
+        Standard,
+        /// The mock token, configured with the given storage
+        Mock(Vec<(u64, U256)>),
+    }
+
+    #[rstest]
+    #[case::sellable_v2(
+        TokenSetup::Standard,
+        SellPool::UniswapV2(PAIR),
+        SellCheck::Sellable { amount_out: U256::from(996006981039903216u64) }
+    )]
+    #[case::sellable_v3(
+        TokenSetup::Standard,
+        SellPool::UniswapV3(V3_POOL),
+        SellCheck::Sellable { amount_out: U256::from(997_000_000_000_000_000u64) }
+    )]
+    #[case::max_transaction_limit_v2(
+        TokenSetup::Mock(vec![(2, U256::from(500_000_000_000_000_000u64))]),
+        SellPool::UniswapV2(PAIR),
+        SellCheck::MaxTransactionLimit { max_amount: U256::from(499524414062500000u64) }
+    )]
+    #[case::max_transaction_limit_v3(
+        TokenSetup::Mock(vec![(2, U256::from(500_000_000_000_000_000u64))]),
+        SellPool::UniswapV3(V3_POOL),
+        SellCheck::MaxTransactionLimit { max_amount: U256::from(499524414062500000u64) }
+    )]
+    #[case::transfer_from_restricted_v2(
+        TokenSetup::Mock(vec![(3, U256::from(1))]),
+        SellPool::UniswapV2(PAIR),
+        SellCheck::TransferRestricted(String::new())
+    )]
+    #[case::transfer_from_restricted_v3(
+        TokenSetup::Mock(vec![(3, U256::from(1))]),
+        SellPool::UniswapV3(V3_POOL),
+        SellCheck::TransferRestricted(String::new())
+    )]
+    #[case::approve_reverted(
+        TokenSetup::Mock(vec![(4, U256::from(1))]),
+        SellPool::UniswapV2(PAIR),
+        SellCheck::ApproveReverted(String::new())
+    )]
+    fn test_check_sellability(
+        #[case] token_setup: TokenSetup,
+        #[case] pool: SellPool,
+        #[case] expected: SellCheck,
+    ) {
+        let reserve = U256::from(10).pow(U256::from(21));
+        let slots = (ERC20Slots::new(U256::ZERO, U256::from(1)), ContractCompiler::Solidity);
+        let engine = create_engine(PreCachedDB::new().unwrap(), false).unwrap();
+        let standard_token_code = Bytes::from_static(ERC20_BYTECODE);
+        // Both pools hold reserves of both tokens
+        let mut token_accounts = Vec::new();
+        for address in [TOKEN, QUOTE_TOKEN] {
+            let mut overwrite_factory =
+                ERC20OverwriteFactory::new(address, slots.0.clone(), slots.1);
+            overwrite_factory.set_balance(reserve, PAIR);
+            overwrite_factory.set_balance(reserve, V3_POOL);
+            let mut storage = overwrite_factory
+                .get_overwrites()
+                .remove(&address)
+                .unwrap();
+            let code = match (&token_setup, address) {
+                (TokenSetup::Mock(config), TOKEN) => {
+                    storage.extend(
+                        config
+                            .iter()
+                            .map(|(slot, value)| (U256::from(*slot), *value)),
+                    );
+                    mock_token_code()
+                }
+                _ => standard_token_code.clone(),
+            };
+            token_accounts.push((address, code, storage));
+        }
+        let tokens = HashMap::from([
+            (U256::ZERO, U256::from_be_slice(TOKEN.as_slice())),
+            (U256::from(1), U256::from_be_slice(QUOTE_TOKEN.as_slice())),
+        ]);
+        let mut pair_storage = tokens.clone();
+        pair_storage.extend([(U256::from(2), reserve), (U256::from(3), reserve)]);
+        for (address, code, storage) in token_accounts
+            .into_iter()
+            .chain([(PAIR, mock_pair_code(), pair_storage), (V3_POOL, mock_v3_pool_code(), tokens)])
+        {
+            let code = Bytecode::new_raw(code);
+            engine.state.init_account(
+                address,
+                AccountInfo::new(U256::ZERO, 0, code.hash_slow(), code),
+                Some(storage),
+                false,
+            );
+        }
+        let token = Token::new(
+            "0x0000000000000000000000000000000000001234",
+            18,
+            "TKN",
+            BigUint::from(50_000u64),
+        );
+
+        let result = check_sellability(
+            &token,
+            pool,
+            token.one(),
+            Some(slots),
+            &BlockHeader::default(),
+            &engine,
+        )
+        .unwrap();
+
+        match expected {
+            SellCheck::ApproveReverted(_) => {
+                assert!(matches!(result, SellCheck::ApproveReverted(_)), "{:?}", result)
+            }
+            SellCheck::TransferRestricted(_) => {
+                assert!(matches!(result, SellCheck::TransferRestricted(_)), "{:?}", result)
+            }
+            expected => assert_eq!(result, expected),
+        }
+    }
+
+    #[test]
+    fn test_get_amount_out_v2() {
+        let reserve = U256::from(10).pow(U256::from(21));
+
+        assert_eq!(
+            get_amount_out_v2(U256::from(10).pow(U256::from(18)), reserve, reserve),
+            U256::from(996006981039903216u64)
+        );
+        assert_eq!(get_amount_out_v2(U256::from(1), U256::ZERO, U256::ZERO), U256::ZERO);
+    }
+}
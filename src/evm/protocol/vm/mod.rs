@@ -1,9 +1,13 @@
 mod adapter_contract;
 pub mod constants;
 mod erc20_token;
+pub mod honeypot;
 mod models;
 pub mod state;
 pub mod state_builder;
+pub mod token_behaviour;
 pub mod tycho_decoder;
-mod tycho_simulation_contract;
+pub mod tycho_simulation_contract;
 pub mod utils;
+
+pub use erc20_token::{ERC20Slots, SlotLayout};
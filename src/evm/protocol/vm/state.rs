@@ -3,6 +3,7 @@ use std::{
     collections::{HashMap, HashSet},
     fmt::Debug,
     str::FromStr,
+    sync::{Arc, RwLock},
 };
 
 use alloy_primitives::{Address, U256};
@@ -12,6 +13,7 @@ use revm::DatabaseRef;
 use tycho_core::{dto::ProtocolStateDelta, Bytes};
 
 use super::{
+    adapter_contract::Trade,
     constants::{EXTERNAL_ACCOUNT, MAX_BALANCE},
     erc20_token::{ERC20OverwriteFactory, ERC20Slots, Overwrites},
     models::Capability,
@@ -23,7 +25,10 @@ use crate::{
             engine_db_interface::EngineDatabaseInterface, simulation_db::BlockHeader,
             tycho_db::PreCachedDB,
         },
-        protocol::{u256_num::u256_to_biguint, utils::bytes_to_address},
+        protocol::{
+            u256_num::{biguint_to_u256, u256_to_biguint},
+            utils::bytes_to_address,
+        },
         ContractCompiler, SlotId,
     },
     models::Token,
@@ -34,6 +39,12 @@ use crate::{
     },
 };
 
+/// Inverse of the relative tolerance of the sell amount found by `get_amount_in` when searching,
+/// i.e. the search stops once the sell amount is known within `1 / AMOUNT_IN_SEARCH_TOLERANCE`.
+const AMOUNT_IN_SEARCH_TOLERANCE: u64 = 1_000_000;
+/// Maximum number of swaps simulated by `get_amount_in` when searching
+const MAX_AMOUNT_IN_SEARCH_ITERATIONS: u32 = 64;
+
 #[derive(Clone, Debug)]
 pub struct EVMPoolState<D: EngineDatabaseInterface + Clone + Debug>
 where
@@ -73,6 +84,13 @@ where
     manual_updates: bool,
     /// The adapter contract. This is used to interact with the protocol when running simulations
     adapter_contract: TychoSimulationContract<D>,
+    /// If set, spot prices are not computed eagerly for all token pairs whenever the cache is
+    /// cleared. Instead, they are computed on the first `spot_price` call for a pair and memoized
+    /// until the next cache clear.
+    lazy_spot_prices: bool,
+    /// Memoized spot prices computed on demand while `lazy_spot_prices` is set. Shared behind a
+    /// lock since `spot_price` only has immutable access to the state.
+    spot_price_cache: Arc<RwLock<HashMap<(Address, Address), f64>>>,
 }
 
 impl<D> EVMPoolState<D>
@@ -113,9 +131,20 @@ where
             token_storage_slots,
             manual_updates,
             adapter_contract,
+            lazy_spot_prices: false,
+            spot_price_cache: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
+    /// Enables or disables lazy spot price computation.
+    ///
+    /// In lazy mode, `clear_all_cache` no longer recomputes the spot prices of every token pair.
+    /// Instead, a pair's spot price is computed and memoized the first time `spot_price` is called
+    /// for it.
+    pub fn set_lazy_spot_prices(&mut self, lazy: bool) {
+        self.lazy_spot_prices = lazy;
+    }
+
     /// Ensures the pool supports the given capability
     ///
     /// # Arguments
@@ -153,7 +182,8 @@ where
     /// 1. Ensures the pool has the required capability to perform price calculations.
     /// 2. Iterates over all permutations of token pairs (sell token and buy token). For each pair:
     ///    - Retrieves all possible overwrites, considering the maximum balance limit.
-    ///    - Calculates the sell amount limit, considering the overwrites.
+    ///    - Calculates the sell amount limit, considering the overwrites. This is skipped if the
+    ///      pool supports `MarginalPrice`, in which case the price is queried for a zero amount.
     ///    - Invokes the adapter contract's `price` function to retrieve the calculated price for
     ///      the token pair, considering the sell amount limit.
     ///    - Processes the price based on whether the `ScaledPrice` capability is present:
@@ -165,7 +195,8 @@ where
     ///
     /// # Usage
     ///
-    /// Spot prices need to be set before attempting to retrieve prices using `spot_price`.
+    /// Spot prices need to be set before attempting to retrieve prices using `spot_price`, unless
+    /// lazy spot prices are enabled (see `set_lazy_spot_prices`).
     ///
     /// Tip: Setting spot prices on the pool every time the pool actually changes will result in
     /// faster price fetching than if prices are only set immediately before attempting to retrieve
@@ -183,38 +214,17 @@ where
         {
             let sell_token_address = bytes_to_address(sell_token_address)?;
             let buy_token_address = bytes_to_address(buy_token_address)?;
-            let overwrites = Some(self.get_overwrites(
-                vec![sell_token_address, buy_token_address],
-                *MAX_BALANCE / U256::from(100),
-            )?);
-            let sell_amount_limit = self.get_sell_amount_limit(
-                vec![sell_token_address, buy_token_address],
-                overwrites.clone(),
-            )?;
-            let price_result = self.adapter_contract.price(
-                &self.id,
-                sell_token_address,
-                buy_token_address,
-                vec![sell_amount_limit / U256::from(100)],
-                self.block.number,
-                overwrites,
-            )?;
+            let adapter_price = self.get_adapter_price(sell_token_address, buy_token_address)?;
 
             let price = if self
                 .capabilities
                 .contains(&Capability::ScaledPrice)
             {
-                *price_result.first().ok_or_else(|| {
-                    SimulationError::FatalError("Calculated price array is empty".to_string())
-                })?
+                adapter_price
             } else {
-                let unscaled_price = price_result.first().ok_or_else(|| {
-                    SimulationError::FatalError("Calculated price array is empty".to_string())
-                })?;
                 let sell_token_decimals = self.get_decimals(tokens, &sell_token_address)?;
                 let buy_token_decimals = self.get_decimals(tokens, &buy_token_address)?;
-                *unscaled_price * 10f64.powi(sell_token_decimals as i32) /
-                    10f64.powi(buy_token_decimals as i32)
+                scale_price(adapter_price, sell_token_decimals, buy_token_decimals)
             };
 
             self.spot_prices
@@ -223,6 +233,52 @@ where
         Ok(())
     }
 
+    /// Queries the adapter contract for the price of `sell_token` denominated in `buy_token`.
+    ///
+    /// If the pool supports `Capability::MarginalPrice`, the price is queried for a sell amount of
+    /// zero, which yields the marginal price directly and avoids an additional `getLimits` call.
+    /// Otherwise, the price is queried for 1% of the sell amount limit.
+    ///
+    /// The returned price is not scaled by token decimals.
+    fn get_adapter_price(
+        &self,
+        sell_token_address: Address,
+        buy_token_address: Address,
+    ) -> Result<f64, SimulationError> {
+        self.ensure_capability(Capability::PriceFunction)?;
+        let overwrites = Some(self.get_overwrites(
+            vec![sell_token_address, buy_token_address],
+            *MAX_BALANCE / U256::from(100),
+        )?);
+        let amount = if self
+            .capabilities
+            .contains(&Capability::MarginalPrice)
+        {
+            U256::ZERO
+        } else {
+            let sell_amount_limit = self.get_sell_amount_limit(
+                vec![sell_token_address, buy_token_address],
+                overwrites.clone(),
+            )?;
+            sell_amount_limit / U256::from(100)
+        };
+        let price_result = self.adapter_contract.price(
+            &self.id,
+            sell_token_address,
+            buy_token_address,
+            vec![amount],
+            self.block.number,
+            overwrites,
+        )?;
+
+        price_result
+            .first()
+            .cloned()
+            .ok_or_else(|| {
+                SimulationError::FatalError("Calculated price array is empty".to_string())
+            })
+    }
+
     fn get_decimals(
         &self,
         tokens: &HashMap<Bytes, Token>,
@@ -276,7 +332,13 @@ where
             .engine
             .clear_temp_storage();
         self.block_lasting_overwrites.clear();
-        self.set_spot_prices(tokens)?;
+        if self.lazy_spot_prices {
+            // Replace rather than clear the cache, it may still be shared with older clones.
+            self.spot_prices.clear();
+            self.spot_price_cache = Arc::new(RwLock::new(HashMap::new()));
+        } else {
+            self.set_spot_prices(tokens)?;
+        }
         Ok(())
     }
 
@@ -386,6 +448,209 @@ where
         Ok(balance_overwrites)
     }
 
+    /// Calculates the amount of `token_in` required to receive exactly `amount_out` of
+    /// `token_out` (an exact-out swap).
+    ///
+    /// If the adapter supports `Capability::BuySide` the adapter's `swap` is called as a buy
+    /// order. Otherwise, the required sell amount is found by a binary search over sell-side
+    /// swaps, bounded by the pool's sell limit. The search stops once the sell amount is known
+    /// within a relative tolerance of one millionth, so the returned amount can exceed the
+    /// smallest sufficient amount by up to that much.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount_out` - The exact amount of `token_out` to buy.
+    /// * `token_in` - The token being sold.
+    /// * `token_out` - The token being bought.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<GetAmountOutResult, SimulationError>` - On success, the `amount` of the result is
+    ///   the amount of `token_in` that has to be sold, together with the gas used and the new state
+    ///   of the pool after the swap. If `amount_out` exceeds what the pool can provide, an
+    ///   `InvalidInput` error is returned containing the result for the maximum buyable amount.
+    pub fn get_amount_in(
+        &self,
+        amount_out: BigUint,
+        token_in: &Token,
+        token_out: &Token,
+    ) -> Result<GetAmountOutResult, SimulationError> {
+        if self
+            .capabilities
+            .contains(&Capability::BuySide)
+        {
+            self.get_amount_in_buy_side(amount_out, token_in, token_out)
+        } else {
+            self.get_amount_in_by_search(amount_out, token_in, token_out)
+        }
+    }
+
+    fn get_amount_in_buy_side(
+        &self,
+        amount_out: BigUint,
+        token_in: &Token,
+        token_out: &Token,
+    ) -> Result<GetAmountOutResult, SimulationError> {
+        let sell_token_address = bytes_to_address(&token_in.address)?;
+        let buy_token_address = bytes_to_address(&token_out.address)?;
+        let buy_amount = biguint_to_u256(&amount_out);
+        let overwrites = self.get_overwrites(
+            vec![sell_token_address, buy_token_address],
+            *MAX_BALANCE / U256::from(100),
+        )?;
+        let (sell_amount_limit, buy_amount_limit) = self.adapter_contract.get_limits(
+            &self.id,
+            sell_token_address,
+            buy_token_address,
+            self.block.number,
+            Some(overwrites.clone()),
+        )?;
+        let (buy_amount_respecting_limit, buy_amount_exceeds_limit) = if self
+            .capabilities
+            .contains(&Capability::HardLimits) &&
+            buy_amount_limit < buy_amount
+        {
+            (buy_amount_limit, true)
+        } else {
+            (buy_amount, false)
+        };
+
+        let overwrites_with_sell_limit =
+            self.get_overwrites(vec![sell_token_address, buy_token_address], sell_amount_limit)?;
+        let complete_overwrites = self.merge(&overwrites, &overwrites_with_sell_limit);
+
+        let (trade, new_state) = self.swap_and_update(
+            sell_token_address,
+            buy_token_address,
+            true,
+            buy_amount_respecting_limit,
+            complete_overwrites,
+        )?;
+
+        // For buy orders the adapter returns the amount of sell token spent.
+        let result = GetAmountOutResult::new(
+            u256_to_biguint(trade.received_amount),
+            u256_to_biguint(trade.gas_used),
+            Box::new(new_state),
+        );
+        if buy_amount_exceeds_limit {
+            return Err(SimulationError::LimitExceeded {
+                limit: u256_to_biguint(buy_amount_limit),
+                partial_result: Some(result),
+            });
+        }
+        Ok(result)
+    }
+
+    fn get_amount_in_by_search(
+        &self,
+        amount_out: BigUint,
+        token_in: &Token,
+        token_out: &Token,
+    ) -> Result<GetAmountOutResult, SimulationError> {
+        let sell_token_address = bytes_to_address(&token_in.address)?;
+        let buy_token_address = bytes_to_address(&token_out.address)?;
+        let overwrites = self.get_overwrites(
+            vec![sell_token_address, buy_token_address],
+            *MAX_BALANCE / U256::from(100),
+        )?;
+        let sell_amount_limit = self
+            .get_sell_amount_limit(vec![sell_token_address, buy_token_address], Some(overwrites))?;
+
+        // The largest possible sell amount bounds the search from above.
+        let mut best =
+            self.get_amount_out(u256_to_biguint(sell_amount_limit), token_in, token_out)?;
+        if best.amount < amount_out {
+            return Err(SimulationError::LimitExceeded {
+                limit: best.amount,
+                partial_result: Some(GetAmountOutResult::new(
+                    u256_to_biguint(sell_amount_limit),
+                    best.gas,
+                    best.new_state,
+                )),
+            });
+        }
+
+        // Find the smallest sell amount that yields at least `amount_out`, up to a relative
+        // tolerance. `low` never satisfies the target, `high` always does.
+        let mut low = U256::ZERO;
+        let mut high = sell_amount_limit;
+        let mut iterations = 0;
+        while high - low > U256::from(1u64) &&
+            high - low > high / U256::from(AMOUNT_IN_SEARCH_TOLERANCE) &&
+            iterations < MAX_AMOUNT_IN_SEARCH_ITERATIONS
+        {
+            iterations += 1;
+            let mid = low + (high - low) / U256::from(2u64);
+            let result = self.get_amount_out(u256_to_biguint(mid), token_in, token_out)?;
+            if result.amount >= amount_out {
+                high = mid;
+                best = result;
+            } else {
+                low = mid;
+            }
+        }
+
+        Ok(GetAmountOutResult::new(u256_to_biguint(high), best.gas, best.new_state))
+    }
+
+    /// Executes a swap through the adapter contract and returns the resulting trade together with
+    /// a copy of this state that has the swap's storage changes and the new price applied.
+    fn swap_and_update(
+        &self,
+        sell_token_address: Address,
+        buy_token_address: Address,
+        is_buy: bool,
+        amount: U256,
+        overwrites: HashMap<Address, Overwrites>,
+    ) -> Result<(Trade, Self), SimulationError> {
+        let (trade, state_changes) = self.adapter_contract.swap(
+            &self.id,
+            sell_token_address,
+            buy_token_address,
+            is_buy,
+            amount,
+            self.block.number,
+            Some(overwrites),
+        )?;
+
+        let mut new_state = self.clone();
+        // Lazily computed prices refer to the state before the swap
+        new_state.spot_price_cache = Arc::new(RwLock::new(HashMap::new()));
+
+        // Apply state changes to the new state
+        for (address, state_update) in state_changes {
+            if let Some(storage) = state_update.storage {
+                let block_overwrites = new_state
+                    .block_lasting_overwrites
+                    .entry(address)
+                    .or_default();
+                for (slot, value) in storage {
+                    let slot = U256::from_str(&slot.to_string()).map_err(|_| {
+                        SimulationError::FatalError("Failed to decode slot index".to_string())
+                    })?;
+                    let value = U256::from_str(&value.to_string()).map_err(|_| {
+                        SimulationError::FatalError("Failed to decode slot overwrite".to_string())
+                    })?;
+                    block_overwrites.insert(slot, value);
+                }
+            }
+        }
+
+        // Update spot prices
+        let new_price = trade.price;
+        if new_price != 0.0f64 {
+            new_state
+                .spot_prices
+                .insert((sell_token_address, buy_token_address), new_price);
+            new_state
+                .spot_prices
+                .insert((buy_token_address, sell_token_address), 1.0f64 / new_price);
+        }
+
+        Ok((trade, new_state))
+    }
+
     fn merge(
         &self,
         target: &HashMap<Address, Overwrites>,
@@ -403,6 +668,15 @@ where
         merged
     }
 
+    /// Returns whether the pool depends on any of the given contracts.
+    ///
+    /// Used to invalidate pools whose contracts were deleted.
+    pub fn involves_any_contract(&self, contracts: &HashSet<Address>) -> bool {
+        !self
+            .involved_contracts
+            .is_disjoint(contracts)
+    }
+
     #[cfg(test)]
     pub fn get_involved_contracts(&self) -> HashSet<Address> {
         self.involved_contracts.clone()
@@ -432,13 +706,41 @@ where
     fn spot_price(&self, base: &Token, quote: &Token) -> Result<f64, SimulationError> {
         let base_address = bytes_to_address(&base.address)?;
         let quote_address = bytes_to_address(&quote.address)?;
-        self.spot_prices
+        if let Some(price) = self
+            .spot_prices
             .get(&(base_address, quote_address))
-            .cloned()
-            .ok_or(SimulationError::FatalError(format!(
-                "Spot price not found for base token {} and quote token {}",
-                base_address, quote_address
-            )))
+        {
+            return Ok(*price);
+        }
+        if !self.lazy_spot_prices {
+            return Err(SimulationError::UnsupportedPair {
+                sell_token: base.address.clone(),
+                buy_token: quote.address.clone(),
+            });
+        }
+
+        if let Some(price) = self
+            .spot_price_cache
+            .read()
+            .unwrap()
+            .get(&(base_address, quote_address))
+        {
+            return Ok(*price);
+        }
+        let adapter_price = self.get_adapter_price(base_address, quote_address)?;
+        let price = if self
+            .capabilities
+            .contains(&Capability::ScaledPrice)
+        {
+            adapter_price
+        } else {
+            scale_price(adapter_price, base.decimals, quote.decimals)
+        };
+        self.spot_price_cache
+            .write()
+            .unwrap()
+            .insert((base_address, quote_address), price);
+        Ok(price)
     }
 
     fn get_amount_out(
@@ -472,59 +774,25 @@ where
             self.get_overwrites(vec![sell_token_address, buy_token_address], sell_amount_limit)?;
         let complete_overwrites = self.merge(&overwrites, &overwrites_with_sell_limit);
 
-        let (trade, state_changes) = self.adapter_contract.swap(
-            &self.id,
+        let (trade, new_state) = self.swap_and_update(
             sell_token_address,
             buy_token_address,
             false,
             sell_amount_respecting_limit,
-            self.block.number,
-            Some(complete_overwrites),
+            complete_overwrites,
         )?;
 
-        let mut new_state = self.clone();
-
-        // Apply state changes to the new state
-        for (address, state_update) in state_changes {
-            if let Some(storage) = state_update.storage {
-                let block_overwrites = new_state
-                    .block_lasting_overwrites
-                    .entry(address)
-                    .or_default();
-                for (slot, value) in storage {
-                    let slot = U256::from_str(&slot.to_string()).map_err(|_| {
-                        SimulationError::FatalError("Failed to decode slot index".to_string())
-                    })?;
-                    let value = U256::from_str(&value.to_string()).map_err(|_| {
-                        SimulationError::FatalError("Failed to decode slot overwrite".to_string())
-                    })?;
-                    block_overwrites.insert(slot, value);
-                }
-            }
-        }
-
-        // Update spot prices
-        let new_price = trade.price;
-        if new_price != 0.0f64 {
-            new_state
-                .spot_prices
-                .insert((sell_token_address, buy_token_address), new_price);
-            new_state
-                .spot_prices
-                .insert((buy_token_address, sell_token_address), 1.0f64 / new_price);
-        }
-
         let buy_amount = trade.received_amount;
 
         if sell_amount_exceeds_limit {
-            return Err(SimulationError::InvalidInput(
-                format!("Sell amount exceeds limit {}", sell_amount_limit),
-                Some(GetAmountOutResult::new(
+            return Err(SimulationError::LimitExceeded {
+                limit: u256_to_biguint(sell_amount_limit),
+                partial_result: Some(GetAmountOutResult::new(
                     u256_to_biguint(buy_amount),
                     u256_to_biguint(trade.gas_used),
                     Box::new(new_state.clone()),
                 )),
-            ));
+            });
         }
         Ok(GetAmountOutResult::new(
             u256_to_biguint(buy_amount),
@@ -580,6 +848,11 @@ where
     }
 }
 
+/// Scales an adapter price by the decimals of the sell and buy token.
+fn scale_price(price: f64, sell_token_decimals: usize, buy_token_decimals: usize) -> f64 {
+    price * 10f64.powi(sell_token_decimals as i32) / 10f64.powi(buy_token_decimals as i32)
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
@@ -647,6 +920,7 @@ mod tests {
             )
             .unwrap(),
             timestamp: 1722875891,
+            ..Default::default()
         };
 
         for account in accounts.clone() {
@@ -675,6 +949,7 @@ mod tests {
             )
             .expect("Invalid block hash"),
             timestamp: 0,
+            ..Default::default()
         };
 
         let pool_id: String =
@@ -851,11 +1126,87 @@ mod tests {
         assert!(result.is_err());
 
         match result {
-            Err(SimulationError::InvalidInput(msg1, amount_out_result)) => {
-                assert_eq!(msg1, "Sell amount exceeds limit 100279494253364362835");
-                assert!(amount_out_result.is_some());
+            Err(SimulationError::LimitExceeded { limit, partial_result }) => {
+                assert_eq!(limit, BigUint::from_str("100279494253364362835").unwrap());
+                assert!(partial_result.is_some());
+            }
+            _ => panic!(
+                "Test failed: was expecting an Err(SimulationError::LimitExceeded {{ .. }}) value"
+            ),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_amount_in_buy_side() {
+        let pool_state = setup_pool_state().await;
+        let amount_out = BigUint::from_str("137780051463393923").unwrap();
+
+        let result = pool_state
+            .get_amount_in(amount_out.clone(), &dai(), &bal())
+            .unwrap();
+
+        let amount_out_check = pool_state
+            .get_amount_out(result.amount.clone(), &dai(), &bal())
+            .unwrap();
+        assert_eq!(result.amount, BigUint::from_str("1000000000000000000").unwrap());
+        assert!(amount_out_check.amount >= amount_out);
+        let new_state = result
+            .new_state
+            .as_any()
+            .downcast_ref::<EVMPoolState<PreCachedDB>>()
+            .unwrap();
+        assert_ne!(new_state.spot_prices, pool_state.spot_prices);
+    }
+
+    #[tokio::test]
+    async fn test_get_amount_in_by_search() {
+        let mut pool_state = setup_pool_state().await;
+        pool_state
+            .capabilities
+            .remove(&Capability::BuySide);
+        let amount_out = BigUint::from_str("137780051463393923").unwrap();
+
+        let result = pool_state
+            .get_amount_in(amount_out.clone(), &dai(), &bal())
+            .unwrap();
+
+        // The result yields at least `amount_out` and is the smallest such sell amount up to the
+        // search tolerance
+        let at_result = pool_state
+            .get_amount_out(result.amount.clone(), &dai(), &bal())
+            .unwrap();
+        let below_tolerance =
+            &result.amount - &result.amount / AMOUNT_IN_SEARCH_TOLERANCE - BigUint::one();
+        let below_result = pool_state
+            .get_amount_out(below_tolerance, &dai(), &bal())
+            .unwrap();
+        assert!(at_result.amount >= amount_out);
+        assert!(below_result.amount < amount_out);
+    }
+
+    #[tokio::test]
+    async fn test_get_amount_in_exceeds_limit() {
+        let mut pool_state = setup_pool_state().await;
+        pool_state
+            .capabilities
+            .remove(&Capability::BuySide);
+
+        let result = pool_state.get_amount_in(
+            BigUint::from_str("100000000000000000000").unwrap(),
+            &dai(),
+            &bal(),
+        );
+
+        match result {
+            Err(SimulationError::LimitExceeded { partial_result, .. }) => {
+                assert_eq!(
+                    partial_result.unwrap().amount,
+                    BigUint::from_str("100279494253364362835").unwrap()
+                );
+            }
+            _ => {
+                panic!("Test failed: was expecting an Err(SimulationError::LimitExceeded {{ .. }})")
             }
-            _ => panic!("Test failed: was expecting an Err(SimulationError::RetryDifferentInput(_, _)) value"),
         }
     }
 
@@ -889,6 +1240,39 @@ mod tests {
         assert_eq!(bal_limit, U256::from_str("13997408640689987484").unwrap());
     }
 
+    #[tokio::test]
+    async fn test_lazy_spot_prices() {
+        let mut pool_state = setup_pool_state().await;
+        pool_state.set_lazy_spot_prices(true);
+
+        assert!(pool_state.spot_prices.is_empty());
+        let dai_bal_spot_price = pool_state
+            .spot_price(&dai(), &bal())
+            .unwrap();
+        let bal_dai_spot_price = pool_state
+            .spot_price(&bal(), &dai())
+            .unwrap();
+
+        assert_eq!(dai_bal_spot_price, 0.137_778_914_319_047_9);
+        assert_eq!(bal_dai_spot_price, 7.071_503_245_428_246);
+        let cache = pool_state
+            .spot_price_cache
+            .read()
+            .unwrap()
+            .clone();
+        assert_eq!(cache.get(&(dai_addr(), bal_addr())), Some(&dai_bal_spot_price));
+        assert_eq!(cache.get(&(bal_addr(), dai_addr())), Some(&bal_dai_spot_price));
+
+        pool_state
+            .clear_all_cache(&HashMap::new())
+            .unwrap();
+        assert!(pool_state
+            .spot_price_cache
+            .read()
+            .unwrap()
+            .is_empty());
+    }
+
     #[tokio::test]
     async fn test_set_spot_prices() {
         let mut pool_state = setup_pool_state().await;
@@ -919,4 +1303,14 @@ mod tests {
         assert_eq!(dai_bal_spot_price, &0.137_778_914_319_047_9);
         assert_eq!(bal_dai_spot_price, &7.071_503_245_428_246);
     }
+
+    #[tokio::test]
+    async fn test_involves_any_contract() {
+        let mut pool_state = setup_pool_state().await;
+        let vault = Address::from_str("0xBA12222222228d8Ba445958a75a0704d566BF2C8").unwrap();
+        pool_state.involved_contracts = HashSet::from([vault]);
+
+        assert!(pool_state.involves_any_contract(&HashSet::from([vault, Address::ZERO])));
+        assert!(!pool_state.involves_any_contract(&HashSet::from([Address::ZERO])));
+    }
 }
@@ -17,11 +17,11 @@ use tycho_core::Bytes as TychoBytes;
 
 use super::{
     constants::{EXTERNAL_ACCOUNT, MAX_BALANCE},
-    erc20_token::{brute_force_slots, ERC20Slots},
+    erc20_token::{detect_slots, ERC20Slots},
     models::Capability,
     state::EVMPoolState,
     tycho_simulation_contract::TychoSimulationContract,
-    utils::get_code_for_contract,
+    utils::{get_code_for_contract, ErrorRegistry},
 };
 use crate::{
     evm::{
@@ -69,6 +69,7 @@ use crate::{
 ///         number: 1,
 ///         hash: Default::default(),
 ///         timestamp: 1632456789,
+///         ..Default::default()
 ///     };
 ///
 ///     // Optional: Add token balances
@@ -100,10 +101,12 @@ where
     stateless_contracts: Option<HashMap<String, Option<Vec<u8>>>>,
     token_storage_slots: Option<HashMap<Address, (ERC20Slots, ContractCompiler)>>,
     manual_updates: Option<bool>,
+    lazy_spot_prices: Option<bool>,
     trace: Option<bool>,
     engine: Option<SimulationEngine<D>>,
     adapter_contract: Option<TychoSimulationContract<D>>,
     adapter_contract_bytecode: Option<Bytecode>,
+    error_registry: Option<ErrorRegistry>,
 }
 
 impl<D> EVMPoolStateBuilder<D>
@@ -131,10 +134,12 @@ where
             stateless_contracts: None,
             token_storage_slots: None,
             manual_updates: None,
+            lazy_spot_prices: None,
             trace: None,
             engine: None,
             adapter_contract: None,
             adapter_contract_bytecode: None,
+            error_registry: None,
         }
     }
 
@@ -174,6 +179,13 @@ where
         self
     }
 
+    /// Compute spot prices on demand on the first `spot_price` call per pair and block, instead of
+    /// eagerly for all token pairs.
+    pub fn lazy_spot_prices(mut self, lazy_spot_prices: bool) -> Self {
+        self.lazy_spot_prices = Some(lazy_spot_prices);
+        self
+    }
+
     pub fn trace(mut self, trace: bool) -> Self {
         self.trace = Some(trace);
         self
@@ -194,6 +206,13 @@ where
         self
     }
 
+    /// Custom Solidity errors to decode from the adapter's reverts, in addition to the errors of
+    /// the `ISwapAdapter` interface.
+    pub fn error_registry(mut self, error_registry: ErrorRegistry) -> Self {
+        self.error_registry = Some(error_registry);
+        self
+    }
+
     /// Build the final EVMPoolState object
     pub async fn build(mut self, db: D) -> Result<EVMPoolState<D>, SimulationError> {
         let engine = if let Some(engine) = &self.engine {
@@ -215,13 +234,19 @@ where
             )?)
         };
 
+        if let Some(errors) = self.error_registry.take() {
+            self.adapter_contract = self
+                .adapter_contract
+                .map(|contract| contract.with_error_registry(errors));
+        }
+
         self.init_token_storage_slots()?;
         let capabilities = if let Some(capabilities) = &self.capabilities {
             capabilities.clone()
         } else {
             self.get_default_capabilities()?
         };
-        Ok(EVMPoolState::new(
+        let mut pool_state = EVMPoolState::new(
             self.id,
             self.tokens,
             self.block,
@@ -240,7 +265,9 @@ where
                     "Failed to get build engine: Adapter contract not initialized".to_string(),
                 )
             })?,
-        ))
+        );
+        pool_state.set_lazy_spot_prices(self.lazy_spot_prices.unwrap_or(false));
+        Ok(pool_state)
     }
 
     async fn get_default_engine(&self, db: D) -> Result<SimulationEngine<D>, SimulationError> {
@@ -317,7 +344,7 @@ where
                     .get_or_insert(HashMap::new())
                     .insert(
                         t_erc20_address,
-                        brute_force_slots(
+                        detect_slots(
                             &t_erc20_address,
                             &self.block,
                             self.engine
@@ -431,9 +458,11 @@ where
             block_number: self.block.number,
             timestamp,
             overrides: Some(HashMap::new()),
+            account_overrides: None,
             caller: *EXTERNAL_ACCOUNT,
             value: U256::from(0u64),
             gas_limit: None,
+            env: engine.environment().clone(),
         };
 
         let sim_result = engine
@@ -466,7 +495,8 @@ mod tests {
         let tokens =
             vec![TychoBytes::from_str("0000000000000000000000000000000000000000").unwrap()];
         let balances = HashMap::new();
-        let block = BlockHeader { number: 1, hash: B256::default(), timestamp: 234 };
+        let block =
+            BlockHeader { number: 1, hash: B256::default(), timestamp: 234, ..Default::default() };
         let adapter_address =
             Address::from_str("0xA2C5C98A892fD6656a7F39A2f63228C0Bc846270").unwrap();
         let result = tokio_test::block_on(
@@ -489,7 +519,8 @@ mod tests {
         let token2 = TychoBytes::from_str("0000000000000000000000000000000000000002").unwrap();
         let token3 = TychoBytes::from_str("0000000000000000000000000000000000000003").unwrap();
         let tokens = vec![token2.clone(), token3.clone()];
-        let block = BlockHeader { number: 1, hash: B256::default(), timestamp: 234 };
+        let block =
+            BlockHeader { number: 1, hash: B256::default(), timestamp: 234, ..Default::default() };
         let balances = HashMap::new();
         let adapter_address =
             Address::from_str("0xA2C5C98A892fD6656a7F39A2f63228C0Bc846270").unwrap();
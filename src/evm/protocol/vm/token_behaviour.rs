@@ -0,0 +1,362 @@
+//! Detection of tokens that don't behave like plain ERC20 tokens on transfers.
+//!
+//! Tokens charging a fee on transfers, rebasing tokens and tokens restricting who can transfer
+//! break the assumption that a swap's output amount is what the receiver gets. The detection
+//! simulates transfers between synthetic accounts, funded by overwriting their balances, and
+//! compares the amount sent with the balance changes of both accounts.
+use std::{collections::HashMap, fmt::Debug};
+
+use alloy_primitives::{address, Address, U256};
+use alloy_sol_types::{sol, SolCall, SolValue};
+use revm::{primitives::Bytecode, DatabaseRef};
+
+use super::{
+    erc20_token::{detect_slots, ERC20OverwriteFactory, ERC20Slots, Overwrites},
+    utils::{coerce_error, ErrorRegistry},
+};
+use crate::{
+    evm::{
+        engine_db::{
+            engine_db_interface::EngineDatabaseInterface,
+            simulation_db::{AccountOverride, BlockHeader},
+        },
+        protocol::utils::bytes_to_address,
+        simulation::{SimulationEngine, SimulationEngineError, SimulationParameters},
+        ContractCompiler,
+    },
+    models::Token,
+    protocol::errors::SimulationError,
+};
+
+sol! {
+    function balanceOf(address account) external view returns (uint256);
+    function transfer(address to, uint256 amount) external returns (bool);
+}
+
+/// Synthetic account sending the tokens
+const SENDER: Address = address!("5e4de7c3fa32f2f1d8d1b1e28bd5e6e8bf5a0001");
+/// Synthetic account receiving the tokens
+const RECEIVER: Address = address!("5e4de7c3fa32f2f1d8d1b1e28bd5e6e8bf5a0002");
+
+/// Basis points in a whole
+const BPS: u64 = 10_000;
+
+/// How a token behaves on transfers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenBehaviour {
+    /// Transfers move exactly the sent amount.
+    Standard,
+    /// The receiver gets less than the sent amount. Taxes are in basis points of the amount.
+    FeeOnTransfer { buy_tax: u64, sell_tax: u64 },
+    /// Balances don't change by the transferred amounts, e.g. because they are derived from
+    /// shares.
+    Rebasing,
+    /// Transfers between fresh accounts revert or return `false`, e.g. because of a blocklist or
+    /// trading restrictions.
+    Blocklisting,
+}
+
+impl TokenBehaviour {
+    /// Records the taxes of the behaviour on `token`, i.e. sets `Token::buy_tax` and
+    /// `Token::sell_tax`. Tokens that don't charge fees on transfers get zero taxes.
+    pub fn apply_to(&self, token: &mut Token) {
+        (token.buy_tax, token.sell_tax) = match *self {
+            TokenBehaviour::FeeOnTransfer { buy_tax, sell_tax } => (buy_tax, sell_tax),
+            _ => (0, 0),
+        };
+    }
+}
+
+/// Balance changes caused by a transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransferOutcome {
+    Reverted,
+    /// Amount the sender's balance decreased by and the receiver's balance increased by, `None`
+    /// if the balance changed in the other direction.
+    Moved {
+        sent: Option<U256>,
+        received: Option<U256>,
+    },
+}
+
+/// Detects how a token behaves on transfers.
+///
+/// Transfers one whole token (according to its decimals) between synthetic accounts. If `pool`
+/// is given, buys are simulated as transfers from the pool and sells as transfers to the pool,
+/// as tokens often only tax trades. Otherwise both taxes are the tax of a transfer between the
+/// synthetic accounts.
+///
+/// Use `TokenBehaviour::apply_to` to record the detected taxes on the token.
+///
+/// # Parameters
+///
+/// * `token` - The token to check.
+/// * `pool` - A pool trading the token, if any.
+/// * `slots` - The storage slots of the token, used to fund the senders. Detected if not given.
+/// * `block` - The block header at which the simulation is executed.
+/// * `engine` - The simulation engine. Its database has to contain the token and the pool.
+///
+/// # Errors
+///
+/// Returns an error if the token's storage slots can't be detected or if the token's state
+/// can't be fetched.
+pub fn detect_token_behaviour<D: EngineDatabaseInterface + Clone + Debug>(
+    token: &Token,
+    pool: Option<Address>,
+    slots: Option<(ERC20Slots, ContractCompiler)>,
+    block: &BlockHeader,
+    engine: &SimulationEngine<D>,
+) -> Result<TokenBehaviour, SimulationError>
+where
+    <D as DatabaseRef>::Error: Debug,
+    <D as EngineDatabaseInterface>::Error: Debug,
+{
+    let token_address = bytes_to_address(&token.address)?;
+    let (slots, compiler) = match slots {
+        Some(slots) => slots,
+        None => detect_slots(&token_address, block, engine)?,
+    };
+    let checker = TransferChecker {
+        token: token_address,
+        slots,
+        compiler,
+        amount: token.one(),
+        block,
+        engine,
+    };
+    let (buy, sell) = match pool {
+        Some(pool) => (checker.transfer(pool, RECEIVER)?, checker.transfer(SENDER, pool)?),
+        None => {
+            let outcome = checker.transfer(SENDER, RECEIVER)?;
+            (outcome, outcome)
+        }
+    };
+    Ok(classify(checker.amount, buy, sell))
+}
+
+/// Classifies a token given the outcome of a buy and a sell of `amount`.
+fn classify(amount: U256, buy: TransferOutcome, sell: TransferOutcome) -> TokenBehaviour {
+    if buy == TransferOutcome::Reverted || sell == TransferOutcome::Reverted {
+        return TokenBehaviour::Blocklisting;
+    }
+    let tax = |outcome: TransferOutcome| match outcome {
+        TransferOutcome::Moved { sent: Some(sent), received: Some(received) }
+            if sent == amount && received <= amount =>
+        {
+            Some(((amount - received) * U256::from(BPS) / amount).to::<u64>())
+        }
+        _ => None,
+    };
+    match (tax(buy), tax(sell)) {
+        (Some(0), Some(0)) => TokenBehaviour::Standard,
+        (Some(buy_tax), Some(sell_tax)) => TokenBehaviour::FeeOnTransfer { buy_tax, sell_tax },
+        _ => TokenBehaviour::Rebasing,
+    }
+}
+
+struct TransferChecker<'a, D: EngineDatabaseInterface + Clone + Debug>
+where
+    <D as DatabaseRef>::Error: Debug,
+    <D as EngineDatabaseInterface>::Error: Debug,
+{
+    token: Address,
+    slots: ERC20Slots,
+    compiler: ContractCompiler,
+    amount: U256,
+    block: &'a BlockHeader,
+    engine: &'a SimulationEngine<D>,
+}
+
+impl<D: EngineDatabaseInterface + Clone + Debug> TransferChecker<'_, D>
+where
+    <D as DatabaseRef>::Error: Debug,
+    <D as EngineDatabaseInterface>::Error: Debug,
+{
+    /// Transfers `amount` from `sender` to `receiver`, after funding the sender with twice the
+    /// amount.
+    fn transfer(
+        &self,
+        sender: Address,
+        receiver: Address,
+    ) -> Result<TransferOutcome, SimulationError> {
+        let mut overwrite_factory =
+            ERC20OverwriteFactory::new(self.token, self.slots.clone(), self.compiler);
+        overwrite_factory.set_balance(self.amount * U256::from(2), sender);
+        let overwrites = overwrite_factory.get_overwrites();
+
+        let sender_before = self.balance_of(sender, Some(overwrites.clone()))?;
+        let receiver_before = self.balance_of(receiver, Some(overwrites.clone()))?;
+
+        // Balances are read within the bundle, so they reflect the state after the transfer
+        let results = match self.engine.simulate_bundle(&[
+            self.params(
+                sender,
+                transferCall { to: receiver, amount: self.amount }.abi_encode(),
+                Some(overwrites),
+            ),
+            self.params(sender, balanceOfCall { account: sender }.abi_encode(), None),
+            self.params(sender, balanceOfCall { account: receiver }.abi_encode(), None),
+        ]) {
+            Ok(results) => results,
+            Err(SimulationEngineError::StorageError(msg)) => {
+                return Err(SimulationError::RecoverableError(msg))
+            }
+            Err(_) => return Ok(TransferOutcome::Reverted),
+        };
+        // Tokens not returning a value on transfers are accepted
+        if matches!(bool::abi_decode(&results[0].result, true), Ok(false)) {
+            return Ok(TransferOutcome::Reverted);
+        }
+        let sender_after = decode_balance(&results[1].result)?;
+        let receiver_after = decode_balance(&results[2].result)?;
+
+        Ok(TransferOutcome::Moved {
+            sent: sender_before.checked_sub(sender_after),
+            received: receiver_after.checked_sub(receiver_before),
+        })
+    }
+
+    fn balance_of(
+        &self,
+        account: Address,
+        overwrites: Option<HashMap<Address, Overwrites>>,
+    ) -> Result<U256, SimulationError> {
+        let params = self.params(account, balanceOfCall { account }.abi_encode(), overwrites);
+        let result = self
+            .engine
+            .simulate(&params)
+            .map_err(|err| coerce_error(&err, &ErrorRegistry::default(), None, params.gas_limit))?;
+        decode_balance(&result.result)
+    }
+
+    fn params(
+        &self,
+        caller: Address,
+        data: Vec<u8>,
+        overrides: Option<HashMap<Address, Overwrites>>,
+    ) -> SimulationParameters {
+        SimulationParameters {
+            caller,
+            to: self.token,
+            data,
+            value: U256::ZERO,
+            overrides,
+            // The synthetic accounts only exist for the simulated transactions
+            account_overrides: Some(HashMap::from(
+                [SENDER, RECEIVER]
+                    .map(|account| (account, AccountOverride::new_account(Bytecode::new()))),
+            )),
+            gas_limit: None,
+            block_number: self.block.number,
+            timestamp: self.block.timestamp,
+            env: self.engine.environment().clone(),
+        }
+    }
+}
+
+fn decode_balance(result: &[u8]) -> Result<U256, SimulationError> {
+    U256::abi_decode(result, true)
+        .map_err(|e| SimulationError::FatalError(format!("Failed to decode balance: {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use num_bigint::BigUint;
+    use revm::primitives::AccountInfo;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::evm::{
+        engine_db::{create_engine, tycho_db::PreCachedDB},
+        test_utils::assemble,
+    };
+
+    fn moved(sent: u64, received: u64) -> TransferOutcome {
+        TransferOutcome::Moved {
+            sent: Some(U256::from(sent)),
+            received: Some(U256::from(received)),
+        }
+    }
+
+    #[rstest]
+    #[case::standard(moved(100, 100), moved(100, 100), TokenBehaviour::Standard)]
+    #[case::fee_on_transfer(
+        moved(100, 99),
+        moved(100, 95),
+        TokenBehaviour::FeeOnTransfer { buy_tax: 100, sell_tax: 500 }
+    )]
+    #[case::rebasing(moved(99, 99), moved(100, 100), TokenBehaviour::Rebasing)]
+    #[case::rebasing_receiver(
+        moved(100, 100),
+        TransferOutcome::Moved { sent: Some(U256::from(100)), received: None },
+        TokenBehaviour::Rebasing
+    )]
+    #[case::blocklisting(moved(100, 100), TransferOutcome::Reverted, TokenBehaviour::Blocklisting)]
+    fn test_classify(
+        #[case] buy: TransferOutcome,
+        #[case] sell: TransferOutcome,
+        #[case] expected: TokenBehaviour,
+    ) {
+        assert_eq!(classify(U256::from(100), buy, sell), expected);
+    }
+
+    #[test]
+    fn test_detect_fee_on_transfer() {
+        // Token with balances in a map at slot 0, charging a 5% fee on transfers
+        let code = Bytecode::new_raw(assemble(
+            "
+            PUSH1 0 CALLDATALOAD PUSH1 0xe0 SHR
+            DUP1 PUSH4 0x70a08231 EQ @balance_of JUMPI
+            PUSH4 0xa9059cbb EQ @transfer JUMPI
+            PUSH1 0 DUP1 REVERT
+
+            ; balanceOf(address account)
+            balance_of:
+            POP PUSH1 4 CALLDATALOAD PUSH1 0 MSTORE PUSH1 0 PUSH1 0x20 MSTORE
+            PUSH1 0x40 PUSH1 0 KECCAK256 SLOAD
+            PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+
+            ; transfer(address to, uint256 amount), the receiver gets 95% of the amount
+            transfer:
+            CALLER PUSH1 0 MSTORE PUSH1 0 PUSH1 0x20 MSTORE PUSH1 0x40 PUSH1 0 KECCAK256
+            DUP1 SLOAD PUSH1 0x24 CALLDATALOAD DUP1 DUP3 LT @fail JUMPI
+            DUP1 DUP3 SUB DUP4 SSTORE
+            PUSH1 4 CALLDATALOAD PUSH1 0 MSTORE PUSH1 0x40 PUSH1 0 KECCAK256
+            DUP2 PUSH1 20 SWAP1 DIV DUP3 SUB DUP2 SLOAD ADD SWAP1 SSTORE
+            PUSH1 1 PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+
+            fail: PUSH1 0 DUP1 REVERT
+            ",
+        ));
+        let mut token = Token::new(
+            "0x0000000000000000000000000000000000001234",
+            18,
+            "FEE",
+            BigUint::from(50_000u64),
+        );
+        let engine = create_engine(PreCachedDB::new().unwrap(), false).unwrap();
+        engine.state.init_account(
+            Address::from_str("0x0000000000000000000000000000000000001234").unwrap(),
+            AccountInfo::new(U256::ZERO, 0, code.hash_slow(), code),
+            None,
+            false,
+        );
+
+        let behaviour = detect_token_behaviour(
+            &token,
+            None,
+            Some((ERC20Slots::new(U256::ZERO, U256::from(1)), ContractCompiler::Solidity)),
+            &BlockHeader::default(),
+            &engine,
+        )
+        .unwrap();
+
+        assert_eq!(behaviour, TokenBehaviour::FeeOnTransfer { buy_tax: 500, sell_tax: 500 });
+        behaviour.apply_to(&mut token);
+        assert_eq!((token.buy_tax, token.sell_tax), (500, 500));
+        TokenBehaviour::Standard.apply_to(&mut token);
+        assert_eq!((token.buy_tax, token.sell_tax), (0, 0));
+    }
+}
@@ -34,6 +34,13 @@ impl From<Header> for BlockHeader {
                     .try_into()
                     .expect("Hash must be 32 bytes"),
             ),
+            parent_hash: B256::new(
+                header
+                    .parent_hash
+                    .as_ref()
+                    .try_into()
+                    .expect("Parent hash must be 32 bytes"),
+            ),
             timestamp: now,
         }
     }
@@ -1,7 +1,7 @@
-use std::{collections::HashMap, fmt::Debug};
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
 
 use alloy_primitives::{keccak256, Address, Keccak256, B256, U256};
-use alloy_sol_types::SolValue;
+use alloy_sol_types::{ContractError, SolCall, SolInterface, SolValue};
 use chrono::Utc;
 use revm::{
     db::DatabaseRef,
@@ -10,12 +10,14 @@ use revm::{
 
 use super::{
     constants::{EXTERNAL_ACCOUNT, MAX_BALANCE},
-    utils::coerce_error,
+    utils::{coerce_error, ErrorRegistry},
 };
 use crate::{
     evm::{
         engine_db::engine_db_interface::EngineDatabaseInterface,
-        simulation::{SimulationEngine, SimulationParameters, SimulationResult},
+        simulation::{
+            SimulationEngine, SimulationEngineError, SimulationParameters, SimulationResult,
+        },
     },
     protocol::errors::SimulationError,
 };
@@ -26,6 +28,34 @@ pub struct TychoSimulationResponse {
     pub simulation_result: SimulationResult,
 }
 
+/// Response of a call made with `TychoSimulationContract::call_typed`
+#[derive(Debug, Clone)]
+pub struct TypedCallResponse<T> {
+    /// The decoded return value of the call
+    pub return_value: T,
+    pub simulation_result: SimulationResult,
+}
+
+/// Error of a call made with `TychoSimulationContract::call_typed_with_errors`
+#[derive(Debug)]
+pub enum TypedCallError<E> {
+    /// The call reverted with a revert reason, a panic or one of the custom errors `E`.
+    ///
+    /// `error` is the revert converted with the contract's error registry, like the reverts of
+    /// untyped calls.
+    Reverted { revert: ContractError<E>, error: SimulationError },
+    /// The simulation failed otherwise, or the return value couldn't be decoded
+    Simulation(SimulationError),
+}
+
+impl<E> From<TypedCallError<E>> for SimulationError {
+    fn from(err: TypedCallError<E>) -> Self {
+        match err {
+            TypedCallError::Reverted { error, .. } | TypedCallError::Simulation(error) => error,
+        }
+    }
+}
+
 /// Represents a contract interface that interacts with the tycho_simulation environment to perform
 /// simulations on Ethereum smart contracts.
 ///
@@ -45,6 +75,7 @@ pub struct TychoSimulationResponse {
 /// - `address`: The address of the contract being simulated.
 /// - `engine`: The `SimulationEngine` instance responsible for simulating transactions and managing
 ///   the contract's state.
+/// - `errors`: The custom Solidity errors decoded from the contract's reverts.
 ///
 /// # Errors
 /// Returns errors of type `SimulationError` when encoding, decoding, or simulation operations
@@ -57,6 +88,7 @@ where
 {
     pub(crate) address: Address,
     pub(crate) engine: SimulationEngine<D>,
+    errors: Arc<ErrorRegistry>,
 }
 
 impl<D: EngineDatabaseInterface + Clone + Debug> TychoSimulationContract<D>
@@ -65,7 +97,7 @@ where
     <D as EngineDatabaseInterface>::Error: std::fmt::Debug,
 {
     pub fn new(address: Address, engine: SimulationEngine<D>) -> Result<Self, SimulationError> {
-        Ok(Self { address, engine })
+        Ok(Self { address, engine, errors: Arc::default() })
     }
 
     // Creates a new instance with the ISwapAdapter ABI
@@ -90,7 +122,14 @@ where
             false,
         );
 
-        Ok(Self { address, engine })
+        Ok(Self { address, engine, errors: Arc::default() })
+    }
+
+    /// Decodes the contract's reverts with the given custom errors, instead of only the errors of
+    /// the `ISwapAdapter` interface.
+    pub fn with_error_registry(mut self, errors: ErrorRegistry) -> Self {
+        self.errors = Arc::new(errors);
+        self
     }
 
     fn encode_input(&self, selector: &str, args: impl SolValue) -> Vec<u8> {
@@ -127,8 +166,83 @@ where
         value: U256,
     ) -> Result<TychoSimulationResponse, SimulationError> {
         let call_data = self.encode_input(selector, args);
-        let params = SimulationParameters {
-            data: call_data,
+        let params = self.params(call_data, block_number, timestamp, overrides, caller, value);
+
+        let sim_result = self.simulate(params)?;
+
+        Ok(TychoSimulationResponse {
+            return_value: sim_result.result.to_vec(),
+            simulation_result: sim_result,
+        })
+    }
+
+    /// Calls the contract with a call type declared with `sol!`, decoding the return value.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// sol! {
+    ///     function balanceOf(address account) external view returns (uint256);
+    /// }
+    ///
+    /// let balance = contract
+    ///     .call_typed(&balanceOfCall { account }, block.number, None, None, None, U256::ZERO)?
+    ///     .return_value
+    ///     ._0;
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn call_typed<C: SolCall>(
+        &self,
+        call: &C,
+        block_number: u64,
+        timestamp: Option<u64>,
+        overrides: Option<HashMap<Address, HashMap<U256, U256>>>,
+        caller: Option<Address>,
+        value: U256,
+    ) -> Result<TypedCallResponse<C::Return>, SimulationError> {
+        let params =
+            self.params(call.abi_encode(), block_number, timestamp, overrides, caller, value);
+        let sim_result = self.simulate(params)?;
+        decode_returns::<C>(sim_result)
+    }
+
+    /// Like `call_typed`, but decodes reverts with the custom errors `E` of the contract, e.g. the
+    /// `<Interface>Errors` enum generated by `sol!` for an interface.
+    #[allow(clippy::too_many_arguments)]
+    pub fn call_typed_with_errors<C: SolCall, E: SolInterface>(
+        &self,
+        call: &C,
+        block_number: u64,
+        timestamp: Option<u64>,
+        overrides: Option<HashMap<Address, HashMap<U256, U256>>>,
+        caller: Option<Address>,
+        value: U256,
+    ) -> Result<TypedCallResponse<C::Return>, TypedCallError<E>> {
+        let params =
+            self.params(call.abi_encode(), block_number, timestamp, overrides, caller, value);
+        match self.engine.simulate(&params) {
+            Ok(sim_result) => decode_returns::<C>(sim_result).map_err(TypedCallError::Simulation),
+            Err(err) => {
+                let error = coerce_error(&err, &self.errors, None, params.gas_limit);
+                Err(match decode_revert::<E>(&err) {
+                    Some(revert) => TypedCallError::Reverted { revert, error },
+                    None => TypedCallError::Simulation(error),
+                })
+            }
+        }
+    }
+
+    fn params(
+        &self,
+        data: Vec<u8>,
+        block_number: u64,
+        timestamp: Option<u64>,
+        overrides: Option<HashMap<Address, HashMap<U256, U256>>>,
+        caller: Option<Address>,
+        value: U256,
+    ) -> SimulationParameters {
+        SimulationParameters {
+            data,
             to: self.address,
             block_number,
             timestamp: timestamp.unwrap_or_else(|| {
@@ -138,23 +252,42 @@ where
                     .timestamp() as u64
             }),
             overrides,
+            account_overrides: None,
             caller: caller.unwrap_or(*EXTERNAL_ACCOUNT),
             value,
             gas_limit: None,
-        };
-
-        let sim_result = self.simulate(params)?;
-
-        Ok(TychoSimulationResponse {
-            return_value: sim_result.result.to_vec(),
-            simulation_result: sim_result,
-        })
+            env: self.engine.environment().clone(),
+        }
     }
 
     fn simulate(&self, params: SimulationParameters) -> Result<SimulationResult, SimulationError> {
         self.engine
             .simulate(&params)
-            .map_err(|e| coerce_error(&e, "pool_state", params.gas_limit))
+            .map_err(|e| coerce_error(&e, &self.errors, None, params.gas_limit))
+    }
+}
+
+fn decode_returns<C: SolCall>(
+    sim_result: SimulationResult,
+) -> Result<TypedCallResponse<C::Return>, SimulationError> {
+    let return_value = C::abi_decode_returns(&sim_result.result, true).map_err(|e| {
+        SimulationError::FatalError(format!(
+            "Failed to decode return value of {}: {:?}",
+            C::SIGNATURE,
+            e
+        ))
+    })?;
+    Ok(TypedCallResponse { return_value, simulation_result: sim_result })
+}
+
+/// Decodes the revert data of a failed simulation, if it reverted.
+fn decode_revert<E: SolInterface>(err: &SimulationEngineError) -> Option<ContractError<E>> {
+    match err {
+        SimulationEngineError::TransactionError { data, .. } => {
+            let data = hex::decode(data.strip_prefix("0x")?).ok()?;
+            ContractError::<E>::abi_decode(&data, true).ok()
+        }
+        _ => None,
     }
 }
 
@@ -163,6 +296,7 @@ mod tests {
     use std::str::FromStr;
 
     use alloy_primitives::hex;
+    use alloy_sol_types::{sol, SolError};
     use revm::{
         db::DatabaseRef,
         primitives::{AccountInfo, Bytecode, B256},
@@ -170,10 +304,23 @@ mod tests {
 
     use super::*;
     use crate::evm::{
-        engine_db::engine_db_interface::EngineDatabaseInterface,
-        protocol::vm::{constants::BALANCER_V2, utils::string_to_bytes32},
+        engine_db::{
+            create_engine, engine_db_interface::EngineDatabaseInterface, tycho_db::PreCachedDB,
+        },
+        protocol::vm::{
+            constants::BALANCER_V2,
+            utils::{string_to_bytes32, CustomErrorKind},
+        },
+        test_utils::assemble,
     };
 
+    sol! {
+        interface IToken {
+            error InsufficientBalance(uint256 available);
+            function balanceOf(address account) external view returns (uint256);
+        }
+    }
+
     #[derive(Debug, Clone)]
     struct MockDatabase;
 
@@ -267,4 +414,85 @@ mod tests {
         assert_eq!(&encoded[36..68], &expected_sell_token); // 32 bytes for address (padded)
         assert_eq!(&encoded[68..100], &expected_buy_token); // 32 bytes for address (padded)
     }
+
+    fn create_contract_with_code(code: &str) -> TychoSimulationContract<PreCachedDB> {
+        let address = Address::from_str("0x0000000000000000000000000000000000001234").unwrap();
+        let engine = create_engine(PreCachedDB::new().unwrap(), false).unwrap();
+        let code = Bytecode::new_raw(assemble(code));
+        engine.state.init_account(
+            address,
+            AccountInfo::new(U256::ZERO, 0, code.hash_slow(), code),
+            None,
+            false,
+        );
+        TychoSimulationContract::new(address, engine).unwrap()
+    }
+
+    #[test]
+    fn test_call_typed() {
+        // Returns 42 for any call
+        let contract =
+            create_contract_with_code("PUSH1 42 PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN");
+
+        let response = contract
+            .call_typed(
+                &IToken::balanceOfCall { account: Address::ZERO },
+                1,
+                Some(0),
+                None,
+                None,
+                U256::ZERO,
+            )
+            .unwrap();
+
+        assert_eq!(response.return_value._0, U256::from(42));
+    }
+
+    #[test]
+    fn test_call_typed_with_errors() {
+        // Reverts with `InsufficientBalance(7)` for any call
+        let contract = create_contract_with_code(&format!(
+            "
+            PUSH4 0x{selector} PUSH1 0xe0 SHL PUSH1 0 MSTORE PUSH1 7 PUSH1 4 MSTORE
+            PUSH1 0x24 PUSH1 0 REVERT
+            ",
+            selector = hex::encode(IToken::InsufficientBalance::SELECTOR),
+        ));
+        let call = |contract: &TychoSimulationContract<PreCachedDB>| {
+            contract.call_typed_with_errors::<_, IToken::ITokenErrors>(
+                &IToken::balanceOfCall { account: Address::ZERO },
+                1,
+                Some(0),
+                None,
+                None,
+                U256::ZERO,
+            )
+        };
+
+        match call(&contract) {
+            Err(TypedCallError::Reverted {
+                revert: ContractError::CustomError(IToken::ITokenErrors::InsufficientBalance(error)),
+                ..
+            }) => assert_eq!(error.available, U256::from(7)),
+            _ => panic!("Expected an InsufficientBalance error"),
+        }
+
+        // Converted errors are decoded with the contract's registry
+        let mut errors = ErrorRegistry::default();
+        errors
+            .register("error InsufficientBalance(uint256 available)", CustomErrorKind::InvalidInput)
+            .unwrap();
+        let contract = contract.with_error_registry(errors);
+        let Err(err) = call(&contract) else { panic!("Expected the call to revert") };
+        let err = SimulationError::from(err);
+        assert!(
+            matches!(
+                &err,
+                SimulationError::InvalidInput(msg, None)
+                    if msg.contains("InsufficientBalance(available: 7)")
+            ),
+            "{:?}",
+            err
+        );
+    }
 }
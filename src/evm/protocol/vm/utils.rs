@@ -1,10 +1,12 @@
 use std::{collections::HashMap, env, str::FromStr};
 
 use alloy::{
+    dyn_abi::{DynSolValue, JsonAbiExt},
+    json_abi::{Error as AbiError, JsonAbi},
     providers::{Provider, ProviderBuilder},
     transports::{RpcError, TransportErrorKind},
 };
-use alloy_primitives::{Address, U256};
+use alloy_primitives::{Address, FixedBytes, U256};
 use alloy_sol_types::SolValue;
 use hex::FromHex;
 use num_bigint::BigInt;
@@ -12,21 +14,170 @@ use revm::primitives::{Bytecode, Bytes};
 use serde_json::Value;
 
 use crate::{
-    evm::{simulation::SimulationEngineError, ContractCompiler, SlotId},
+    evm::{
+        protocol::u256_num::u256_to_biguint, simulation::SimulationEngineError, ContractCompiler,
+        SlotId,
+    },
     protocol::errors::SimulationError,
 };
 
+/// How a revert with a custom error is reported by `coerce_error`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CustomErrorKind {
+    /// Reported as `SimulationError::VmRevert`
+    Fatal,
+    /// Reported as `SimulationError::InvalidInput`, e.g. for errors about the swapped amount
+    InvalidInput,
+    /// Reported as `SimulationError::RecoverableError`, e.g. for temporarily unavailable pools
+    Recoverable,
+    /// Reported as `SimulationError::LimitExceeded`, for errors about amounts above the pool's
+    /// limit. The limit is the first parameter of the error, which has to be a `uint`; otherwise
+    /// the error is reported like `InvalidInput`.
+    LimitExceeded,
+}
+
+/// Custom Solidity errors decoded by `coerce_error`, by selector.
+///
+/// The default registry knows the errors of the `ISwapAdapter` interface (`Unavailable`,
+/// `LimitExceeded` and `NotImplemented`). Pools reverting with other errors can be given their own
+/// registry, see `TychoSimulationContract::with_error_registry`.
+///
+/// # Example
+///
+/// ```
+/// use tycho_simulation::evm::protocol::vm::utils::{CustomErrorKind, ErrorRegistry};
+///
+/// let mut errors = ErrorRegistry::default();
+/// errors
+///     .register("error PoolPaused(address pool)", CustomErrorKind::Recoverable)
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ErrorRegistry {
+    errors: HashMap<FixedBytes<4>, (AbiError, CustomErrorKind)>,
+}
+
+impl Default for ErrorRegistry {
+    fn default() -> Self {
+        let mut registry = Self { errors: HashMap::new() };
+        for (signature, kind) in [
+            ("error Unavailable(string reason)", CustomErrorKind::Recoverable),
+            ("error LimitExceeded(uint256 limit)", CustomErrorKind::LimitExceeded),
+            ("error NotImplemented(string reason)", CustomErrorKind::Fatal),
+        ] {
+            let error = AbiError::parse(signature).expect("Invalid adapter error signature");
+            registry.insert(error, kind);
+        }
+        registry
+    }
+}
+
+impl ErrorRegistry {
+    /// Registers a custom Solidity error, so that reverts with it are decoded by `coerce_error`.
+    ///
+    /// Registering an error with the same selector as a known one replaces it.
+    ///
+    /// # Parameters
+    ///
+    /// * `signature` - The error's signature, e.g. `error LimitExceeded(uint256 limit)`. Parameter
+    ///   names are optional and only used in error messages.
+    /// * `kind` - The kind of `SimulationError` reverts with this error are reported as.
+    pub fn register(
+        &mut self,
+        signature: &str,
+        kind: CustomErrorKind,
+    ) -> Result<(), SimulationError> {
+        let error = AbiError::parse(signature).map_err(|e| {
+            SimulationError::InvalidInput(
+                format!("Invalid error signature {}: {}", signature, e),
+                None,
+            )
+        })?;
+        self.insert(error, kind);
+        Ok(())
+    }
+
+    /// Registers all custom errors of a contract's ABI, see `register`.
+    pub fn register_abi(&mut self, abi: &JsonAbi, kind: CustomErrorKind) {
+        for error in abi.errors() {
+            self.insert(error.clone(), kind);
+        }
+    }
+
+    fn insert(&mut self, error: AbiError, kind: CustomErrorKind) {
+        self.errors
+            .insert(error.selector(), (error, kind));
+    }
+
+    /// Decodes hex encoded revert data with a registered error, returning the formatted error,
+    /// e.g. `LimitExceeded(limit: 100)`, along with its kind and parameters.
+    fn decode(&self, data: &str) -> Option<(String, CustomErrorKind, Vec<DynSolValue>)> {
+        let data = Vec::from_hex(data.strip_prefix("0x")?).ok()?;
+        let selector = FixedBytes::<4>::try_from(data.get(..4)?).ok()?;
+        let (error, kind) = self.errors.get(&selector)?;
+        let values = error
+            .abi_decode_input(&data[4..], true)
+            .ok()?;
+        let params = error
+            .inputs
+            .iter()
+            .zip(&values)
+            .map(|(param, value)| {
+                if param.name.is_empty() {
+                    format_sol_value(value)
+                } else {
+                    format!("{}: {}", param.name, format_sol_value(value))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        Some((format!("{}({})", error.name, params), *kind, values))
+    }
+}
+
+fn format_sol_value(value: &DynSolValue) -> String {
+    let format_list = |values: &[DynSolValue]| {
+        values
+            .iter()
+            .map(format_sol_value)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match value {
+        DynSolValue::Bool(value) => value.to_string(),
+        DynSolValue::Int(value, _) => value.to_string(),
+        DynSolValue::Uint(value, _) => value.to_string(),
+        DynSolValue::FixedBytes(word, size) => format!("0x{}", hex::encode(&word[..*size])),
+        DynSolValue::Address(address) => address.to_checksum(None),
+        DynSolValue::Bytes(bytes) => format!("0x{}", hex::encode(bytes)),
+        DynSolValue::String(value) => format!("{:?}", value),
+        DynSolValue::Array(values) | DynSolValue::FixedArray(values) => {
+            format!("[{}]", format_list(values))
+        }
+        DynSolValue::Tuple(values) => format!("({})", format_list(values)),
+        value => format!("{:?}", value),
+    }
+}
+
+/// Converts an error of the simulation engine into a `SimulationError`.
+///
+/// Reverts with custom errors are decoded with `errors`. `pool_state` describes the state of the
+/// simulated pool, if known, and is appended to the messages of errors caused by the input.
 pub(crate) fn coerce_error(
     err: &SimulationEngineError,
-    pool_state: &str,
+    errors: &ErrorRegistry,
+    pool_state: Option<&str>,
     gas_limit: Option<u64>,
 ) -> SimulationError {
+    let pool_state = pool_state
+        .map(|state| format!(" Pool state: {}", state))
+        .unwrap_or_default();
     match err {
         // Check for revert situation (if error message starts with "0x")
         SimulationEngineError::TransactionError { ref data, ref gas_used }
             if data.starts_with("0x") =>
         {
-            let reason = parse_solidity_error_message(data);
+            let reason = parse_solidity_error_message(data, errors);
             let err = SimulationEngineError::TransactionError {
                 data: format!("Revert! Reason: {}", reason),
                 gas_used: *gas_used,
@@ -40,8 +191,7 @@ pub(crate) fn coerce_error(
                     return SimulationError::InvalidInput(
                         format!(
                             "SimulationError: Likely out-of-gas. Used: {:.2}% of gas limit. \
-                            Original error: {}. \
-                            Pool state: {}",
+                            Original error: {}.{}",
                             usage * 100.0,
                             err,
                             pool_state,
@@ -50,10 +200,34 @@ pub(crate) fn coerce_error(
                     );
                 }
             }
-            SimulationError::FatalError(format!(
-                "Simulation reverted for unknown reason: {}",
-                reason
-            ))
+            if let Some((error, kind, values)) = errors.decode(data) {
+                let limit = values
+                    .first()
+                    .and_then(DynSolValue::as_uint)
+                    .map(|(limit, _)| limit);
+                return match (kind, limit) {
+                    (CustomErrorKind::LimitExceeded, Some(limit)) => {
+                        SimulationError::LimitExceeded {
+                            limit: u256_to_biguint(limit),
+                            partial_result: None,
+                        }
+                    }
+                    (CustomErrorKind::Fatal, _) => SimulationError::VmRevert {
+                        reason: format!("Simulation reverted with {}", error),
+                        gas_used: *gas_used,
+                    },
+                    (CustomErrorKind::InvalidInput | CustomErrorKind::LimitExceeded, _) => {
+                        SimulationError::InvalidInput(
+                            format!("Simulation reverted with {}.{}", error, pool_state),
+                            None,
+                        )
+                    }
+                    (CustomErrorKind::Recoverable, _) => SimulationError::RecoverableError(
+                        format!("Simulation reverted with {}", error),
+                    ),
+                };
+            }
+            SimulationError::VmRevert { reason, gas_used: *gas_used }
         }
         // Check if "OutOfGas" is part of the error message
         SimulationEngineError::TransactionError { ref data, ref gas_used }
@@ -68,24 +242,29 @@ pub(crate) fn coerce_error(
 
             SimulationError::InvalidInput(
                 format!(
-                    "SimulationError: out-of-gas. {} Original error: {}. Pool state: {}",
+                    "SimulationError: out-of-gas. {} Original error: {}.{}",
                     usage_msg, data, pool_state
                 ),
                 None,
             )
         }
+        SimulationEngineError::OutOfGas(ref message, ref data) => SimulationError::InvalidInput(
+            format!(
+                "SimulationError: out-of-gas. {}. Original error: {}.{}",
+                message, data, pool_state
+            ),
+            None,
+        ),
         SimulationEngineError::TransactionError { ref data, .. } => {
             SimulationError::FatalError(format!("TransactionError: {}", data))
         }
         SimulationEngineError::StorageError(message) => {
             SimulationError::RecoverableError(message.clone())
         }
-        _ => SimulationError::FatalError(err.clone().to_string()), /* Otherwise return the
-                                                                    * original error */
     }
 }
 
-fn parse_solidity_error_message(data: &str) -> String {
+fn parse_solidity_error_message(data: &str, errors: &ErrorRegistry) -> String {
     // 10 for "0x" + 8 hex chars error signature
     if data.len() >= 10 {
         let data_bytes = match Vec::from_hex(&data[2..]) {
@@ -111,6 +290,10 @@ fn parse_solidity_error_message(data: &str) -> String {
             }
         }
 
+        if let Some((error, ..)) = errors.decode(data) {
+            return error;
+        }
+
         // Try decoding as a string (old Solidity revert case)
         if let Ok(decoded) = String::abi_decode(&data_bytes, true) {
             return decoded;
@@ -437,6 +620,7 @@ pub fn json_deserialize_be_bigint_list(input: &[u8]) -> Result<Vec<BigInt>, Simu
 #[cfg(test)]
 mod tests {
     use dotenv::dotenv;
+    use num_bigint::BigUint;
 
     use super::*;
     use crate::utils::hexstring_to_vec;
@@ -465,12 +649,12 @@ mod tests {
             gas_used: None
         };
 
-        let result = coerce_error(&err, "test_pool", None);
+        let result = coerce_error(&err, &ErrorRegistry::default(), Some("test_pool"), None);
 
-        if let SimulationError::FatalError(msg) = result {
-            assert!(msg.contains("Simulation reverted for unknown reason: Invalid operation"));
+        if let SimulationError::VmRevert { reason, .. } = result {
+            assert_eq!(reason, "Invalid operation");
         } else {
-            panic!("Expected SolidityError error");
+            panic!("Expected VmRevert error");
         }
     }
 
@@ -482,7 +666,7 @@ mod tests {
             gas_used: Some(980)
         };
 
-        let result = coerce_error(&err, "test_pool", Some(1000));
+        let result = coerce_error(&err, &ErrorRegistry::default(), Some("test_pool"), Some(1000));
 
         if let SimulationError::InvalidInput(message, _partial_result) = result {
             assert!(message.contains("Used: 98.00% of gas limit."));
@@ -500,7 +684,7 @@ mod tests {
             gas_used: None,
         };
 
-        let result = coerce_error(&err, "test_pool", None);
+        let result = coerce_error(&err, &ErrorRegistry::default(), Some("test_pool"), None);
 
         if let SimulationError::InvalidInput(message, _partial_result) = result {
             assert!(message.contains("Original error: OutOfGas"));
@@ -510,11 +694,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_coerce_error_without_pool_state() {
+        let err = SimulationEngineError::TransactionError {
+            data: "OutOfGas".to_string(),
+            gas_used: None,
+        };
+
+        let result = coerce_error(&err, &ErrorRegistry::default(), None, None);
+
+        if let SimulationError::InvalidInput(message, _partial_result) = result {
+            assert_eq!(message, "SimulationError: out-of-gas.  Original error: OutOfGas.");
+        } else {
+            panic!("Expected InvalidInput error");
+        }
+    }
+
+    #[test]
+    fn test_maybe_coerce_error_out_of_gas_halt() {
+        let err = SimulationEngineError::OutOfGas(
+            "Transaction ran out of gas after using 1000 gas".to_string(),
+            "OutOfGas(Basic)".to_string(),
+        );
+
+        let result = coerce_error(&err, &ErrorRegistry::default(), Some("test_pool"), Some(1000));
+
+        if let SimulationError::InvalidInput(message, None) = result {
+            assert!(message.contains("Original error: OutOfGas(Basic)"));
+        } else {
+            panic!("Expected InvalidInput error");
+        }
+    }
+
     #[test]
     fn test_maybe_coerce_error_storage_error() {
         let err = SimulationEngineError::StorageError("Storage error:".to_string());
 
-        let result = coerce_error(&err, "test_pool", None);
+        let result = coerce_error(&err, &ErrorRegistry::default(), Some("test_pool"), None);
 
         if let SimulationError::RecoverableError(message) = result {
             assert_eq!(message, "Storage error:");
@@ -532,7 +748,7 @@ mod tests {
             gas_used: None,
         };
 
-        let result = coerce_error(&err, "test_pool", None);
+        let result = coerce_error(&err, &ErrorRegistry::default(), Some("test_pool"), None);
 
         if let SimulationError::FatalError(message) = result {
             assert_eq!(message, "TransactionError: Some other error");
@@ -546,7 +762,7 @@ mod tests {
         // Test parsing Solidity Error(string) message
         let data = "0x08c379a00000000000000000000000000000000000000000000000000000000000000020000000000000000000000000000000000000000000000000000000000000000e416d6f756e7420746f6f206c6f77000000000000000000000000000000000000";
 
-        let result = parse_solidity_error_message(data);
+        let result = parse_solidity_error_message(data, &ErrorRegistry::default());
 
         assert_eq!(result, "Amount too low");
     }
@@ -556,7 +772,7 @@ mod tests {
         // Test parsing Solidity Panic(uint256) message
         let data = "0x4e487b710000000000000000000000000000000000000000000000000000000000000001";
 
-        let result = parse_solidity_error_message(data);
+        let result = parse_solidity_error_message(data, &ErrorRegistry::default());
 
         assert_eq!(result, "AssertionError");
     }
@@ -566,11 +782,146 @@ mod tests {
         // Test failed decoding with invalid data
         let data = "0x1234567890";
 
-        let result = parse_solidity_error_message(data);
+        let result = parse_solidity_error_message(data, &ErrorRegistry::default());
 
         assert!(result.contains("Failed to decode"));
     }
 
+    #[test]
+    fn test_parse_solidity_error_message_custom_error() {
+        // LimitExceeded(1000) of the adapter interface
+        let data = "0xcdab4c8700000000000000000000000000000000000000000000000000000000000003e8";
+
+        let result = parse_solidity_error_message(data, &ErrorRegistry::default());
+
+        assert_eq!(result, "LimitExceeded(limit: 1000)");
+    }
+
+    #[test]
+    fn test_coerce_error_limit_exceeded() {
+        let err = SimulationEngineError::TransactionError {
+            data: "0xcdab4c8700000000000000000000000000000000000000000000000000000000000003e8"
+                .to_string(),
+            gas_used: Some(1000),
+        };
+
+        let result = coerce_error(&err, &ErrorRegistry::default(), Some("test_pool"), None);
+
+        if let SimulationError::LimitExceeded { limit, partial_result } = result {
+            assert_eq!(limit, BigUint::from(1000u64));
+            assert!(partial_result.is_none());
+        } else {
+            panic!("Expected LimitExceeded error, got {:?}", result);
+        }
+    }
+
+    #[test]
+    fn test_coerce_error_registered_limit_exceeded() {
+        let mut errors = ErrorRegistry::default();
+        errors
+            .register(
+                "error AmountTooLarge(uint128 maxAmount, address token)",
+                CustomErrorKind::LimitExceeded,
+            )
+            .unwrap();
+        errors
+            .register("error TooLarge(address token)", CustomErrorKind::LimitExceeded)
+            .unwrap();
+        let revert = |signature: &str, params: Vec<u8>| {
+            let mut data = AbiError::parse(signature)
+                .unwrap()
+                .selector()
+                .to_vec();
+            data.extend(params);
+            SimulationEngineError::TransactionError {
+                data: format!("0x{}", hex::encode(data)),
+                gas_used: None,
+            }
+        };
+
+        let result = coerce_error(
+            &revert(
+                "error AmountTooLarge(uint128,address)",
+                (U256::from(500), Address::ZERO).abi_encode_params(),
+            ),
+            &errors,
+            None,
+            None,
+        );
+        assert!(
+            matches!(&result, SimulationError::LimitExceeded { limit, partial_result: None }
+                if *limit == BigUint::from(500u64)),
+            "{:?}",
+            result
+        );
+        // Errors without a limit are reported as errors of the input
+        let result = coerce_error(
+            &revert("error TooLarge(address)", Address::ZERO.abi_encode()),
+            &errors,
+            None,
+            None,
+        );
+        assert!(matches!(result, SimulationError::InvalidInput(..)), "{:?}", result);
+    }
+
+    #[test]
+    fn test_coerce_error_fatal_custom_error() {
+        let mut data = AbiError::parse("error NotImplemented(string)")
+            .unwrap()
+            .selector()
+            .to_vec();
+        data.extend(("swap".to_string(),).abi_encode_params());
+        let err = SimulationEngineError::TransactionError {
+            data: format!("0x{}", hex::encode(data)),
+            gas_used: Some(1000),
+        };
+
+        let result = coerce_error(&err, &ErrorRegistry::default(), Some("test_pool"), None);
+
+        if let SimulationError::VmRevert { reason, gas_used } = result {
+            assert_eq!(reason, "Simulation reverted with NotImplemented(reason: \"swap\")");
+            assert_eq!(gas_used, Some(1000));
+        } else {
+            panic!("Expected VmRevert, got {:?}", result);
+        }
+    }
+
+    #[test]
+    fn test_coerce_error_registered_custom_error() {
+        let mut errors = ErrorRegistry::default();
+        errors
+            .register(
+                "error PoolPaused(address pool, bool permanent)",
+                CustomErrorKind::Recoverable,
+            )
+            .unwrap();
+        let mut data = AbiError::parse("error PoolPaused(address,bool)")
+            .unwrap()
+            .selector()
+            .to_vec();
+        data.extend((Address::with_last_byte(0x12), true).abi_encode_params());
+        let err = SimulationEngineError::TransactionError {
+            data: format!("0x{}", hex::encode(data)),
+            gas_used: None,
+        };
+
+        let result = coerce_error(&err, &errors, Some("test_pool"), None);
+
+        if let SimulationError::RecoverableError(message) = result {
+            assert_eq!(
+                message,
+                "Simulation reverted with PoolPaused(pool: 0x0000000000000000000000000000000000000012, permanent: true)"
+            );
+        } else {
+            panic!("Expected RecoverableError, got {:?}", result);
+        }
+        // Other registries don't know the error
+        assert!(matches!(
+            coerce_error(&err, &ErrorRegistry::default(), Some("test_pool"), None),
+            SimulationError::VmRevert { .. }
+        ));
+    }
+
     #[test]
     fn test_hexstring_to_vec() {
         let hexstring = "0x68656c6c6f";
@@ -1,30 +1,47 @@
-use std::{clone::Clone, collections::HashMap, default::Default, fmt::Debug};
+use std::{clone::Clone, collections::HashMap, default::Default, fmt::Debug, sync::Arc};
 
-use alloy_primitives::U256;
-use foundry_config::{Chain, Config};
-use foundry_evm::traces::{SparsedTraceArena, TraceKind};
+use alloy::providers::Provider;
+use alloy_primitives::{address, U256};
 use revm::{
+    db::WrapDatabaseRef,
+    handler::register::EvmHandler,
     inspector_handle_register,
-    interpreter::{return_ok, InstructionResult},
+    precompile::PrecompileWithAddress,
     primitives::{
-        alloy_primitives, bytes, Address, BlockEnv, EVMError, EVMResult, EvmState, ExecutionResult,
-        Output, ResultAndState, SpecId, TransactTo, TxEnv,
+        bytes, Address, BlockEnv, EVMError, EVMResult, EvmState, ExecutionResult, HaltReason, Log,
+        Output, ResultAndState, SpecId, TransactTo, TxEnv, B256,
     },
-    DatabaseRef, Evm,
+    Database, DatabaseRef, Evm, Inspector,
 };
 use revm_inspectors::tracing::{TracingInspector, TracingInspectorConfig};
 use strum_macros::Display;
-use tokio::runtime::{Handle, Runtime};
 use tracing::{debug, info};
+use tycho_core::models::Chain;
 
 use super::{
     account_storage::StateUpdate,
-    traces::{handle_traces, TraceResult},
+    events::{decode_log, DecodedEvent},
+    traces::CallTrace,
 };
-use crate::evm::engine_db::{
-    engine_db_interface::EngineDatabaseInterface, simulation_db::OverriddenSimulationDB,
+use crate::{
+    evm::engine_db::{
+        engine_db_interface::EngineDatabaseInterface,
+        simulation_db::{
+            AccountOverride, OverlaySimulationDB, OverriddenSimulationDB, SimulationDB,
+            StateOverlay,
+        },
+    },
+    protocol::errors::SimulationError,
 };
 
+/// Gas limit of simulated transactions if none is given in the `SimulationParameters`
+pub const DEFAULT_GAS_LIMIT: u64 = 8_000_000;
+
+/// Coinbase of blocks on OP stack chains, the `SequencerFeeVault` predeploy
+const OP_SEQUENCER_FEE_VAULT: Address = address!("4200000000000000000000000000000000000011");
+/// Coinbase of blocks on Arbitrum, the sequencer address (`"sequencer"` in ASCII)
+const ARBITRUM_SEQUENCER: Address = address!("a4b000000000000000000073657175656e636572");
+
 /// An error representing any transaction simulation result other than successful execution
 #[derive(Debug, Display, Clone, PartialEq)]
 pub enum SimulationEngineError {
@@ -47,6 +64,21 @@ pub struct SimulationResult {
     pub state_updates: HashMap<Address, StateUpdate>,
     /// Gas used by the transaction (already reduced by the refunded gas)
     pub gas_used: u64,
+    /// Logs emitted by the transaction, in order of emission
+    pub logs: Vec<Log>,
+    /// Call trace of the transaction. Only recorded if the engine has tracing enabled.
+    pub call_trace: Option<CallTrace>,
+}
+
+impl SimulationResult {
+    /// Decodes the emitted logs that match a known event (ERC20 transfers and approvals, Uniswap
+    /// V2/V3 swaps). Logs of unknown events are skipped.
+    pub fn decoded_logs(&self) -> Vec<DecodedEvent> {
+        self.logs
+            .iter()
+            .filter_map(decode_log)
+            .collect()
+    }
 }
 
 /// Simulation engine
@@ -58,6 +90,10 @@ where
 {
     pub state: D,
     pub trace: bool,
+    /// Precompiles registered in addition to the ones of the EVM spec
+    precompiles: Vec<PrecompileWithAddress>,
+    /// Environment of the chain the engine simulates
+    env: SimulationEnvironment,
 }
 
 impl<D: EngineDatabaseInterface + Clone + Debug> SimulationEngine<D>
@@ -70,9 +106,34 @@ where
     /// # Arguments
     ///
     /// * `state` - Database reference to be used for simulation
-    /// * `trace` - Whether to print the entire execution trace
+    /// * `trace` - Whether to record the call trace of simulations. The trace is returned on the
+    ///   `SimulationResult`.
     pub fn new(state: D, trace: bool) -> Self {
-        Self { state, trace }
+        Self { state, trace, precompiles: Vec::new(), env: SimulationEnvironment::default() }
+    }
+
+    /// Registers additional precompiles, e.g. the system contracts of a chain. They replace any
+    /// precompile of the EVM spec at the same address.
+    ///
+    /// The database has to contain an account at the address of each precompile, see
+    /// `create_engine_with_preset`.
+    pub fn with_precompiles(mut self, precompiles: Vec<PrecompileWithAddress>) -> Self {
+        self.precompiles = precompiles;
+        self
+    }
+
+    /// Sets the environment of the chain the engine simulates. Defaults to Ethereum's.
+    ///
+    /// Simulations run in the environment of their `SimulationParameters`; this one is what the
+    /// protocol implementations use when building them.
+    pub fn with_environment(mut self, env: SimulationEnvironment) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// The environment of the chain the engine simulates, see `with_environment`.
+    pub fn environment(&self) -> &SimulationEnvironment {
+        &self.env
     }
 
     /// Simulate a transaction
@@ -82,6 +143,181 @@ where
         &self,
         params: &SimulationParameters,
     ) -> Result<SimulationResult, SimulationEngineError> {
+        let (evm_result, call_trace) = self.transact(&self.state, params, self.trace);
+        let mut result = interpret_evm_result(evm_result)?;
+        result.call_trace = call_trace;
+        Ok(result)
+    }
+
+    /// Simulate a transaction and return its call trace, whether the transaction succeeded or not.
+    ///
+    /// Useful to inspect why a simulation failed. The engine's `trace` flag is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns the simulation error only if no call was recorded at all, e.g. because the
+    /// transaction was invalid.
+    pub fn trace_call(
+        &self,
+        params: &SimulationParameters,
+    ) -> Result<CallTrace, SimulationEngineError> {
+        let (evm_result, call_trace) = self.transact(&self.state, params, true);
+        match call_trace {
+            Some(call_trace) => Ok(call_trace),
+            None => Err(interpret_evm_result(evm_result)
+                .err()
+                .unwrap_or_else(|| SimulationEngineError::TransactionError {
+                    data: "No call trace recorded".to_string(),
+                    gas_used: None,
+                })),
+        }
+    }
+
+    /// Simulate a transaction while running the given inspector, e.g. to record the storage slots
+    /// it accesses.
+    ///
+    /// The engine's `trace` flag is ignored, no call trace is attached to the result.
+    pub fn simulate_with_inspector<I>(
+        &self,
+        params: &SimulationParameters,
+        inspector: &mut I,
+    ) -> Result<SimulationResult, SimulationEngineError>
+    where
+        I: for<'a> Inspector<WrapDatabaseRef<OverriddenSimulationDB<'a, D>>>,
+    {
+        interpret_evm_result(self.transact_with_inspector(&self.state, params, Some(inspector)))
+    }
+
+    /// Estimate the minimal gas limit at which a transaction succeeds.
+    ///
+    /// Because of refunds and the 63/64 rule, the gas used by a transaction is usually lower than
+    /// the gas limit it needs. The limit is therefore found by binary search between the gas used
+    /// and the limit given in the parameters (`DEFAULT_GAS_LIMIT` if none is given).
+    ///
+    /// # Errors
+    ///
+    /// * `SimulationEngineError::OutOfGas` - the transaction runs out of gas even with the maximum
+    ///   gas limit.
+    /// * Any other error of the transaction simulated with the maximum gas limit.
+    pub fn estimate_gas(
+        &self,
+        params: &SimulationParameters,
+    ) -> Result<u64, SimulationEngineError> {
+        let max_gas_limit = params
+            .gas_limit
+            .unwrap_or(DEFAULT_GAS_LIMIT);
+        let mut params = params.clone();
+        let mut simulate_with_limit = |gas_limit: u64| {
+            params.gas_limit = Some(gas_limit);
+            let (evm_result, _) = self.transact(&self.state, &params, false);
+            interpret_evm_result(evm_result)
+        };
+
+        let gas_used = match simulate_with_limit(max_gas_limit) {
+            Ok(result) => result.gas_used,
+            Err(SimulationEngineError::OutOfGas(_, data)) => {
+                return Err(SimulationEngineError::OutOfGas(
+                    format!(
+                        "Transaction runs out of gas with the maximum gas limit {max_gas_limit}"
+                    ),
+                    data,
+                ))
+            }
+            Err(err) => return Err(err),
+        };
+
+        // Invariant: the transaction fails with `low` gas and succeeds with `high` gas. The gas
+        // used is net of refunds, so the transaction can't succeed with less.
+        let mut low = gas_used.saturating_sub(1);
+        let mut high = max_gas_limit;
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            match simulate_with_limit(mid) {
+                Ok(_) => high = mid,
+                // Running out of gas in a sub-call may surface as a revert of the caller, so any
+                // failure means the limit is too low.
+                Err(SimulationEngineError::StorageError(err)) => {
+                    return Err(SimulationEngineError::StorageError(err))
+                }
+                Err(_) => low = mid,
+            }
+        }
+        Ok(high)
+    }
+
+    /// Simulate a sequence of transactions, applying the state changes of each transaction before
+    /// executing the next one.
+    ///
+    /// The changes are kept in an overlay on top of the engine's state, so the state itself is
+    /// not modified. State overrides given in the parameters only apply to their own
+    /// transaction.
+    ///
+    /// # Returns
+    ///
+    /// The result of every transaction, in the order they were given. If a transaction fails, the
+    /// simulation stops and its error is returned.
+    pub fn simulate_bundle(
+        &self,
+        params: &[SimulationParameters],
+    ) -> Result<Vec<SimulationResult>, SimulationEngineError> {
+        let mut overlay = StateOverlay::default();
+        let mut results = Vec::with_capacity(params.len());
+        for (index, tx_params) in params.iter().enumerate() {
+            let (evm_result, call_trace) = self.transact(
+                &OverlaySimulationDB::new(&self.state, &overlay),
+                tx_params,
+                self.trace,
+            );
+            if let Ok(ResultAndState { result: ExecutionResult::Success { .. }, state }) =
+                &evm_result
+            {
+                overlay
+                    .apply(state, tx_params.account_overrides.as_ref(), &self.state)
+                    .map_err(|err| {
+                        SimulationEngineError::StorageError(format!("Storage error: {:?}", err))
+                    })?;
+            }
+            let mut result = interpret_evm_result(evm_result).inspect_err(|err| {
+                debug!("Bundle simulation failed at transaction {}: {:?}", index, err)
+            })?;
+            result.call_trace = call_trace;
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    /// Execute a transaction on top of the given database, without interpreting the result.
+    ///
+    /// If `trace` is set, the call trace of the transaction is recorded and returned as well.
+    fn transact<DB: DatabaseRef>(
+        &self,
+        db: &DB,
+        params: &SimulationParameters,
+        trace: bool,
+    ) -> (EVMResult<DB::Error>, Option<CallTrace>)
+    where
+        DB::Error: std::fmt::Debug,
+    {
+        if trace {
+            let mut tracer = TracingInspector::new(TracingInspectorConfig::default());
+            let res = self.transact_with_inspector(db, params, Some(&mut tracer));
+            (res, CallTrace::from_arena(tracer.traces()))
+        } else {
+            (self.transact_with_inspector(db, params, None::<&mut TracingInspector>), None)
+        }
+    }
+
+    /// Execute a transaction on top of the given database, running the inspector if one is given.
+    fn transact_with_inspector<DB: DatabaseRef, I>(
+        &self,
+        db: &DB,
+        params: &SimulationParameters,
+        inspector: Option<&mut I>,
+    ) -> EVMResult<DB::Error>
+    where
+        DB::Error: std::fmt::Debug,
+        I: for<'a> Inspector<WrapDatabaseRef<OverriddenSimulationDB<'a, DB>>>,
+    {
         // We allocate a new EVM so we can work with a simple referenced DB instead of a fully
         // concurrently save shared reference and write locked object. Note that concurrently
         // calling this method is therefore not possible.
@@ -90,19 +326,23 @@ where
         // struct outlive this scope.
 
         // We protect the state from being consumed.
-        let db_ref = OverriddenSimulationDB {
-            inner_db: &self.state,
-            overrides: &params
-                .overrides
-                .clone()
-                .unwrap_or_default(),
-        };
+        let overrides = params
+            .overrides
+            .clone()
+            .unwrap_or_default();
+        let account_overrides = params
+            .account_overrides
+            .clone()
+            .unwrap_or_default();
+        let db_ref =
+            OverriddenSimulationDB::new(db, &overrides).with_account_overrides(&account_overrides);
 
         let tx_env = TxEnv {
             caller: params.revm_caller(),
             gas_limit: params
                 .revm_gas_limit()
-                .unwrap_or(8_000_000),
+                .unwrap_or(DEFAULT_GAS_LIMIT),
+            gas_price: params.env.gas_price,
             transact_to: params.revm_to(),
             value: params.value,
             data: params.revm_data(),
@@ -112,89 +352,106 @@ where
         let block_env = BlockEnv {
             number: params.revm_block_number(),
             timestamp: params.revm_timestamp(),
+            basefee: params.env.basefee,
+            coinbase: params.env.coinbase,
+            prevrandao: params.env.prevrandao,
             ..Default::default()
         };
 
+        let chain_id = params.env.chain_id;
         let default_builder = Evm::builder()
-            .with_spec_id(SpecId::CANCUN)
+            .with_spec_id(params.env.spec_id)
             .with_ref_db(db_ref)
+            .modify_cfg_env(|cfg| cfg.chain_id = chain_id)
             .with_block_env(block_env)
             .with_tx_env(tx_env);
 
-        let evm_result = if self.trace {
-            let mut tracer = TracingInspector::new(TracingInspectorConfig::default());
-            let res = {
+        match inspector {
+            Some(inspector) => {
                 let mut vm = default_builder
-                    .with_external_context(&mut tracer)
+                    .with_external_context(inspector)
                     .append_handler_register(inspector_handle_register)
+                    .append_handler_register_box(Box::new(precompiles_register(
+                        self.precompiles.clone(),
+                    )))
                     .build();
 
                 debug!("Starting simulation with tx parameters: {:#?} {:#?}", vm.tx(), vm.block());
                 vm.transact()
-            };
-
-            if let Ok(result) = res.as_ref() {
-                Self::print_traces(tracer, result)
             }
+            None => {
+                let mut vm = default_builder
+                    .append_handler_register_box(Box::new(precompiles_register(
+                        self.precompiles.clone(),
+                    )))
+                    .build();
 
-            res
-        } else {
-            let mut vm = default_builder.build();
-
-            debug!("Starting simulation with tx parameters: {:#?} {:#?}", vm.tx(), vm.block());
-
-            vm.transact()
-        };
-
-        interpret_evm_result(evm_result)
+                debug!("Starting simulation with tx parameters: {:#?} {:#?}", vm.tx(), vm.block());
+                vm.transact()
+            }
+        }
     }
 
     pub fn clear_temp_storage(&mut self) {
         self.state.clear_temp_storage();
     }
+}
 
-    fn print_traces(tracer: TracingInspector, res: &ResultAndState) {
-        let ResultAndState { result, state: _ } = res;
-        let (exit_reason, _gas_refunded, gas_used, _out, _exec_logs) = match result.clone() {
-            ExecutionResult::Success { reason, gas_used, gas_refunded, output, logs, .. } => {
-                (reason.into(), gas_refunded, gas_used, Some(output), logs)
-            }
-            ExecutionResult::Revert { gas_used, output } => {
-                // Need to fetch the unused gas
-                (InstructionResult::Revert, 0_u64, gas_used, Some(Output::Call(output)), vec![])
-            }
-            ExecutionResult::Halt { reason, gas_used } => {
-                (reason.into(), 0_u64, gas_used, None, vec![])
+impl<P> SimulationEngine<SimulationDB<P>>
+where
+    P: Provider + Debug + Send + Sync + 'static,
+{
+    /// Simulate a transaction, fetching the state it accesses from the node in batches.
+    ///
+    /// The transaction is first executed against the locally known state only, recording the
+    /// accounts and storage slots that are missing. These are fetched concurrently, and the
+    /// process is repeated until no new accesses show up (or `max_rounds` is reached). The
+    /// transaction is then simulated as usual, fetching any remaining state one query at a time.
+    pub fn simulate_with_prefetch(
+        &self,
+        params: &SimulationParameters,
+        max_rounds: usize,
+    ) -> Result<SimulationResult, SimulationEngineError> {
+        for round in 0..max_rounds {
+            let recorder = self.state.access_recorder();
+            // The result is meaningless, since missing state was replaced by defaults.
+            let _ = self.transact(&recorder, params, false);
+            let accesses = recorder.into_accesses();
+            if accesses.is_empty() {
+                break;
             }
-        };
-
-        let trace_res = TraceResult {
-            success: matches!(exit_reason, return_ok!()),
-            traces: Some(vec![(
-                TraceKind::Execution,
-                SparsedTraceArena {
-                    arena: tracer.into_traces(),
-                    ignored: alloy_primitives::map::HashMap::default(),
-                },
-            )]),
-            gas_used,
-        };
+            debug!("Prefetch round {}: {} accounts accessed", round, accesses.len());
+            self.state
+                .prefetch(&accesses)
+                .map_err(|err| {
+                    SimulationEngineError::StorageError(format!("Prefetch error: {:?}", err))
+                })?;
+        }
+        self.simulate(params)
+    }
+}
 
-        tokio::task::block_in_place(|| {
-            let future = async {
-                handle_traces(trace_res, &Config::default(), Some(Chain::default()), true)
-                    .await
-                    .expect("failure handling traces");
-            };
-            if let Ok(handle) = Handle::try_current() {
-                // If successful, use the existing runtime to block on the future
-                handle.block_on(future)
-            } else {
-                // If no runtime is found, create a new one and block on the future
-                let rt = Runtime::new().expect("Failed to create a new runtime");
-                rt.block_on(future)
-            }
-        });
+/// Returns a handler register adding `precompiles` to the precompiles of the EVM spec.
+fn precompiles_register<EXT, DB: Database>(
+    precompiles: Vec<PrecompileWithAddress>,
+) -> impl for<'a> Fn(&mut EvmHandler<'a, EXT, DB>) {
+    move |handler: &mut EvmHandler<'_, EXT, DB>| {
+        if precompiles.is_empty() {
+            return;
+        }
+        let precompiles = precompiles.clone();
+        let load_spec_precompiles = handler
+            .pre_execution
+            .load_precompiles
+            .clone();
+        handler.pre_execution.load_precompiles =
+            Arc::new(move || {
+                let mut loaded = load_spec_precompiles();
+                loaded.extend(precompiles.iter().cloned().map(
+                    |PrecompileWithAddress(address, precompile)| (address, precompile.into()),
+                ));
+                loaded
+            });
     }
 }
 
@@ -218,15 +475,21 @@ fn interpret_evm_result<DBError: std::fmt::Debug>(
 ) -> Result<SimulationResult, SimulationEngineError> {
     match evm_result {
         Ok(result_and_state) => match result_and_state.result {
-            ExecutionResult::Success { gas_used, gas_refunded, output, .. } => {
-                Ok(interpret_evm_success(gas_used, gas_refunded, output, result_and_state.state))
-            }
+            ExecutionResult::Success { gas_used, gas_refunded, output, logs, .. } => Ok(
+                interpret_evm_success(gas_used, gas_refunded, output, logs, result_and_state.state),
+            ),
             ExecutionResult::Revert { output, gas_used } => {
                 Err(SimulationEngineError::TransactionError {
                     data: format!("0x{}", hex::encode(output)),
                     gas_used: Some(gas_used),
                 })
             }
+            ExecutionResult::Halt { reason: reason @ HaltReason::OutOfGas(_), gas_used } => {
+                Err(SimulationEngineError::OutOfGas(
+                    format!("Transaction ran out of gas after using {} gas", gas_used),
+                    format!("{:?}", reason),
+                ))
+            }
             ExecutionResult::Halt { reason, gas_used } => {
                 Err(SimulationEngineError::TransactionError {
                     data: format!("{:?}", reason),
@@ -264,6 +527,7 @@ fn interpret_evm_success(
     gas_used: u64,
     gas_refunded: u64,
     output: Output,
+    logs: Vec<Log>,
     state: EvmState,
 ) -> SimulationResult {
     SimulationResult {
@@ -308,10 +572,12 @@ fn interpret_evm_success(
             account_updates
         },
         gas_used: gas_used - gas_refunded,
+        logs,
+        call_trace: None,
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 /// Data needed to invoke a transaction simulation
 pub struct SimulationParameters {
     /// Address of the sending account
@@ -325,12 +591,86 @@ pub struct SimulationParameters {
     /// EVM state overrides.
     /// Will be merged with existing state. Will take effect only for current simulation.
     pub overrides: Option<HashMap<Address, HashMap<U256, U256>>>,
+    /// Balance, nonce and code overrides of accounts.
+    /// Will take effect only for current simulation.
+    pub account_overrides: Option<HashMap<Address, AccountOverride>>,
     /// Limit of gas to be used by the transaction
     pub gas_limit: Option<u64>,
     /// The block number to be used by the transaction. This is independent of the states block.
     pub block_number: u64,
     /// The timestamp to be used by the transaction
     pub timestamp: u64,
+    /// EVM environment (hardfork, chain id, fees...) the transaction is executed in
+    pub env: SimulationEnvironment,
+}
+
+/// EVM environment of a simulation.
+///
+/// The default matches an Ethereum mainnet environment with zero fees. Use
+/// `SimulationEnvironment::try_from(chain)` to get the preset of another chain.
+///
+/// Note that fees are validated like on chain: `gas_price` must be at least `basefee`, and the
+/// caller must be able to pay for `gas_price * gas_limit`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationEnvironment {
+    /// Hardfork rules to execute the transaction with
+    pub spec_id: SpecId,
+    /// Value returned by the `CHAINID` opcode
+    pub chain_id: u64,
+    /// Base fee of the block, returned by the `BASEFEE` opcode
+    pub basefee: U256,
+    /// Beneficiary of the block, returned by the `COINBASE` opcode
+    pub coinbase: Address,
+    /// Gas price paid by the transaction, returned by the `GASPRICE` opcode
+    pub gas_price: U256,
+    /// Randomness of the block, returned by the `PREVRANDAO` opcode
+    pub prevrandao: Option<B256>,
+}
+
+impl Default for SimulationEnvironment {
+    fn default() -> Self {
+        Self {
+            spec_id: SpecId::CANCUN,
+            chain_id: 1,
+            basefee: U256::ZERO,
+            coinbase: Address::ZERO,
+            gas_price: U256::ZERO,
+            prevrandao: Some(B256::ZERO),
+        }
+    }
+}
+
+impl TryFrom<Chain> for SimulationEnvironment {
+    type Error = SimulationError;
+
+    /// Returns the environment of `chain`.
+    ///
+    /// All supported chains execute Cancun rules: OP stack chains since the Ecotone upgrade and
+    /// Arbitrum since ArbOS 20. They differ in the values of their block environment:
+    ///
+    /// * Base: the block's coinbase is the `SequencerFeeVault` predeploy.
+    /// * Arbitrum: the block's coinbase is the sequencer address and `PREVRANDAO` (formerly
+    ///   `DIFFICULTY`) always returns 1.
+    ///
+    /// zkSync and Starknet don't execute EVM bytecode, so they are rejected.
+    fn try_from(chain: Chain) -> Result<Self, Self::Error> {
+        match chain {
+            Chain::Ethereum => Ok(Self::default()),
+            Chain::Base => {
+                Ok(Self { chain_id: 8453, coinbase: OP_SEQUENCER_FEE_VAULT, ..Default::default() })
+            }
+            Chain::Arbitrum => Ok(Self {
+                chain_id: 42161,
+                coinbase: ARBITRUM_SEQUENCER,
+                prevrandao: Some(B256::with_last_byte(1)),
+                ..Default::default()
+            }),
+            Chain::ZkSync | Chain::Starknet => Err(SimulationError::InvalidInput(
+                format!("{:?} does not execute EVM bytecode", chain),
+                None,
+            )),
+        }
+    }
 }
 
 // Converters of fields to revm types
@@ -379,17 +719,60 @@ mod tests {
     use revm::primitives::{
         bytes, hex, Account, AccountInfo, AccountStatus, Address, Bytecode, Bytes,
         EvmState as rState, EvmStorageSlot, ExecutionResult, HaltReason, InvalidTransaction,
-        OutOfGasError, Output, ResultAndState, SuccessReason, B256,
+        OutOfGasError, Output, ResultAndState, SuccessReason, KECCAK_EMPTY,
     };
 
     use super::*;
-    use crate::{
-        evm::engine_db::{
-            engine_db_interface::EngineDatabaseInterface, simulation_db::SimulationDB,
+    use crate::evm::{
+        engine_db::{
+            create_engine, create_engine_with_preset, engine_db_interface::EngineDatabaseInterface,
+            simulation_db::SimulationDB, tycho_db::PreCachedDB,
         },
-        protocol::errors::SimulationError,
+        presets::{EnginePreset, ARB_SYS_ADDRESS},
+        test_utils::{assemble, MockNode},
     };
 
+    /// Address the counter contract is deployed at by `deploy_counter`
+    const COUNTER: Address = address!("0000000000000000000000000000000000001234");
+
+    /// Code of a counter contract: increments storage slot 0 and returns the new value.
+    fn counter_code() -> Bytecode {
+        Bytecode::new_raw(assemble(
+            "PUSH1 0 SLOAD PUSH1 1 ADD DUP1 PUSH1 0 SSTORE PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN",
+        ))
+    }
+
+    /// Deploys the counter contract at `COUNTER` to the engine's state.
+    fn deploy_counter<D: EngineDatabaseInterface + Clone + Debug>(engine: &SimulationEngine<D>)
+    where
+        <D as DatabaseRef>::Error: Debug,
+        <D as EngineDatabaseInterface>::Error: Debug,
+    {
+        let code = counter_code();
+        engine.state.init_account(
+            COUNTER,
+            AccountInfo::new(U256::ZERO, 0, code.hash_slow(), code),
+            None,
+            false,
+        );
+    }
+
+    /// Parameters of a call from the zero address to `to`, without data, value or overrides.
+    fn call_params(to: Address) -> SimulationParameters {
+        SimulationParameters {
+            caller: Address::ZERO,
+            to,
+            data: Vec::new(),
+            value: U256::ZERO,
+            overrides: None,
+            account_overrides: None,
+            gas_limit: None,
+            block_number: 0,
+            timestamp: 0,
+            env: Default::default(),
+        }
+    }
+
     #[test]
     fn test_converting_to_revm() {
         let address_string = "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D";
@@ -410,9 +793,11 @@ mod tests {
                 .cloned()
                 .collect(),
             ),
+            account_overrides: None,
             gas_limit: Some(33),
             block_number: 0,
             timestamp: 0,
+            env: Default::default(),
         };
 
         assert_eq!(params.revm_caller(), Address::from_str(address_string).unwrap());
@@ -451,9 +836,11 @@ mod tests {
             data: Vec::new(),
             value: U256::from(0u64),
             overrides: None,
+            account_overrides: None,
             gas_limit: None,
             block_number: 0,
             timestamp: 0,
+            env: Default::default(),
         };
 
         assert_eq!(params.overrides, None);
@@ -467,7 +854,11 @@ mod tests {
                 reason: SuccessReason::Return,
                 gas_used: 100_u64,
                 gas_refunded: 10_u64,
-                logs: Vec::new(),
+                logs: vec![Log::new_unchecked(
+                    Address::ZERO,
+                    vec![B256::ZERO],
+                    Bytes::from_static(b"log"),
+                )],
                 output: Output::Call(Bytes::from_static(b"output")),
             },
             state: [(
@@ -532,6 +923,8 @@ mod tests {
         .collect();
         assert_eq!(simulation_result.state_updates, expected_state_updates);
         assert_eq!(simulation_result.gas_used, 90);
+        assert_eq!(simulation_result.logs.len(), 1);
+        assert_eq!(simulation_result.logs[0].data.data, Bytes::from_static(b"log"));
     }
 
     #[test]
@@ -575,8 +968,26 @@ mod tests {
         assert!(result.is_err());
         let err = result.err().unwrap();
         match err {
-            SimulationEngineError::TransactionError { data, gas_used } => {
+            SimulationEngineError::OutOfGas(message, data) => {
+                assert_eq!(message, "Transaction ran out of gas after using 100 gas");
                 assert_eq!(data, "OutOfGas(Basic)");
+            }
+            _ => panic!("Wrong type of SimulationError!"),
+        }
+    }
+
+    #[test]
+    fn test_interpret_result_ok_halt_other_reason() {
+        let evm_result: EVMResult<TransportError> = Ok(ResultAndState {
+            result: ExecutionResult::Halt { reason: HaltReason::OpcodeNotFound, gas_used: 100_u64 },
+            state: rState::default(),
+        });
+
+        let result = interpret_evm_result(evm_result);
+
+        match result {
+            Err(SimulationEngineError::TransactionError { data, gas_used }) => {
+                assert_eq!(data, "OpcodeNotFound");
                 assert_eq!(gas_used, Some(100));
             }
             _ => panic!("Wrong type of SimulationError!"),
@@ -678,9 +1089,11 @@ mod tests {
             data: encoded,
             value: U256::from(0u64),
             overrides: None,
+            account_overrides: None,
             gas_limit: None,
             block_number: 0,
             timestamp: 0,
+            env: Default::default(),
         };
         let eng = SimulationEngine::new(state, true);
 
@@ -806,9 +1219,11 @@ mod tests {
             data: calldata,
             value: U256::from(0u64),
             overrides: Some(overrides),
+            account_overrides: None,
             gas_limit: None,
             block_number: 0,
             timestamp: 0,
+            env: Default::default(),
         };
 
         let eng = SimulationEngine::new(state, false);
@@ -834,4 +1249,277 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_simulate_bundle_carries_state() {
+        let engine = create_engine(PreCachedDB::new().unwrap(), false).unwrap();
+        deploy_counter(&engine);
+        let increment = || call_params(COUNTER);
+
+        let results = engine
+            .simulate_bundle(&[increment(), increment(), increment()])
+            .unwrap();
+
+        let counts = results
+            .iter()
+            .map(|res| U256::abi_decode(&res.result, true).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![U256::from(1), U256::from(2), U256::from(3)]);
+        // The engine's state itself is not modified by the bundle
+        let single = engine.simulate(&increment()).unwrap();
+        assert_eq!(U256::abi_decode(&single.result, true).unwrap(), U256::from(1));
+    }
+
+    #[test]
+    fn test_simulate_bundle_drops_account_overrides() {
+        let engine = create_engine(PreCachedDB::new().unwrap(), false).unwrap();
+        // The account holds no code, the counter contract is only provided as an override.
+        engine
+            .state
+            .init_account(COUNTER, AccountInfo::default(), None, false);
+        let overridden = SimulationParameters {
+            value: U256::from(1000),
+            account_overrides: Some(HashMap::from([
+                (
+                    Address::ZERO,
+                    AccountOverride { balance: Some(U256::from(2000)), ..Default::default() },
+                ),
+                (COUNTER, AccountOverride { code: Some(counter_code()), ..Default::default() }),
+            ])),
+            ..call_params(COUNTER)
+        };
+
+        let results = engine
+            .simulate_bundle(&[overridden.clone(), call_params(COUNTER)])
+            .unwrap();
+
+        assert_eq!(U256::abi_decode(&results[0].result, true).unwrap(), U256::from(1));
+        // The code override is gone, so the second call runs no code
+        assert!(results[1].result.is_empty());
+        // The balance override is gone, so the caller can't transfer value anymore
+        let transfer = SimulationParameters { value: U256::from(1), ..call_params(COUNTER) };
+        assert!(engine
+            .simulate_bundle(&[overridden, transfer])
+            .is_err());
+    }
+
+    #[test]
+    fn test_trace_call() {
+        let engine = create_engine(PreCachedDB::new().unwrap(), true).unwrap();
+        deploy_counter(&engine);
+        let params = call_params(COUNTER);
+
+        let result = engine.simulate(&params).unwrap();
+        let trace = engine.trace_call(&params).unwrap();
+
+        assert_eq!(result.call_trace.as_ref(), Some(&trace));
+        assert_eq!(trace.call_type, "CALL");
+        assert_eq!(trace.to, COUNTER);
+        assert!(trace.success);
+        assert_eq!(U256::abi_decode(&trace.output, true).unwrap(), U256::from(1));
+        assert!(trace.calls.is_empty());
+    }
+
+    #[test]
+    fn test_simulate_with_chain_preset() {
+        let params = SimulationParameters {
+            // `arbBlockNumber()`
+            data: hex::decode("a3b1b31d").unwrap(),
+            block_number: 1234,
+            ..call_params(ARB_SYS_ADDRESS)
+        };
+
+        for trace in [false, true] {
+            let engine = create_engine_with_preset(
+                PreCachedDB::new().unwrap(),
+                trace,
+                &EnginePreset::for_chain(Chain::Arbitrum).unwrap(),
+            )
+            .unwrap();
+
+            let result = engine.simulate(&params).unwrap();
+
+            assert_eq!(U256::abi_decode(&result.result, true).unwrap(), U256::from(1234));
+
+            // The engine runs in the environment of the preset
+            let result = engine
+                .simulate(&SimulationParameters {
+                    // `arbChainID()`
+                    data: hex::decode("d127f54a").unwrap(),
+                    env: engine.environment().clone(),
+                    ..params.clone()
+                })
+                .unwrap();
+
+            assert_eq!(U256::abi_decode(&result.result, true).unwrap(), U256::from(42161));
+        }
+        // Without the Arbitrum preset, `ArbSys` doesn't exist
+        assert!(create_engine(PreCachedDB::new().unwrap(), false)
+            .unwrap()
+            .simulate(&params)
+            .is_err());
+    }
+
+    #[test]
+    fn test_simulate_with_custom_environment() {
+        let engine = create_engine(PreCachedDB::new().unwrap(), false).unwrap();
+        // Returns `abi.encode(block.chainid, block.basefee, block.coinbase)`
+        let contract = Address::from_str("0x0000000000000000000000000000000000001234").unwrap();
+        let code = Bytecode::new_raw(Bytes::from(
+            hex::decode("46600052486020524160405260606000f3").unwrap(),
+        ));
+        engine.state.init_account(
+            contract,
+            AccountInfo::new(U256::ZERO, 0, code.hash_slow(), code),
+            None,
+            false,
+        );
+        let caller = Address::from_str("0x000000000000000000000000000000000000beef").unwrap();
+        engine.state.init_account(
+            caller,
+            AccountInfo { balance: U256::from(10).pow(U256::from(18)), ..Default::default() },
+            None,
+            false,
+        );
+        let coinbase = Address::from_str("0x4838b106fce9647bdf1e7877bf73ce8b0bad5f97").unwrap();
+        let basefee = U256::from(10_000_000_000u64);
+        let env = SimulationEnvironment {
+            basefee,
+            coinbase,
+            gas_price: basefee,
+            ..SimulationEnvironment::try_from(Chain::Base).unwrap()
+        };
+        let params =
+            SimulationParameters { caller, gas_limit: Some(100_000), env, ..call_params(contract) };
+
+        let result = engine.simulate(&params).unwrap();
+
+        let (chain_id, block_basefee, block_coinbase) =
+            <(U256, U256, Address)>::abi_decode(&result.result, true).unwrap();
+        assert_eq!(chain_id, U256::from(8453));
+        assert_eq!(block_basefee, basefee);
+        assert_eq!(block_coinbase, coinbase);
+    }
+
+    #[test]
+    fn test_environment_from_chain() {
+        let arbitrum = SimulationEnvironment::try_from(Chain::Arbitrum).unwrap();
+        let base = SimulationEnvironment::try_from(Chain::Base).unwrap();
+
+        assert_eq!(arbitrum.chain_id, 42161);
+        assert_eq!(arbitrum.spec_id, SpecId::CANCUN);
+        assert_eq!(arbitrum.coinbase, ARBITRUM_SEQUENCER);
+        assert_eq!(arbitrum.prevrandao, Some(B256::with_last_byte(1)));
+        assert_eq!(base.chain_id, 8453);
+        assert_eq!(base.coinbase, OP_SEQUENCER_FEE_VAULT);
+        assert_eq!(
+            SimulationEnvironment::try_from(Chain::Ethereum).unwrap(),
+            SimulationEnvironment::default()
+        );
+        assert!(SimulationEnvironment::try_from(Chain::ZkSync).is_err());
+        assert!(SimulationEnvironment::try_from(Chain::Starknet).is_err());
+    }
+
+    #[test]
+    fn test_simulate_with_account_overrides() {
+        let engine = create_engine(PreCachedDB::new().unwrap(), false).unwrap();
+        // The account holds no code, the counter contract is only provided as an override.
+        engine
+            .state
+            .init_account(COUNTER, AccountInfo::default(), None, false);
+        let params = SimulationParameters {
+            value: U256::from(1000),
+            account_overrides: Some(HashMap::from([
+                (
+                    Address::ZERO,
+                    AccountOverride { balance: Some(U256::from(1000)), ..Default::default() },
+                ),
+                (COUNTER, AccountOverride { code: Some(counter_code()), ..Default::default() }),
+            ])),
+            ..call_params(COUNTER)
+        };
+
+        let result = engine.simulate(&params).unwrap();
+
+        assert_eq!(U256::abi_decode(&result.result, true).unwrap(), U256::from(1));
+        assert_eq!(result.state_updates[&COUNTER].balance, Some(U256::from(1000)));
+        // The shared state is not modified
+        assert_eq!(
+            engine
+                .state
+                .basic_ref(COUNTER)
+                .unwrap()
+                .map(|info| info.code_hash),
+            Some(KECCAK_EMPTY)
+        );
+    }
+
+    #[test]
+    fn test_estimate_gas() {
+        let engine = create_engine(PreCachedDB::new().unwrap(), false).unwrap();
+        deploy_counter(&engine);
+        let params = call_params(COUNTER);
+
+        let estimate = engine.estimate_gas(&params).unwrap();
+
+        let with_limit =
+            |gas_limit| SimulationParameters { gas_limit: Some(gas_limit), ..params.clone() };
+        let gas_used = engine
+            .simulate(&with_limit(estimate))
+            .unwrap()
+            .gas_used;
+        assert!(estimate >= gas_used);
+        assert!(engine
+            .simulate(&with_limit(estimate - 1))
+            .is_err());
+        assert!(matches!(
+            engine.estimate_gas(&with_limit(estimate - 1)),
+            Err(SimulationEngineError::OutOfGas(..))
+        ));
+    }
+
+    #[test]
+    fn test_simulate_with_prefetch() {
+        // Returns the sum of storage slots 0, 1 and 2
+        let contract = Address::from_str("0x0000000000000000000000000000000000001234").unwrap();
+        let code = HashMap::from([(
+            contract,
+            Bytes::from(hex::decode("600054600154016002540160005260206000f3").unwrap()),
+        )]);
+        let storage = HashMap::from([
+            ((contract, U256::from(0)), U256::from(1)),
+            ((contract, U256::from(1)), U256::from(2)),
+            ((contract, U256::from(2)), U256::from(3)),
+        ]);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let node = MockNode::spawn(&runtime, code, storage);
+        let client = runtime.block_on(async {
+            ProviderBuilder::new()
+                .on_builtin(&node.url)
+                .await
+                .unwrap()
+        });
+        let db = SimulationDB::new(Arc::new(client), Some(Arc::new(runtime)), None);
+        let engine = SimulationEngine::new(db, false);
+        let params = SimulationParameters {
+            caller: Address::ZERO,
+            to: contract,
+            data: Vec::new(),
+            value: U256::ZERO,
+            overrides: None,
+            account_overrides: None,
+            gas_limit: None,
+            block_number: 0,
+            timestamp: 0,
+            env: Default::default(),
+        };
+
+        let result = engine
+            .simulate_with_prefetch(&params, 5)
+            .unwrap();
+
+        assert_eq!(U256::abi_decode(&result.result, true).unwrap(), U256::from(6));
+        // Every slot was fetched exactly once, during prefetching
+        assert_eq!(node.storage_requests(), 3);
+    }
 }
@@ -135,6 +135,16 @@ impl ProtocolStreamBuilder {
         self
     }
 
+    /// Sets the maximum number of protocol states decoded or updated concurrently per block.
+    ///
+    /// Decoding VM pool snapshots and refreshing their spot prices requires running EVM
+    /// simulations, so a higher value reduces latency on blocks that update many VM pools.
+    /// Defaults to the number of available CPUs.
+    pub fn parallelism(mut self, parallelism: usize) -> Self {
+        self.decoder.parallelism(parallelism);
+        self
+    }
+
     pub async fn build(
         self,
     ) -> Result<impl Stream<Item = Result<BlockUpdate, StreamDecodeError>>, StreamError> {
@@ -0,0 +1,237 @@
+//! Helpers shared by the EVM tests.
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use alloy_primitives::{Address, Bytes, U256};
+use num_bigint::BigUint;
+use revm::interpreter::{opcode, OpCode};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    runtime::Runtime,
+};
+
+use crate::models::Token;
+
+/// A local JSON-RPC node serving fixed code and storage over HTTP.
+///
+/// Only the methods needed to load accounts are supported; any other method is answered with a
+/// JSON-RPC error.
+pub(crate) struct MockNode {
+    pub url: String,
+    storage_requests: Arc<AtomicUsize>,
+}
+
+impl MockNode {
+    /// Starts the node on a random local port, serving connections on the given runtime.
+    pub(crate) fn spawn(
+        runtime: &Runtime,
+        code: HashMap<Address, Bytes>,
+        storage: HashMap<(Address, U256), U256>,
+    ) -> Self {
+        let listener = runtime
+            .block_on(TcpListener::bind("127.0.0.1:0"))
+            .unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let storage_requests = Arc::new(AtomicUsize::new(0));
+        let state =
+            Arc::new(MockNodeState { code, storage, storage_requests: storage_requests.clone() });
+        runtime.spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(state.clone().serve(stream));
+            }
+        });
+        MockNode { url, storage_requests }
+    }
+
+    /// Number of `eth_getStorageAt` requests received so far.
+    pub(crate) fn storage_requests(&self) -> usize {
+        self.storage_requests
+            .load(Ordering::SeqCst)
+    }
+}
+
+struct MockNodeState {
+    code: HashMap<Address, Bytes>,
+    storage: HashMap<(Address, U256), U256>,
+    storage_requests: Arc<AtomicUsize>,
+}
+
+impl MockNodeState {
+    /// Serves requests over a single HTTP connection until the client closes it.
+    async fn serve(self: Arc<Self>, stream: TcpStream) {
+        let mut reader = BufReader::new(stream);
+        while let Some(body) = read_request(&mut reader).await {
+            let response = match serde_json::from_slice::<Value>(&body) {
+                Ok(request) => self.respond(&request),
+                Err(err) => json!({
+                    "jsonrpc": "2.0",
+                    "id": Value::Null,
+                    "error": {"code": -32700, "message": err.to_string()}
+                }),
+            }
+            .to_string();
+            let written = reader
+                .get_mut()
+                .write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                        response.len(),
+                        response
+                    )
+                    .as_bytes(),
+                )
+                .await;
+            if written.is_err() {
+                return;
+            }
+        }
+    }
+
+    fn respond(&self, request: &Value) -> Value {
+        let params = &request["params"];
+        let address = params[0]
+            .as_str()
+            .and_then(|address| Address::from_str(address).ok());
+        let result = match (request["method"].as_str(), address) {
+            (Some("eth_getBalance" | "eth_getTransactionCount"), Some(_)) => Ok(json!("0x0")),
+            (Some("eth_getCode"), Some(address)) => Ok(json!(self
+                .code
+                .get(&address)
+                .cloned()
+                .unwrap_or_default())),
+            (Some("eth_getStorageAt"), Some(address)) => {
+                self.storage_requests
+                    .fetch_add(1, Ordering::SeqCst);
+                match params[1]
+                    .as_str()
+                    .and_then(|index| U256::from_str(index).ok())
+                {
+                    Some(index) => Ok(json!(self
+                        .storage
+                        .get(&(address, index))
+                        .copied()
+                        .unwrap_or_default())),
+                    None => Err(format!("Invalid storage index {}", params[1])),
+                }
+            }
+            (method, _) => Err(format!("Unsupported request {:?} with params {}", method, params)),
+        };
+        match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": request["id"], "result": result}),
+            Err(message) => json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": {"code": -32601, "message": message}
+            }),
+        }
+    }
+}
+
+/// Reads the body of the next HTTP request, or `None` once the connection is closed.
+async fn read_request(reader: &mut BufReader<TcpStream>) -> Option<Vec<u8>> {
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader
+            .read_line(&mut line)
+            .await
+            .unwrap_or(0) ==
+            0
+        {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().ok()?;
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .await
+        .ok()?;
+    Some(body)
+}
+
+/// Assembles EVM bytecode from mnemonics, e.g. `PUSH1 0x00 CALLDATALOAD`.
+///
+/// Instructions and their immediates are separated by whitespace, `;` starts a comment.
+/// `label:` marks a jump destination, emitting a `JUMPDEST`, and `@label` pushes the label's
+/// offset with a `PUSH2`.
+pub(crate) fn assemble(source: &str) -> Bytes {
+    let mut code = Vec::new();
+    let mut labels = HashMap::new();
+    let mut references = Vec::new();
+    let mut tokens = source.lines().flat_map(|line| {
+        line.split(';')
+            .next()
+            .unwrap_or_default()
+            .split_whitespace()
+    });
+    while let Some(token) = tokens.next() {
+        if let Some(label) = token.strip_suffix(':') {
+            labels.insert(label, code.len());
+            code.push(opcode::JUMPDEST);
+        } else if let Some(label) = token.strip_prefix('@') {
+            code.push(opcode::PUSH2);
+            references.push((code.len(), label));
+            code.extend([0, 0]);
+        } else {
+            let op = (0..=u8::MAX)
+                .filter_map(OpCode::new)
+                .find(|op| op.as_str() == token)
+                .unwrap_or_else(|| panic!("Unknown instruction {}", token));
+            code.push(op.get());
+            if (opcode::PUSH1..=opcode::PUSH32).contains(&op.get()) {
+                let size = (op.get() - opcode::PUSH1 + 1) as usize;
+                let immediate = tokens
+                    .next()
+                    .and_then(|value| U256::from_str(value).ok())
+                    .unwrap_or_else(|| panic!("Missing immediate of {}", token));
+                code.extend_from_slice(&immediate.to_be_bytes::<32>()[32 - size..]);
+            }
+        }
+    }
+    for (position, label) in references {
+        let offset = *labels
+            .get(label)
+            .unwrap_or_else(|| panic!("Unknown label {}", label));
+        code[position..position + 2].copy_from_slice(&(offset as u16).to_be_bytes());
+    }
+    code.into()
+}
+
+/// Token `X` of the Uniswap test pools
+pub(crate) fn token_x() -> Token {
+    Token::new("0x6b175474e89094c44da98b954eedeac495271d0f", 18, "X", BigUint::from(10_000u32))
+}
+
+/// Token `Y` of the Uniswap test pools
+pub(crate) fn token_y() -> Token {
+    Token::new("0xf1ca9cb74685755965c7458528a36934df52a3ef", 18, "Y", BigUint::from(10_000u32))
+}
+
+/// Asserts that `actual` differs from `expected` by at most `tolerance`.
+pub(crate) fn assert_close(actual: &BigUint, expected: &BigUint, tolerance: u32) {
+    let diff = if actual > expected { actual - expected } else { expected - actual };
+    assert!(
+        diff <= BigUint::from(tolerance),
+        "{} is not within {} of {}",
+        actual,
+        tolerance,
+        expected
+    );
+}
@@ -1,84 +1,205 @@
-use foundry_config::{Chain, Config};
-use foundry_evm::traces::{
-    decode_trace_arena,
-    identifier::{EtherscanIdentifier, SignaturesIdentifier},
-    render_trace_arena, CallTraceDecoder, CallTraceDecoderBuilder, DebugTraceIdentifier, Traces,
-};
-
-/// A slimmed down return from the executor used for returning minimal trace + gas metering info
-#[derive(Debug)]
-pub struct TraceResult {
-    pub success: bool,
-    pub traces: Option<Traces>,
-    pub gas_used: u64,
+use std::collections::HashMap;
+
+use alloy_primitives::{keccak256, Address, Bytes, FixedBytes, U256};
+use alloy_sol_types::decode_revert_reason;
+use lazy_static::lazy_static;
+use revm_inspectors::tracing::{types::CallTraceNode, CallTraceArena};
+use serde::{Deserialize, Serialize};
+
+/// Function signatures known to the default `SignatureRegistry`: the ERC20 interface and the
+/// swap adapter interface.
+const DEFAULT_SIGNATURES: &[&str] = &[
+    "totalSupply()",
+    "balanceOf(address)",
+    "transfer(address,uint256)",
+    "transferFrom(address,address,uint256)",
+    "approve(address,uint256)",
+    "allowance(address,address)",
+    "decimals()",
+    "symbol()",
+    "name()",
+    "price(bytes32,address,address,uint256[])",
+    "swap(bytes32,address,address,uint8,uint256)",
+    "getLimits(bytes32,address,address)",
+    "getCapabilities(bytes32,address,address)",
+    "getTokens(bytes32)",
+    "getPoolIds(uint256,uint256)",
+    "minGasUsage()",
+];
+
+lazy_static! {
+    static ref DEFAULT_REGISTRY: SignatureRegistry = SignatureRegistry::with_defaults();
 }
 
-pub async fn handle_traces(
-    mut result: TraceResult,
-    config: &Config,
-    chain: Option<Chain>,
-    decode_internal: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut decoder = CallTraceDecoderBuilder::new()
-        .with_signature_identifier(SignaturesIdentifier::new(
-            Config::foundry_cache_dir(),
-            config.offline,
-        )?)
-        .build();
-
-    let mut etherscan_identifier = EtherscanIdentifier::new(config, chain)?;
-    if let Some(etherscan_identifier) = &mut etherscan_identifier {
-        for (_, trace) in result
-            .traces
-            .as_deref_mut()
-            .unwrap_or_default()
-        {
-            decoder.identify(trace, etherscan_identifier);
+/// A local registry mapping function selectors to their signatures, used to decode call traces
+/// without reaching out to external services.
+#[derive(Debug, Clone, Default)]
+pub struct SignatureRegistry {
+    signatures: HashMap<FixedBytes<4>, String>,
+}
+
+impl SignatureRegistry {
+    /// Creates a registry seeded with the ERC20 and swap adapter function signatures.
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::default();
+        for signature in DEFAULT_SIGNATURES {
+            registry.register(signature);
         }
+        registry
     }
 
-    if decode_internal {
-        let sources = if let Some(etherscan_identifier) = &etherscan_identifier {
-            etherscan_identifier
-                .get_compiled_contracts()
-                .await?
-        } else {
-            Default::default()
-        };
-        decoder.debug_identifier = Some(DebugTraceIdentifier::new(sources));
+    /// Registers a function signature, e.g. `"transfer(address,uint256)"`.
+    pub fn register(&mut self, signature: &str) {
+        let selector = FixedBytes::from_slice(&keccak256(signature.as_bytes())[..4]);
+        self.signatures
+            .insert(selector, signature.to_string());
     }
 
-    print_traces(&mut result, &decoder).await?;
+    /// Returns the signature registered for the given selector, if any.
+    pub fn get(&self, selector: &FixedBytes<4>) -> Option<&str> {
+        self.signatures
+            .get(selector)
+            .map(String::as_str)
+    }
+}
 
-    Ok(())
+/// A call frame of a simulated transaction, including all of its sub-calls.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallTrace {
+    /// Type of the call, e.g. `CALL`, `STATICCALL`, `DELEGATECALL` or `CREATE`
+    pub call_type: String,
+    /// Address of the caller
+    pub from: Address,
+    /// Address of the called (or created) contract
+    pub to: Address,
+    /// Amount of native token sent with the call
+    pub value: U256,
+    /// Function selector of the call. `None` for contract creations and calls without calldata.
+    pub selector: Option<FixedBytes<4>>,
+    /// Signature of the called function, if the selector is known to the signature registry
+    pub function: Option<String>,
+    /// Calldata of the call
+    pub input: Bytes,
+    /// Data returned by the call
+    pub output: Bytes,
+    /// Gas used by the call, including its sub-calls
+    pub gas_used: u64,
+    /// Whether the call succeeded
+    pub success: bool,
+    /// Revert reason of a failed call, if it could be decoded
+    pub revert_reason: Option<String>,
+    /// Sub-calls made by this call, in execution order
+    pub calls: Vec<CallTrace>,
 }
 
-pub async fn print_traces(
-    result: &mut TraceResult,
-    decoder: &CallTraceDecoder,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let traces = result
-        .traces
-        .as_mut()
-        .expect("No traces found");
-
-    let mut traces_strings = Vec::new();
-    for (_, arena) in traces {
-        decode_trace_arena(arena, decoder).await?;
-        traces_strings.push(render_trace_arena(arena));
+impl CallTrace {
+    /// Builds the call tree recorded by a tracing inspector, decoding selectors with the default
+    /// signature registry.
+    ///
+    /// Returns `None` if no call was recorded.
+    pub fn from_arena(arena: &CallTraceArena) -> Option<Self> {
+        let mut trace = Self::from_node(arena, arena.nodes().first()?);
+        trace.decode(&DEFAULT_REGISTRY);
+        Some(trace)
+    }
+
+    fn from_node(arena: &CallTraceArena, node: &CallTraceNode) -> Self {
+        let trace = &node.trace;
+        let selector = if trace.kind.is_any_create() {
+            None
+        } else {
+            trace
+                .data
+                .get(..4)
+                .map(FixedBytes::from_slice)
+        };
+        Self {
+            call_type: trace.kind.to_string(),
+            from: trace.caller,
+            to: trace.address,
+            value: trace.value,
+            selector,
+            function: None,
+            input: trace.data.clone(),
+            output: trace.output.clone(),
+            gas_used: trace.gas_used,
+            success: trace.success,
+            revert_reason: if trace.success { None } else { decode_revert_reason(&trace.output) },
+            calls: node
+                .children
+                .iter()
+                .map(|&child| Self::from_node(arena, &arena.nodes()[child]))
+                .collect(),
+        }
+    }
+
+    /// Resolves the function signature of this call and all sub-calls with the given registry.
+    ///
+    /// Calls whose selector is not known to the registry are left unchanged.
+    pub fn decode(&mut self, registry: &SignatureRegistry) {
+        if let Some(function) = self
+            .selector
+            .and_then(|selector| registry.get(&selector))
+        {
+            self.function = Some(function.to_string());
+        }
+        for call in &mut self.calls {
+            call.decode(registry);
+        }
     }
-    println!("Traces:");
-    for t in traces_strings {
-        println!("{}", t);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(input: &str, calls: Vec<CallTrace>) -> CallTrace {
+        let input = Bytes::from(hex::decode(input).unwrap());
+        CallTrace {
+            call_type: "CALL".to_string(),
+            from: Address::ZERO,
+            to: Address::ZERO,
+            value: U256::ZERO,
+            selector: input
+                .get(..4)
+                .map(FixedBytes::from_slice),
+            function: None,
+            input,
+            output: Bytes::new(),
+            gas_used: 21_000,
+            success: true,
+            revert_reason: None,
+            calls,
+        }
     }
-    println!();
 
-    if result.success {
-        println!("Transaction successfully executed.");
-    } else {
-        println!("Transaction failed.");
+    #[test]
+    fn test_decode_call_trace() {
+        // balanceOf(address) calling an unknown function
+        let mut trace = call(
+            "70a08231000000000000000000000000000000000000000000000000000000000000dead",
+            vec![call("deadbeef", vec![])],
+        );
+
+        trace.decode(&SignatureRegistry::with_defaults());
+
+        assert_eq!(trace.function, Some("balanceOf(address)".to_string()));
+        assert_eq!(trace.calls[0].function, None);
+
+        let mut registry = SignatureRegistry::default();
+        registry.register("foo()");
+        let mut trace = call("c2985578", vec![]);
+        trace.decode(&registry);
+        assert_eq!(trace.function, Some("foo()".to_string()));
     }
 
-    println!("Gas used: {}", result.gas_used);
-    Ok(())
+    #[test]
+    fn test_call_trace_json_roundtrip() {
+        let trace = call("a9059cbb", vec![call("", vec![])]);
+
+        let json = serde_json::to_string(&trace).unwrap();
+        let deserialized: CallTrace = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(deserialized, trace);
+    }
 }
@@ -25,6 +25,10 @@ pub struct Token {
     pub symbol: String,
     /// The amount of gas it takes to transfer the token
     pub gas: BigUint,
+    /// Tax charged when buying the token from a pool, in basis points of the amount
+    pub buy_tax: u64,
+    /// Tax charged when selling the token to a pool, in basis points of the amount
+    pub sell_tax: u64,
 }
 
 impl Token {
@@ -49,7 +53,7 @@ impl Token {
                 .unwrap_or_else(|_| panic!("Invalid token address: {:?}", address)),
         );
         let sym = symbol.to_string();
-        Token { address: addr, decimals, symbol: sym, gas }
+        Token { address: addr, decimals, symbol: sym, gas, buy_tax: 0, sell_tax: 0 }
     }
 
     /// One
@@ -99,6 +103,8 @@ impl TryFrom<ResponseToken> for Token {
                     .copied()
                     .expect("Expected a value in gas"),
             ),
+            buy_tax: 0,
+            sell_tax: 0,
         })
     }
 }
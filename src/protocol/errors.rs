@@ -1,8 +1,10 @@
 //! Protocol generic errors
 use std::{fmt, io};
 
+use num_bigint::BigUint;
 use serde_json::Error as SerdeError;
 use thiserror::Error;
+use tycho_core::Bytes;
 
 use super::models::GetAmountOutResult;
 
@@ -41,13 +43,23 @@ impl From<SimulationError> for InvalidSnapshotError {
 ///
 /// `SimulationError` encompasses all possible errors that can occur in the package,
 /// wrapping lower-level errors in a user-friendly way for easier handling and display.
+///
+/// Every error belongs to one of three categories, see `SimulationError::category`:
+/// - `Recoverable`: The simulation has failed with a recoverable error. Retrying at a later time
+///   may succeed. It may have failed due to a temporary issue, such as a network problem.
+/// - `InvalidInput`: The simulation has failed due to bad input parameters.
+/// - `Fatal`: There is a bug with this pool or protocol - do not attempt simulation again.
+///
 /// Variants:
-/// - `RecoverableError`: Indicates that the simulation has failed with a recoverable error.
-///   Retrying at a later time may succeed. It may have failed due to a temporary issue, such as a
-///   network problem.
-/// - `InvalidInput`: Indicates that the simulation has failed due to bad input parameters.
-/// - `FatalError`: There is a bug with this pool or protocol - do not attempt simulation again.
+/// - `RecoverableError`, `InvalidInput` and `FatalError`: Errors without a more specific kind, in
+///   the respective category.
+/// - `InsufficientLiquidity`: The pool can't provide the requested amount.
+/// - `LimitExceeded`: The amount exceeds the pool's trade limit.
+/// - `UnsupportedPair`: The pool doesn't trade the given tokens against each other.
+/// - `MathOverflow`: An arithmetic operation overflowed.
+/// - `VmRevert`: A simulated contract call reverted.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum SimulationError {
     #[error("Fatal error: {0}")]
     FatalError(String),
@@ -55,6 +67,63 @@ pub enum SimulationError {
     InvalidInput(String, Option<GetAmountOutResult>),
     #[error("Recoverable error: {0}")]
     RecoverableError(String),
+    /// The pool can't provide the requested amount. `partial_result` is the result of trading as
+    /// much as possible, `None` if the pool has no liquidity at all.
+    #[error("Insufficient liquidity")]
+    InsufficientLiquidity { partial_result: Option<GetAmountOutResult> },
+    /// The amount exceeds the pool's limit. `partial_result` is the result of trading the limit,
+    /// if known.
+    #[error("Amount exceeds limit {limit}")]
+    LimitExceeded { limit: BigUint, partial_result: Option<GetAmountOutResult> },
+    #[error("Unsupported pair: {sell_token} -> {buy_token}")]
+    UnsupportedPair { sell_token: Bytes, buy_token: Bytes },
+    #[error("Math overflow: {0}")]
+    MathOverflow(String),
+    #[error("VM revert: {reason}")]
+    VmRevert { reason: String, gas_used: Option<u64> },
+}
+
+/// The category of a `SimulationError`, telling how to handle it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulationErrorCategory {
+    /// Retrying later may succeed
+    Recoverable,
+    /// Retrying with different input, e.g. a smaller amount, may succeed
+    InvalidInput,
+    /// Retrying won't succeed
+    Fatal,
+}
+
+impl SimulationError {
+    /// Returns the category of the error.
+    ///
+    /// `InsufficientLiquidity` is recoverable if the pool has no liquidity at all, as liquidity
+    /// may be added later, and an invalid input otherwise, as a smaller amount can be traded.
+    pub fn category(&self) -> SimulationErrorCategory {
+        match self {
+            SimulationError::RecoverableError(_) |
+            SimulationError::InsufficientLiquidity { partial_result: None } => {
+                SimulationErrorCategory::Recoverable
+            }
+            SimulationError::InvalidInput(..) |
+            SimulationError::InsufficientLiquidity { partial_result: Some(_) } |
+            SimulationError::LimitExceeded { .. } |
+            SimulationError::UnsupportedPair { .. } => SimulationErrorCategory::InvalidInput,
+            SimulationError::FatalError(_) |
+            SimulationError::MathOverflow(_) |
+            SimulationError::VmRevert { .. } => SimulationErrorCategory::Fatal,
+        }
+    }
+
+    /// Returns the result of the part of the trade that could be simulated, if any.
+    pub fn partial_result(&self) -> Option<&GetAmountOutResult> {
+        match self {
+            SimulationError::InvalidInput(_, partial_result) |
+            SimulationError::InsufficientLiquidity { partial_result } |
+            SimulationError::LimitExceeded { partial_result, .. } => partial_result.as_ref(),
+            _ => None,
+        }
+    }
 }
 
 impl<T> From<SimulationError> for TransitionError<T> {
@@ -97,3 +166,23 @@ impl From<SerdeError> for FileError {
         FileError::Parse(err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulation_error_category() {
+        let insufficient_liquidity =
+            SimulationError::InsufficientLiquidity { partial_result: None };
+        let limit_exceeded =
+            SimulationError::LimitExceeded { limit: BigUint::from(100u64), partial_result: None };
+        let revert = SimulationError::VmRevert { reason: "reverted".to_string(), gas_used: None };
+
+        assert_eq!(insufficient_liquidity.category(), SimulationErrorCategory::Recoverable);
+        assert_eq!(limit_exceeded.category(), SimulationErrorCategory::InvalidInput);
+        assert_eq!(limit_exceeded.to_string(), "Amount exceeds limit 100");
+        assert_eq!(revert.category(), SimulationErrorCategory::Fatal);
+        assert!(revert.partial_result().is_none());
+    }
+}
@@ -164,6 +164,87 @@ impl GetAmountOutResult {
     }
 }
 
+/// Result of swapping until a pool reaches a target price
+///
+/// # Fields
+///
+/// * `amount_in`: BigUint, the amount of the sold token needed to reach the price, including fees
+/// * `amount_out`: BigUint, the amount of the bought token received
+/// * `gas`: BigUint, the gas of the swap
+/// * `new_state`: the state of the pool after the swap
+#[derive(Debug)]
+pub struct SwapToPriceResult {
+    pub amount_in: BigUint,
+    pub amount_out: BigUint,
+    pub gas: BigUint,
+    pub new_state: Box<dyn ProtocolSim>,
+}
+
+impl SwapToPriceResult {
+    /// Constructs a new SwapToPriceResult struct with the given amounts, gas and state
+    pub fn new(
+        amount_in: BigUint,
+        amount_out: BigUint,
+        gas: BigUint,
+        new_state: Box<dyn ProtocolSim>,
+    ) -> Self {
+        SwapToPriceResult { amount_in, amount_out, gas, new_state }
+    }
+}
+
+/// DepthLevel struct represents the trade that moves the price of a pool by a given amount
+///
+/// # Fields
+///
+/// * `price_move_bps`: u32, the price move in basis points. Selling the input token lowers its
+///   price in the output token by this amount.
+/// * `amount_in`: BigUint, the amount of the input token to sell
+/// * `amount_out`: BigUint, the amount of the output token received
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepthLevel {
+    pub price_move_bps: u32,
+    pub amount_in: BigUint,
+    pub amount_out: BigUint,
+}
+
+/// LiquidityDepth struct represents the depth of a pool in both directions of a pair
+///
+/// Levels are sorted by price move. Price moves the pool can't reach, e.g. because it runs out of
+/// liquidity or hits a trade limit, have no level.
+///
+/// # Fields
+///
+/// * `a_to_b`: Vec<DepthLevel>, the levels when selling token a for token b
+/// * `b_to_a`: Vec<DepthLevel>, the levels when selling token b for token a
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LiquidityDepth {
+    pub a_to_b: Vec<DepthLevel>,
+    pub b_to_a: Vec<DepthLevel>,
+}
+
+/// PriceImpactResult struct represents the result of getting the amount out of a trading pair,
+/// together with the prices before, during and after the trade
+///
+/// All prices are in units of the output token per unit of the input token, adjusted for the
+/// tokens' decimals, like `ProtocolSim::spot_price`.
+///
+/// # Fields
+///
+/// * `result`: GetAmountOutResult, the result of the trade
+/// * `spot_price`: f64, the spot price before the trade
+/// * `execution_price`: f64, the price the trade is executed at, i.e. the amount out per amount in
+/// * `price_impact_bps`: f64, the relative difference between the spot price and the execution
+///   price, in basis points. It includes the pool's fee.
+/// * `marginal_price`: f64, the spot price after the trade
+#[derive(Debug)]
+pub struct PriceImpactResult {
+    pub result: GetAmountOutResult,
+    pub spot_price: f64,
+    pub execution_price: f64,
+    pub price_impact_bps: f64,
+    pub marginal_price: f64,
+}
+
 #[derive(Debug)]
 pub struct BlockUpdate {
     pub block_number: u64,
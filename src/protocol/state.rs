@@ -8,6 +8,10 @@
 //!  - `fee`: Returns the protocol's fee as a ratio.
 //!  - `spot_price`: Returns the current spot price between two tokens.
 //!  - `get_amount_out`: Returns the amount of output tokens given an amount of input tokens.
+//!  - `get_amount_out_with_price_impact`: Like `get_amount_out`, also returning the execution
+//!    price, the price impact and the price after the trade.
+//!  - `get_depth`: Returns the trade moving the price by a given amount.
+//!  - `liquidity_depth`: Returns the trades moving the price by given amounts, in both directions.
 //!  - `delta_transition`: Applies a state delta to the simulated protocol.
 //!  - `clone_box`: Clones the simulated protocol state as a trait object.
 //!  - `as_any`: Allows downcasting of the trait object.
@@ -49,13 +53,14 @@ use std::{any::Any, collections::HashMap};
 #[cfg(test)]
 use mockall::mock;
 use num_bigint::BigUint;
+use num_traits::{One, ToPrimitive, Zero};
 use tycho_core::{dto::ProtocolStateDelta, Bytes};
 
 use crate::{
     models::Token,
     protocol::{
-        errors::{SimulationError, TransitionError},
-        models::GetAmountOutResult,
+        errors::{SimulationError, SimulationErrorCategory, TransitionError},
+        models::{DepthLevel, GetAmountOutResult, LiquidityDepth, PriceImpactResult},
     },
 };
 
@@ -104,6 +109,121 @@ pub trait ProtocolSim: std::fmt::Debug + Send + Sync + 'static {
         token_out: &Token,
     ) -> Result<GetAmountOutResult, SimulationError>;
 
+    /// Returns the amount out given an amount in and input/output tokens, together with the
+    /// execution price, the price impact and the marginal price after the trade.
+    ///
+    /// The marginal price is the spot price of the state after the trade, i.e. of
+    /// `GetAmountOutResult::new_state`.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount_in` - The amount in of the input token.
+    /// * `token_in` - The input token ERC20 token.
+    /// * `token_out` - The output token ERC20 token.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a `PriceImpactResult` struct on success or a
+    ///  `SimulationError` on failure.
+    fn get_amount_out_with_price_impact(
+        &self,
+        amount_in: BigUint,
+        token_in: &Token,
+        token_out: &Token,
+    ) -> Result<PriceImpactResult, SimulationError> {
+        if amount_in.is_zero() {
+            return Err(SimulationError::InvalidInput("Amount in cannot be zero".to_string(), None));
+        }
+        let spot_price = self.spot_price(token_in, token_out)?;
+        let result = self.get_amount_out(amount_in.clone(), token_in, token_out)?;
+        let marginal_price = result
+            .new_state
+            .spot_price(token_in, token_out)?;
+
+        let to_units = |amount: &BigUint, decimals: usize| {
+            amount.to_f64().unwrap_or(f64::INFINITY) / 10f64.powi(decimals as i32)
+        };
+        let execution_price =
+            to_units(&result.amount, token_out.decimals) / to_units(&amount_in, token_in.decimals);
+        let price_impact_bps = (spot_price - execution_price) / spot_price * 10_000.0;
+
+        Ok(PriceImpactResult {
+            result,
+            spot_price,
+            execution_price,
+            price_impact_bps,
+            marginal_price,
+        })
+    }
+
+    /// Returns the trade of `token_in` for `token_out` that moves the price of `token_in` in
+    /// `token_out` down by `price_move_bps` basis points.
+    ///
+    /// The price after the trade is the marginal price, i.e. the spot price of the new state. The
+    /// default implementation searches the amount numerically using `get_amount_out`, up to a
+    /// relative precision of `DEPTH_SEARCH_PRECISION_BPS`. Protocols with a closed form override
+    /// it.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_in` - The input token ERC20 token.
+    /// * `token_out` - The output token ERC20 token.
+    /// * `price_move_bps` - The price move in basis points, below 10000.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `DepthLevel`, or `None` if the price move can't be reached, e.g.
+    /// because the pool runs out of liquidity or the amount exceeds the pool's limit.
+    fn get_depth(
+        &self,
+        token_in: &Token,
+        token_out: &Token,
+        price_move_bps: u32,
+    ) -> Result<Option<DepthLevel>, SimulationError> {
+        search_depth(self, token_in, token_out, price_move_bps)
+    }
+
+    /// Returns the liquidity depth of the pool in both directions of a pair, i.e. the trades that
+    /// move the price by each of the given amounts.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_a` - The first token of the pair.
+    /// * `token_b` - The second token of the pair.
+    /// * `price_moves_bps` - The price moves in basis points, each between 1 and 9999.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a `LiquidityDepth` struct on success or a `SimulationError` on
+    /// failure.
+    fn liquidity_depth(
+        &self,
+        token_a: &Token,
+        token_b: &Token,
+        price_moves_bps: &[u32],
+    ) -> Result<LiquidityDepth, SimulationError> {
+        let mut price_moves_bps = price_moves_bps.to_vec();
+        price_moves_bps.sort_unstable();
+        price_moves_bps.dedup();
+        for &price_move_bps in &price_moves_bps {
+            check_price_move(price_move_bps)?;
+        }
+
+        let mut depth = LiquidityDepth::default();
+        for (token_in, token_out, levels) in
+            [(token_a, token_b, &mut depth.a_to_b), (token_b, token_a, &mut depth.b_to_a)]
+        {
+            for &price_move_bps in &price_moves_bps {
+                match self.get_depth(token_in, token_out, price_move_bps)? {
+                    Some(level) => levels.push(level),
+                    // Larger moves can't be reached either
+                    None => break,
+                }
+            }
+        }
+        Ok(depth)
+    }
+
     /// Decodes and applies a protocol state delta to the state
     ///
     /// Will error if the provided delta is missing any required attributes or if any of the
@@ -139,6 +259,93 @@ pub trait ProtocolSim: std::fmt::Debug + Send + Sync + 'static {
     fn eq(&self, other: &dyn ProtocolSim) -> bool;
 }
 
+/// Basis points in a whole
+const BPS: u32 = 10_000;
+
+/// Relative precision of the amounts found by the numerical search of `ProtocolSim::get_depth`
+pub const DEPTH_SEARCH_PRECISION_BPS: u32 = 1;
+
+/// Maximum number of trades simulated by the numerical search of `ProtocolSim::get_depth`
+const MAX_DEPTH_SEARCH_STEPS: usize = 256;
+
+/// Checks that a price move is between 1 and 9999 basis points.
+pub(crate) fn check_price_move(price_move_bps: u32) -> Result<(), SimulationError> {
+    if price_move_bps == 0 || price_move_bps >= BPS {
+        return Err(SimulationError::InvalidInput(
+            format!("Invalid price move of {} bps", price_move_bps),
+            None,
+        ));
+    }
+    Ok(())
+}
+
+/// Searches the amount of `token_in` moving the price by `price_move_bps` with `get_amount_out`.
+///
+/// The amount is doubled, starting from one whole token, until the price moves far enough, and
+/// then bisected. This assumes the marginal price decreases with the amount sold.
+fn search_depth<S: ProtocolSim + ?Sized>(
+    state: &S,
+    token_in: &Token,
+    token_out: &Token,
+    price_move_bps: u32,
+) -> Result<Option<DepthLevel>, SimulationError> {
+    check_price_move(price_move_bps)?;
+    let target_price =
+        state.spot_price(token_in, token_out)? * (1.0 - price_move_bps as f64 / BPS as f64);
+    // Sells `amount`, returning the result and whether the price moved far enough. `None` if the
+    // amount can't be sold.
+    let trade = |amount: &BigUint| -> Result<Option<(GetAmountOutResult, bool)>, SimulationError> {
+        match state.get_amount_out(amount.clone(), token_in, token_out) {
+            Ok(result) => {
+                let price = result
+                    .new_state
+                    .spot_price(token_in, token_out)?;
+                Ok(Some((result, price <= target_price)))
+            }
+            Err(err)
+                if err.category() == SimulationErrorCategory::InvalidInput ||
+                    matches!(err, SimulationError::InsufficientLiquidity { .. }) =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    };
+
+    let mut low = BigUint::zero();
+    let mut high = BigUint::from(10u32).pow(token_in.decimals as u32);
+    let mut steps = 0;
+    let mut best = loop {
+        if steps == MAX_DEPTH_SEARCH_STEPS {
+            return Ok(None);
+        }
+        steps += 1;
+        match trade(&high)? {
+            Some((result, true)) => break result,
+            Some((_, false)) => {
+                low = high.clone();
+                high *= 2u32;
+            }
+            None => return Ok(None),
+        }
+    };
+    while steps < MAX_DEPTH_SEARCH_STEPS &&
+        &high - &low > (&high * DEPTH_SEARCH_PRECISION_BPS / BPS).max(BigUint::one())
+    {
+        steps += 1;
+        let mid = (&low + &high) / 2u32;
+        match trade(&mid)? {
+            Some((result, true)) => {
+                high = mid;
+                best = result;
+            }
+            Some((_, false)) => low = mid,
+            None => break,
+        }
+    }
+    Ok(Some(DepthLevel { price_move_bps, amount_in: high, amount_out: best.amount }))
+}
+
 impl Clone for Box<dyn ProtocolSim> {
     fn clone(&self) -> Box<dyn ProtocolSim> {
         self.clone_box()
@@ -210,3 +417,120 @@ impl ProtocolSim for MockProtocolSim {
         self.eq(other)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reserve of the sold token in `constant_product_pool`
+    const RESERVE: f64 = 1_000_000.0;
+
+    fn token(address: &str) -> Token {
+        Token::new(address, 0, "TKN", BigUint::from(10_000u32))
+    }
+
+    /// Mock of a constant product pool without fees, after `sold` tokens were sold to it. Trades
+    /// report half the amount in as their output.
+    fn constant_product_pool(sold: f64) -> MockProtocolSim {
+        let mut pool = MockProtocolSim::new();
+        pool.expect_spot_price()
+            .returning(move |_, _| Ok((RESERVE / (RESERVE + sold)).powi(2)));
+        pool.expect_get_amount_out()
+            .returning(move |amount_in, _, _| {
+                let amount = amount_in.to_f64().unwrap();
+                Ok(GetAmountOutResult::new(
+                    amount_in / 2u32,
+                    BigUint::zero(),
+                    Box::new(constant_product_pool(sold + amount)),
+                ))
+            });
+        pool
+    }
+
+    #[test]
+    fn test_search_depth() {
+        let pool = constant_product_pool(0.0);
+        let (token_in, token_out) = (
+            token("0x0000000000000000000000000000000000000001"),
+            token("0x0000000000000000000000000000000000000002"),
+        );
+
+        // Doubles from 1 to 8192, then bisects down to the smallest amount moving the price
+        let level = pool
+            .get_depth(&token_in, &token_out, 100)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            level,
+            DepthLevel {
+                price_move_bps: 100,
+                amount_in: BigUint::from(5038u32),
+                amount_out: BigUint::from(2519u32)
+            }
+        );
+        assert!(pool
+            .get_depth(&token_in, &token_out, 0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_search_depth_unreachable() {
+        let (token_in, token_out) = (
+            token("0x0000000000000000000000000000000000000001"),
+            token("0x0000000000000000000000000000000000000002"),
+        );
+        // The pool runs out of liquidity
+        let mut pool = MockProtocolSim::new();
+        pool.expect_spot_price()
+            .returning(|_, _| Ok(1.0));
+        pool.expect_get_amount_out()
+            .returning(|_, _, _| {
+                Err(SimulationError::InsufficientLiquidity { partial_result: None })
+            });
+
+        assert_eq!(
+            pool.get_depth(&token_in, &token_out, 100)
+                .unwrap(),
+            None
+        );
+
+        // The price never moves
+        let mut pool = MockProtocolSim::new();
+        pool.expect_spot_price()
+            .returning(|_, _| Ok(1.0));
+        pool.expect_get_amount_out()
+            .times(MAX_DEPTH_SEARCH_STEPS)
+            .returning(|amount_in, _, _| {
+                let mut new_state = MockProtocolSim::new();
+                new_state
+                    .expect_spot_price()
+                    .returning(|_, _| Ok(1.0));
+                Ok(GetAmountOutResult::new(amount_in, BigUint::zero(), Box::new(new_state)))
+            });
+
+        assert_eq!(
+            pool.get_depth(&token_in, &token_out, 100)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_search_depth_fatal_error() {
+        let mut pool = MockProtocolSim::new();
+        pool.expect_spot_price()
+            .returning(|_, _| Ok(1.0));
+        pool.expect_get_amount_out()
+            .returning(|_, _, _| Err(SimulationError::FatalError("Broken pool".to_string())));
+
+        assert!(matches!(
+            pool.get_depth(
+                &token("0x0000000000000000000000000000000000000001"),
+                &token("0x0000000000000000000000000000000000000002"),
+                100
+            ),
+            Err(SimulationError::FatalError(_))
+        ));
+    }
+}
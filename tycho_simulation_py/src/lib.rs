@@ -1,8 +1,8 @@
 use pyo3::prelude::*;
 use simulation_py::SimulationEngine;
 use structs_py::{
-    AccountInfo, AccountUpdate, BlockHeader, SimulationDB, SimulationParameters, SimulationResult,
-    StateUpdate, TychoDB,
+    AccountInfo, AccountUpdate, BlockHeader, Log, SimulationDB, SimulationParameters,
+    SimulationResult, StateUpdate, TychoDB,
 };
 use tracing_subscriber::EnvFilter;
 
@@ -37,6 +37,7 @@ fn _tycho_simulation_py(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<SimulationEngine>()?;
     m.add_class::<SimulationParameters>()?;
     m.add_class::<SimulationResult>()?;
+    m.add_class::<Log>()?;
     m.add_class::<StateUpdate>()?;
     m.add_class::<BlockHeader>()?;
     m.add_class::<AccountInfo>()?;
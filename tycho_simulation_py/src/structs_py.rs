@@ -37,6 +37,14 @@ use tycho_simulation::evm::{
 ///     Block number available to the transaction
 /// timestamp: int
 ///     Timestamp value available to the transaction
+/// chain_id: Optional[int]
+///     Chain id returned by the ``CHAINID`` opcode. Defaults to Ethereum mainnet.
+/// basefee: Optional[int]
+///     Base fee of the block. Defaults to zero.
+/// coinbase: Optional[str]
+///     Beneficiary of the block. Defaults to the zero address.
+/// gas_price: Optional[int]
+///     Gas price paid by the transaction, at least ``basefee``. Defaults to zero.
 #[pyclass]
 #[derive(Clone, Debug)]
 pub struct SimulationParameters {
@@ -56,13 +64,21 @@ pub struct SimulationParameters {
     pub block_number: Option<u64>,
     #[pyo3(get)]
     pub timestamp: Option<u64>,
+    #[pyo3(get)]
+    pub chain_id: Option<u64>,
+    #[pyo3(get)]
+    pub basefee: Option<BigUint>,
+    #[pyo3(get)]
+    pub coinbase: Option<String>,
+    #[pyo3(get)]
+    pub gas_price: Option<BigUint>,
 }
 
 #[pymethods]
 impl SimulationParameters {
     #[new]
     #[pyo3(
-        text_signature = "(caller, to, data, value, overrides=None, gas_limit=None, block_number=0, timestamp=0)"
+        text_signature = "(caller, to, data, value, overrides=None, gas_limit=None, block_number=0, timestamp=0, chain_id=None, basefee=None, coinbase=None, gas_price=None)"
     )]
     #[allow(clippy::too_many_arguments)]
     fn new(
@@ -74,8 +90,25 @@ impl SimulationParameters {
         gas_limit: Option<u64>,
         block_number: Option<u64>,
         timestamp: Option<u64>,
+        chain_id: Option<u64>,
+        basefee: Option<BigUint>,
+        coinbase: Option<String>,
+        gas_price: Option<BigUint>,
     ) -> Self {
-        Self { caller, to, data, value, overrides, gas_limit, block_number, timestamp }
+        Self {
+            caller,
+            to,
+            data,
+            value,
+            overrides,
+            gas_limit,
+            block_number,
+            timestamp,
+            chain_id,
+            basefee,
+            coinbase,
+            gas_price,
+        }
     }
 
     fn __repr__(&self) -> String {
@@ -105,15 +138,30 @@ impl From<SimulationParameters> for simulation::SimulationParameters {
             }
             None => None,
         };
+        let mut env = simulation::SimulationEnvironment::default();
+        if let Some(chain_id) = params.chain_id {
+            env.chain_id = chain_id;
+        }
+        if let Some(basefee) = params.basefee {
+            env.basefee = U256::from_be_slice(basefee.to_bytes_be().as_slice());
+        }
+        if let Some(coinbase) = params.coinbase {
+            env.coinbase = RevmAddress::from_str(coinbase.as_str()).expect("Wrong address format");
+        }
+        if let Some(gas_price) = params.gas_price {
+            env.gas_price = U256::from_be_slice(gas_price.to_bytes_be().as_slice());
+        }
         simulation::SimulationParameters {
             caller: RevmAddress::from_str(params.caller.as_str()).unwrap(),
             to: RevmAddress::from_str(params.to.as_str()).unwrap(),
             data: params.data,
             value: U256::from_be_slice(params.value.to_bytes_be().as_slice()),
             overrides,
+            account_overrides: None,
             gas_limit: params.gas_limit,
             block_number: params.block_number.unwrap_or(0),
             timestamp: params.timestamp.unwrap_or(0),
+            env,
         }
     }
 }
@@ -285,6 +333,50 @@ impl From<AccountUpdate> for tycho_models::AccountUpdate {
     }
 }
 
+/// A log emitted by a simulated transaction
+///
+/// Attributes
+/// ----------
+///
+/// address: str
+///     Address of the contract that emitted the log
+/// topics: list[bytearray]
+///     Indexed topics of the log. The first topic usually is the event signature hash
+/// data: bytearray
+///     Non-indexed data of the log
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct Log {
+    #[pyo3(get)]
+    pub address: String,
+    #[pyo3(get)]
+    pub topics: Vec<Vec<u8>>,
+    #[pyo3(get)]
+    pub data: Vec<u8>,
+}
+
+#[pymethods]
+impl Log {
+    fn __repr__(&self) -> String {
+        format!("{:#?}", self)
+    }
+}
+
+impl From<revm::primitives::Log> for Log {
+    fn from(rust_log: revm::primitives::Log) -> Self {
+        Log {
+            address: format!("{:#x}", rust_log.address),
+            topics: rust_log
+                .data
+                .topics()
+                .iter()
+                .map(|topic| topic.to_vec())
+                .collect(),
+            data: rust_log.data.data.to_vec(),
+        }
+    }
+}
+
 /// The result of a successful simulation
 ///
 /// Attributes
@@ -296,6 +388,8 @@ impl From<AccountUpdate> for tycho_models::AccountUpdate {
 ///     State changes caused by the transaction
 /// gas_used: int
 ///     Gas used by the transaction (already reduced by the refunded gas)
+/// logs: list[Log]
+///     Logs emitted by the transaction, in order of emission
 #[pyclass]
 #[derive(Clone, Debug)]
 pub struct SimulationResult {
@@ -305,6 +399,8 @@ pub struct SimulationResult {
     pub state_updates: HashMap<String, StateUpdate>,
     #[pyo3(get)]
     pub gas_used: u64,
+    #[pyo3(get)]
+    pub logs: Vec<Log>,
 }
 
 #[pymethods]
@@ -324,6 +420,11 @@ impl From<simulation::SimulationResult> for SimulationResult {
             result: rust_result.result.into(),
             state_updates: py_state_updates,
             gas_used: rust_result.gas_used,
+            logs: rust_result
+                .logs
+                .into_iter()
+                .map(Log::from)
+                .collect(),
         }
     }
 }
@@ -411,6 +512,7 @@ impl From<BlockHeader> for tycho_simulation::evm::engine_db::simulation_db::Bloc
             number: py_header.number,
             hash: B256::from_str(&py_header.hash).unwrap(),
             timestamp: py_header.timestamp,
+            ..Default::default()
         }
     }
 }